@@ -2,20 +2,35 @@ use async_trait::async_trait;
 use mongodb::Database;
 use serde::{Deserialize, Serialize};
 
+use crate::domain::role::DataScope;
+
 pub trait RBACRole: Send {
     fn to_casbin_policy(&self) -> Vec<Vec<String>>;
+
+    /// returns the tenant, the name and the data scope of the role
+    fn data_scope(&self) -> (String, String, DataScope);
 }
 
 pub trait RBACUser: Send {
     fn account(&self) -> String;
 
-    fn role_name(&self) -> String;
+    /// returns the (tenant, role name) pairs granted to the user
+    fn roles(&self) -> Vec<(String, String)>;
 }
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     #[error("Fetcher Error from MongoDB: {0}")]
     DatabaseError(#[from] mongodb::error::Error),
+
+    #[error("Fetcher Error from file: {0}")]
+    FileError(#[from] std::io::Error),
+
+    #[error("Fetcher Error, can not parse policy file: {0}")]
+    ParseError(#[from] toml::de::Error),
+
+    #[error("Fetcher Error, invalid policy: {0}")]
+    InvalidPolicy(String),
 }
 
 /// fetch all roles from database
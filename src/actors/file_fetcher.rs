@@ -0,0 +1,283 @@
+use std::collections::HashSet;
+
+use async_trait::async_trait;
+use mongodb::Database;
+use serde::Deserialize;
+
+use crate::domain::{
+    common::default_tenant,
+    role::{DataScope, Effect, Role, RouteItem},
+};
+
+use super::fetcher::{Error, RBACRole, RBACRoleFetcher, RBACUser, RBACUserFetcher};
+
+/// a policy file in toml format
+///
+/// it defines the roles and who holds them, the accounts still sign in with the users
+/// of the database.
+///
+/// ```toml
+/// [[roles]]
+/// name = "admin"
+/// tenant = "default" # optional
+/// permissions = [{ path = "role:*" }, { path = "role:write", effect = "deny" }]
+/// data_scope = { kind = "department" } # optional, all by default
+///
+/// [[users]]
+/// account = "zhangsan"
+/// roles = [{ role = "admin" }, { tenant = "shop", role = "viewer" }]
+/// ```
+#[derive(Debug, Deserialize)]
+pub struct PolicyFile {
+    #[serde(default)]
+    pub roles: Vec<FileRole>,
+    #[serde(default)]
+    pub users: Vec<FileUser>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FileRole {
+    pub name: String,
+    #[serde(default = "default_tenant")]
+    pub tenant: String,
+    #[serde(default)]
+    pub permissions: Vec<FilePermission>,
+    #[serde(default)]
+    pub data_scope: DataScope,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FilePermission {
+    pub path: String,
+    #[serde(default)]
+    pub effect: Effect,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct FileUser {
+    pub account: String,
+    #[serde(default)]
+    pub roles: Vec<FileAssignment>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct FileAssignment {
+    #[serde(default = "default_tenant")]
+    pub tenant: String,
+    pub role: String,
+}
+
+impl PolicyFile {
+    /// parse and validate a policy file
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the content is not valid toml or the
+    /// polices are not consistent, see [PolicyFile::validate].
+    pub fn parse(content: &str) -> Result<Self, Error> {
+        let file: PolicyFile = toml::from_str(content)?;
+        file.validate()?;
+        Ok(file)
+    }
+
+    /// check that names are not empty, role names are unique inside a tenant
+    /// and users only reference roles that exist in the tenant.
+    pub fn validate(&self) -> Result<(), Error> {
+        let mut roles = HashSet::new();
+
+        for role in self.roles.iter() {
+            if role.name.is_empty() {
+                return Err(Error::InvalidPolicy("role name is empty".to_string()));
+            }
+
+            if !roles.insert((role.tenant.as_str(), role.name.as_str())) {
+                return Err(Error::InvalidPolicy(format!(
+                    "role {} is defined twice in tenant {}",
+                    role.name, role.tenant
+                )));
+            }
+
+            if role.permissions.iter().any(|p| p.path.is_empty()) {
+                return Err(Error::InvalidPolicy(format!(
+                    "role {} has an empty permission",
+                    role.name
+                )));
+            }
+        }
+
+        for user in self.users.iter() {
+            if user.account.is_empty() {
+                return Err(Error::InvalidPolicy("user account is empty".to_string()));
+            }
+
+            for assignment in user.roles.iter() {
+                if !roles.contains(&(assignment.tenant.as_str(), assignment.role.as_str())) {
+                    return Err(Error::InvalidPolicy(format!(
+                        "user {} references unknown role {} in tenant {}",
+                        user.account, assignment.role, assignment.tenant
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl From<&FileRole> for Role {
+    fn from(role: &FileRole) -> Self {
+        let permissions = role
+            .permissions
+            .iter()
+            .map(|permission| RouteItem {
+                module: "".to_string(),
+                path: permission.path.clone(),
+                description: "".to_string(),
+                effect: permission.effect,
+            })
+            .collect();
+
+        Role::new(
+            format!("file:{}:{}", role.tenant, role.name),
+            role.tenant.clone(),
+            role.name.clone(),
+            permissions,
+            role.data_scope.clone(),
+        )
+    }
+}
+
+impl RBACUser for FileUser {
+    fn account(&self) -> String {
+        self.account.clone()
+    }
+
+    fn roles(&self) -> Vec<(String, String)> {
+        self.roles
+            .iter()
+            .map(|item| (item.tenant.clone(), item.role.clone()))
+            .collect()
+    }
+}
+
+/// fetch roles and users from a policy file instead of the database.
+///
+/// the file is read again on every reload, so edits are picked up by a reset.
+#[derive(Debug, Clone)]
+pub struct FilePolicyFetcher {
+    path: String,
+}
+
+impl FilePolicyFetcher {
+    /// returns a fetcher for the file, after checking that it can be loaded
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the file can not be read or is invalid.
+    pub async fn open(path: &str) -> Result<Self, Error> {
+        let fetcher = FilePolicyFetcher {
+            path: path.to_string(),
+        };
+        fetcher.load().await?;
+
+        Ok(fetcher)
+    }
+
+    async fn load(&self) -> Result<PolicyFile, Error> {
+        let content = tokio::fs::read_to_string(&self.path).await?;
+        PolicyFile::parse(&content)
+    }
+}
+
+#[async_trait]
+impl RBACRoleFetcher for FilePolicyFetcher {
+    async fn find_all(&self, _: &Database) -> Result<Vec<Box<dyn RBACRole>>, Error> {
+        let file = self.load().await?;
+
+        Ok(file
+            .roles
+            .iter()
+            .map(|role| Box::new(Role::from(role)) as Box<dyn RBACRole>)
+            .collect())
+    }
+}
+
+#[async_trait]
+impl RBACUserFetcher for FilePolicyFetcher {
+    async fn find_all(&self, _: &Database) -> Result<Vec<Box<dyn RBACUser>>, Error> {
+        let file = self.load().await?;
+
+        Ok(file
+            .users
+            .into_iter()
+            .map(|user| Box::new(user) as Box<dyn RBACUser>)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONTENT: &str = r#"
+[[roles]]
+name = "admin"
+permissions = [{ path = "role:*" }, { path = "role:write", effect = "deny" }]
+
+[[roles]]
+name = "viewer"
+tenant = "shop"
+data_scope = { kind = "own" }
+
+[[users]]
+account = "zhangsan"
+roles = [{ role = "admin" }, { tenant = "shop", role = "viewer" }]
+"#;
+
+    #[test]
+    fn test_parse_policy_file() {
+        let file = PolicyFile::parse(CONTENT).unwrap();
+
+        let policies = Role::from(&file.roles[0]).to_casbin_policy();
+        assert_eq!(policies[1], vec!["admin", "default", "role:write", "deny"]);
+        assert_eq!(Role::from(&file.roles[0]).data_scope, DataScope::All);
+        assert_eq!(
+            Role::from(&file.roles[1]).data_scope(),
+            ("shop".to_string(), "viewer".to_string(), DataScope::Own)
+        );
+
+        assert_eq!(
+            file.users[0].roles(),
+            vec![
+                ("default".to_string(), "admin".to_string()),
+                ("shop".to_string(), "viewer".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_validate_policy_file() {
+        // the role exists in another tenant only
+        let unknown_role = r#"
+[[roles]]
+name = "viewer"
+tenant = "shop"
+
+[[users]]
+account = "zhangsan"
+roles = [{ role = "viewer" }]
+"#;
+        assert!(PolicyFile::parse(unknown_role).is_err());
+
+        let duplicated = r#"
+[[roles]]
+name = "admin"
+
+[[roles]]
+name = "admin"
+"#;
+        assert!(PolicyFile::parse(duplicated).is_err());
+
+        assert!(PolicyFile::parse("roles = 1").is_err());
+    }
+}
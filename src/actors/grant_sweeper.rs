@@ -0,0 +1,74 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use mongodb::Database;
+
+use crate::database::repositories::user::UserRepository;
+
+use super::rbac::RbacActorHandler;
+
+/// drop expired role grants and reload the polices when grants start or end,
+/// and expire the accounts past their expiry date.
+///
+/// grants outside their period are already ignored when the polices are loaded,
+/// the sweep makes a period boundary take effect within `interval`. expired accounts
+/// are refused before the sweep as well, it records their transition.
+pub fn spawn(
+    database: Database,
+    repository: UserRepository,
+    rbac: RbacActorHandler,
+    interval: Duration,
+) {
+    tokio::spawn(async move {
+        let mut last_sweep = now();
+
+        loop {
+            tokio::time::sleep(interval).await;
+            let now = now();
+
+            match repository.expire_accounts(now, &database).await {
+                Ok(0) => {}
+                Ok(expired) => println!("{} accounts expired", expired),
+                Err(err) => println!("Failed to expire accounts: {}", err),
+            }
+
+            match sweep(&repository, &database, last_sweep, now).await {
+                Ok(0) => {}
+                Ok(changed) => {
+                    println!(
+                        "{} users have grants starting or ending, reloading polices",
+                        changed
+                    );
+                    if let Err(err) = rbac.reset().await {
+                        println!("Failed to reset rbac polices: {}", err);
+                    }
+                }
+                Err(err) => {
+                    println!("Failed to sweep role grants: {}", err);
+                    continue;
+                }
+            }
+
+            last_sweep = now;
+        }
+    });
+}
+
+/// returns the number of users whose grants started or were removed.
+async fn sweep(
+    repository: &UserRepository,
+    database: &Database,
+    since: u64,
+    now: u64,
+) -> crate::database::errors::Result<u64> {
+    let expired = repository.remove_expired_grants(now, database).await?;
+    let started = repository
+        .count_started_grants(since, now, database)
+        .await?;
+
+    Ok(expired + started)
+}
+
+fn now() -> u64 {
+    Utc::now().timestamp() as u64
+}
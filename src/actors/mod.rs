@@ -1,3 +1,8 @@
 pub mod fetcher;
+pub mod file_fetcher;
+pub mod grant_sweeper;
 pub mod id_gen;
+pub mod mongo_adapter;
 pub mod rbac;
+pub mod rbac_watcher;
+pub mod sender;
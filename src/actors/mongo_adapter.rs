@@ -0,0 +1,292 @@
+use async_trait::async_trait;
+use casbin::{error::AdapterError, Adapter, Filter, Model};
+use futures_util::StreamExt;
+use mongodb::{
+    bson::{doc, Document},
+    Collection, Database,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::database::repositories::collection_names::CASBIN_RULE;
+
+/// a casbin rule as stored in the collection, in the layout used by casbin adapters
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CasbinRule {
+    pub ptype: String,
+    pub v0: String,
+    pub v1: String,
+    pub v2: String,
+    pub v3: String,
+    pub v4: String,
+    pub v5: String,
+}
+
+impl CasbinRule {
+    /// returns the rule of the policy type, missing values are left empty
+    pub fn new(ptype: &str, rule: &[String]) -> Self {
+        let value = |index: usize| rule.get(index).cloned().unwrap_or_default();
+
+        CasbinRule {
+            ptype: ptype.to_string(),
+            v0: value(0),
+            v1: value(1),
+            v2: value(2),
+            v3: value(3),
+            v4: value(4),
+            v5: value(5),
+        }
+    }
+
+    /// returns the values of the rule without the trailing empty ones
+    pub fn values(&self) -> Vec<String> {
+        let mut values = vec![
+            self.v0.clone(),
+            self.v1.clone(),
+            self.v2.clone(),
+            self.v3.clone(),
+            self.v4.clone(),
+            self.v5.clone(),
+        ];
+
+        while values.last().is_some_and(|value| value.is_empty()) {
+            values.pop();
+        }
+
+        values
+    }
+
+    /// returns the filter matching exactly this rule
+    fn to_doc(&self) -> Document {
+        doc! {
+            "ptype": self.ptype.clone(),
+            "v0": self.v0.clone(),
+            "v1": self.v1.clone(),
+            "v2": self.v2.clone(),
+            "v3": self.v3.clone(),
+            "v4": self.v4.clone(),
+            "v5": self.v5.clone(),
+        }
+    }
+}
+
+/// match the non empty values in the filter, the first value is the field at `field_index`
+fn values_filter(filter: &mut Document, field_index: usize, values: &[String]) {
+    for (offset, value) in values.iter().enumerate() {
+        if !value.is_empty() {
+            filter.insert(format!("v{}", field_index + offset), value.clone());
+        }
+    }
+}
+
+fn adapter_error(err: mongodb::error::Error) -> casbin::Error {
+    AdapterError(Box::new(err)).into()
+}
+
+/// a casbin adapter storing rules in the `casbin_rules` collection
+#[derive(Clone)]
+pub struct MongoAdapter {
+    collection: Collection<CasbinRule>,
+    is_filtered: bool,
+}
+
+impl MongoAdapter {
+    pub fn new(database: &Database) -> Self {
+        MongoAdapter {
+            collection: database.collection(CASBIN_RULE),
+            is_filtered: false,
+        }
+    }
+
+    async fn load(&self, m: &mut dyn Model, filter: Document) -> casbin::Result<()> {
+        let mut cursor = self
+            .collection
+            .find(filter, None)
+            .await
+            .map_err(adapter_error)?;
+
+        while let Some(rule) = cursor.next().await {
+            let rule = rule.map_err(adapter_error)?;
+            let Some(sec) = rule.ptype.get(..1) else {
+                continue;
+            };
+
+            m.add_policy(sec, &rule.ptype, rule.values());
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Adapter for MongoAdapter {
+    async fn load_policy(&mut self, m: &mut dyn Model) -> casbin::Result<()> {
+        self.is_filtered = false;
+        self.load(m, Document::new()).await
+    }
+
+    async fn load_filtered_policy<'a>(
+        &mut self,
+        m: &mut dyn Model,
+        f: Filter<'a>,
+    ) -> casbin::Result<()> {
+        let mut conditions = vec![];
+        for (sec, values) in [("p", &f.p), ("g", &f.g)] {
+            let mut filter = doc! { "ptype": { "$regex": format!("^{}", sec) } };
+            let values: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+            values_filter(&mut filter, 0, &values);
+            conditions.push(filter);
+        }
+
+        self.is_filtered = true;
+        self.load(m, doc! { "$or": conditions }).await
+    }
+
+    async fn save_policy(&mut self, m: &mut dyn Model) -> casbin::Result<()> {
+        let mut rules = vec![];
+        for sec in ["p", "g"] {
+            if let Some(ast_map) = m.get_model().get(sec) {
+                for (ptype, ast) in ast_map {
+                    for rule in ast.get_policy() {
+                        rules.push(CasbinRule::new(ptype, rule));
+                    }
+                }
+            }
+        }
+
+        self.clear_policy().await?;
+        if !rules.is_empty() {
+            self.collection
+                .insert_many(rules, None)
+                .await
+                .map_err(adapter_error)?;
+        }
+
+        Ok(())
+    }
+
+    async fn clear_policy(&mut self) -> casbin::Result<()> {
+        self.collection
+            .delete_many(Document::new(), None)
+            .await
+            .map_err(adapter_error)?;
+
+        Ok(())
+    }
+
+    fn is_filtered(&self) -> bool {
+        self.is_filtered
+    }
+
+    async fn add_policy(
+        &mut self,
+        _sec: &str,
+        ptype: &str,
+        rule: Vec<String>,
+    ) -> casbin::Result<bool> {
+        self.collection
+            .insert_one(CasbinRule::new(ptype, &rule), None)
+            .await
+            .map_err(adapter_error)?;
+
+        Ok(true)
+    }
+
+    async fn add_policies(
+        &mut self,
+        _sec: &str,
+        ptype: &str,
+        rules: Vec<Vec<String>>,
+    ) -> casbin::Result<bool> {
+        if rules.is_empty() {
+            return Ok(true);
+        }
+
+        let rules: Vec<CasbinRule> = rules
+            .iter()
+            .map(|rule| CasbinRule::new(ptype, rule))
+            .collect();
+        self.collection
+            .insert_many(rules, None)
+            .await
+            .map_err(adapter_error)?;
+
+        Ok(true)
+    }
+
+    async fn remove_policy(
+        &mut self,
+        _sec: &str,
+        ptype: &str,
+        rule: Vec<String>,
+    ) -> casbin::Result<bool> {
+        let result = self
+            .collection
+            .delete_one(CasbinRule::new(ptype, &rule).to_doc(), None)
+            .await
+            .map_err(adapter_error)?;
+
+        Ok(result.deleted_count > 0)
+    }
+
+    async fn remove_policies(
+        &mut self,
+        sec: &str,
+        ptype: &str,
+        rules: Vec<Vec<String>>,
+    ) -> casbin::Result<bool> {
+        let mut removed = true;
+        for rule in rules {
+            removed &= self.remove_policy(sec, ptype, rule).await?;
+        }
+
+        Ok(removed)
+    }
+
+    async fn remove_filtered_policy(
+        &mut self,
+        _sec: &str,
+        ptype: &str,
+        field_index: usize,
+        field_values: Vec<String>,
+    ) -> casbin::Result<bool> {
+        let mut filter = doc! { "ptype": ptype };
+        values_filter(&mut filter, field_index, &field_values);
+
+        let result = self
+            .collection
+            .delete_many(filter, None)
+            .await
+            .map_err(adapter_error)?;
+
+        Ok(result.deleted_count > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rule_values() {
+        let values = vec![
+            "alice".to_string(),
+            "default".to_string(),
+            "report:read".to_string(),
+            "allow".to_string(),
+        ];
+        let rule = CasbinRule::new("p", &values);
+
+        assert_eq!(rule.v3, "allow");
+        assert_eq!(rule.v4, "");
+        assert_eq!(rule.values(), values);
+    }
+
+    #[test]
+    fn test_values_filter() {
+        let mut filter = doc! { "ptype": "p" };
+        values_filter(&mut filter, 1, &["".to_string(), "report:read".to_string()]);
+
+        assert_eq!(filter, doc! { "ptype": "p", "v2": "report:read" });
+    }
+}
@@ -1,31 +1,86 @@
-use casbin::{CoreApi, Enforcer, MgmtApi, RbacApi};
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use arc_swap::ArcSwap;
+use casbin::{
+    function_map::key_match2, rhai::ImmutableString, CoreApi, Enforcer, MgmtApi, RbacApi,
+};
 
 use mongodb::Database;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use tokio::sync::{
     mpsc::{self, Receiver},
     oneshot,
 };
 
+/// the built-in casbin model, used when no model file is configured
 const MODEL: &str = r#"
 [request_definition]
-r = sub, action
+r = sub, dom, path
 
 [policy_definition]
-p = sub, action
+p = sub, dom, path, eft
 
 [role_definition]
-g = _, _
+g = _, _, _
 
 [policy_effect]
-e = some(where (p.eft == allow))
+e = some(where (p.eft == allow)) && !some(where (p.eft == deny))
 
 [matchers]
-m = g(r.sub, p.sub) && r.action == p.action || r.sub == "bozzasggmy"
+m = g(r.sub, p.sub, r.dom) && r.dom == p.dom && permissionMatch(r.path, p.path)
 "#;
 
-use crate::database::{self};
+/// separates the permissions of a request checked together, see [route_permission]
+const ALTERNATIVE_SEPARATOR: char = ' ';
+
+/// returns the request of a handler that requires the capability on a route path.
+///
+/// a rule covering either of them grants the request and a deny rule covering either
+/// refuses it, so path rules such as `/admin/*` keep working next to capabilities.
+pub fn route_permission(capability: &str, path: &str) -> String {
+    format!("{}{}{}", capability, ALTERNATIVE_SEPARATOR, path)
+}
+
+/// returns true if the permission pattern of a policy covers the requested permission,
+/// or any of them for a request built by [route_permission].
+///
+/// patterns starting with `/` are route paths matched like casbin's `keyMatch2`,
+/// any other pattern is a capability name such as `role:write`, where `*` covers
+/// every capability and `role:*` every capability of the module.
+pub fn permission_match(permission: &str, pattern: &str) -> bool {
+    permission
+        .split(ALTERNATIVE_SEPARATOR)
+        .any(|permission| matches_pattern(permission, pattern))
+}
+
+fn matches_pattern(permission: &str, pattern: &str) -> bool {
+    if pattern.starts_with('/') {
+        return key_match2(permission, pattern);
+    }
+
+    match pattern.strip_suffix('*') {
+        Some(prefix) => {
+            (prefix.is_empty() || prefix.ends_with(':')) && permission.starts_with(prefix)
+        }
+        None => permission == pattern,
+    }
+}
+
+/// account that passes every permission check.
+///
+/// checked outside the model, a matcher clause would also match deny rules.
+pub const SUPERUSER: &str = "bozzasggmy";
+
+use crate::{
+    database::{self},
+    domain::role::{DataScope, Effect},
+};
 
-use super::fetcher::{self, RBACRole, RBACRoleFetcher, RBACUser, RBACUserFetcher};
+use super::{
+    fetcher::{self, RBACRole, RBACRoleFetcher, RBACUser, RBACUserFetcher},
+    mongo_adapter::MongoAdapter,
+};
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
@@ -40,6 +95,12 @@ pub enum Error {
 
     #[error("Fetcher error: {0}")]
     FetcherError(#[from] fetcher::Error),
+
+    #[error("Invalid rule: {0}")]
+    InvalidRule(String),
+
+    #[error("the policy adapter is not enabled")]
+    AdapterDisabled,
 }
 
 impl From<String> for Error {
@@ -49,92 +110,487 @@ impl From<String> for Error {
 }
 
 /// command for rbac actor
+///
+/// permission checks do not go through the actor, they read the current [PolicySnapshot].
 pub enum Command {
-    /// check permission
-    CheckPermission {
-        user: String,
-        action: String,
-        respond_to: oneshot::Sender<bool>,
-    },
-    Reset,
+    /// reload all polices from the fetchers, the result is sent back
+    Reset(oneshot::Sender<Result<(), Error>>),
+    /// list the rules stored by the policy adapter
+    Rules(oneshot::Sender<Result<Vec<StoredRule>, Error>>),
+    /// store a rule through the policy adapter, replies false if it already exists
+    AddRule(StoredRule, oneshot::Sender<Result<bool, Error>>),
+    /// remove a stored rule, replies false if it does not exist
+    RemoveRule(StoredRule, oneshot::Sender<Result<bool, Error>>),
+}
+
+/// a rule stored by the policy adapter, added to the rules derived from roles and users
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StoredRule {
+    /// `p` for a policy `sub, dom, path, eft`, `g` for a grouping `user, role, dom`
+    pub ptype: String,
+    pub rule: Vec<String>,
+}
+
+impl StoredRule {
+    /// check the rule has the shape of the built-in model
+    pub fn validate(&self) -> Result<(), Error> {
+        let valid = match self.ptype.as_str() {
+            "p" => {
+                self.rule.len() == 4
+                    && (self.rule[3] == Effect::Allow.as_str()
+                        || self.rule[3] == Effect::Deny.as_str())
+            }
+            "g" => self.rule.len() == 3,
+            _ => false,
+        };
+
+        if !valid || self.rule.iter().any(|value| value.is_empty()) {
+            return Err(Error::InvalidRule(format!(
+                "{} {:?}, expected p = sub, dom, path, allow|deny or g = user, role, dom",
+                self.ptype, self.rule
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// returns the tenant the rule applies to, empty for a rule of another shape
+    pub fn domain(&self) -> &str {
+        let index = match self.ptype.as_str() {
+            "p" => 1,
+            "g" => 2,
+            _ => return "",
+        };
+
+        self.rule.get(index).map_or("", |value| value.as_str())
+    }
+}
+
+/// delays between attempts to load the polices
+#[derive(Debug, Clone, Copy)]
+pub struct Backoff {
+    /// attempts made at startup before the server starts in degraded mode
+    pub attempts: u32,
+    pub initial: Duration,
+    pub max: Duration,
+}
+
+impl Backoff {
+    /// returns the delay before the retry following the given number of failures,
+    /// doubling from `initial` up to `max`.
+    pub fn delay(&self, failures: u32) -> Duration {
+        let factor = 2u32.saturating_pow(failures.saturating_sub(1));
+        self.initial.saturating_mul(factor).min(self.max)
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff {
+            attempts: 5,
+            initial: Duration::from_secs(1),
+            max: Duration::from_secs(30),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RbacStatus {
+    /// the first load has not finished yet
+    Starting,
+    /// the last load succeeded
+    Ready,
+    /// the last load failed, the last good polices are still used if there are any
+    Degraded,
+}
+
+/// the state of the policy loading, exposed to readiness checks
+#[derive(Debug, Clone, Serialize)]
+pub struct RbacHealth {
+    pub status: RbacStatus,
+    /// version of the polices in use, empty if none were ever loaded
+    pub version: String,
+    /// unix timestamp of the last successful load
+    pub loaded_at: Option<i64>,
+    pub last_error: Option<String>,
+    /// failed loads since the last successful one
+    pub failures: u32,
+}
+
+impl RbacHealth {
+    /// returns true once a policy set has been loaded, even if later reloads failed.
+    pub fn is_ready(&self) -> bool {
+        self.loaded_at.is_some()
+    }
+}
+
+impl Default for RbacHealth {
+    fn default() -> Self {
+        RbacHealth {
+            status: RbacStatus::Starting,
+            version: String::new(),
+            loaded_at: None,
+            last_error: None,
+            failures: 0,
+        }
+    }
+}
+
+/// an immutable, fully loaded policy set.
+///
+/// the actor builds a new snapshot on every reload and swaps it in atomically,
+/// request tasks keep using the previous one until they load the pointer again.
+pub struct PolicySnapshot {
+    enforcer: Enforcer,
+    /// digest of the loaded polices, equal on every instance that loaded the same data
+    version: String,
+    /// data scopes of the roles by tenant and name
+    scopes: HashMap<(String, String), DataScope>,
+}
+
+impl PolicySnapshot {
+    /// returns the digest of the loaded polices
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// returns true if the user is allowed to access the path inside the tenant.
+    ///
+    /// a matching deny rule wins over any allow rule.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the enforcer fails to evaluate the request.
+    pub fn check(&self, user: &str, tenant: &str, path: &str) -> Result<bool, Error> {
+        if user == SUPERUSER {
+            return Ok(true);
+        }
+
+        Ok(self.enforcer.enforce((user, tenant, path))?)
+    }
+
+    /// returns the data scopes of the roles the user holds in the tenant.
+    ///
+    /// roles only known from stored rules have no data scope.
+    pub fn data_scopes(&self, user: &str, tenant: &str) -> Vec<DataScope> {
+        if user == SUPERUSER {
+            return vec![DataScope::All];
+        }
+
+        self.enforcer
+            .get_role_manager()
+            .read()
+            .get_roles(user, Some(tenant))
+            .into_iter()
+            .filter_map(|role| self.scopes.get(&(tenant.to_string(), role)).cloned())
+            .collect()
+    }
+
+    /// returns the decision for the request together with the roles and rules behind it.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the enforcer fails to evaluate the request.
+    pub fn explain(&self, user: &str, tenant: &str, path: &str) -> Result<Explanation, Error> {
+        let allowed = self.check(user, tenant, path)?;
+        let roles = self
+            .enforcer
+            .get_role_manager()
+            .read()
+            .get_roles(user, Some(tenant));
+
+        // stored rules may name the user directly
+        let mut rules = vec![];
+        for role in std::iter::once(&user.to_string()).chain(roles.iter()) {
+            for policy in self.enforcer.get_filtered_policy(0, vec![role.clone()]) {
+                let [_, policy_tenant, policy_path, effect] = policy.as_slice() else {
+                    continue;
+                };
+
+                rules.push(RuleExplanation {
+                    role: role.clone(),
+                    tenant: policy_tenant.clone(),
+                    path: policy_path.clone(),
+                    effect: effect.clone(),
+                    matched: policy_tenant == tenant && permission_match(path, policy_path),
+                });
+            }
+        }
+
+        Ok(Explanation {
+            allowed,
+            roles,
+            rules,
+        })
+    }
+}
+
+/// a policy rule of one of the user's roles and whether it matched the request
+#[derive(Debug, Clone, Serialize)]
+pub struct RuleExplanation {
+    pub role: String,
+    pub tenant: String,
+    pub path: String,
+    /// allow or deny
+    pub effect: String,
+    pub matched: bool,
+}
+
+/// the decision of a permission check and the rules it was based on
+#[derive(Debug, Clone, Serialize)]
+pub struct Explanation {
+    pub allowed: bool,
+    /// roles of the user inside the requested tenant
+    pub roles: Vec<String>,
+    pub rules: Vec<RuleExplanation>,
 }
 
 struct RbacActor<R: RBACRoleFetcher, U: RBACUserFetcher> {
     receiver: Receiver<Command>,
     database: Database,
-    enforcer: Enforcer,
+    /// casbin model text every snapshot is built from
+    model: String,
+    snapshot: Arc<ArcSwap<PolicySnapshot>>,
+    health: Arc<ArcSwap<RbacHealth>>,
+    backoff: Backoff,
+    /// stores rules that are not derived from roles and users, if enabled
+    adapter: Option<MongoAdapter>,
+    /// enforcer over the stored rules only, used for the management api
+    stored: Option<Enforcer>,
     role_fetcher: R,
     user_fetcher: U,
 }
 
 impl<R: RBACRoleFetcher, U: RBACUserFetcher> RbacActor<R, U> {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         receiver: Receiver<Command>,
         database: Database,
-        enforcer: Enforcer,
+        model: String,
+        snapshot: Arc<ArcSwap<PolicySnapshot>>,
+        health: Arc<ArcSwap<RbacHealth>>,
+        backoff: Backoff,
+        adapter: Option<MongoAdapter>,
         role_fetcher: R,
         user_fetcher: U,
     ) -> Self {
         RbacActor {
             receiver,
             database,
-            enforcer,
+            model,
+            snapshot,
+            health,
+            backoff,
+            adapter,
+            stored: None,
             role_fetcher,
             user_fetcher,
         }
     }
 
-    async fn load_polices(&mut self) -> Result<(), Error> {
-        if let Err(err) = self.enforcer.clear_policy().await {
-            println!("Failed to clear polices: {}", err);
+    /// returns the enforcer over the stored rules, created on first use.
+    async fn stored(&mut self) -> Result<&mut Enforcer, Error> {
+        let adapter = self.adapter.clone().ok_or(Error::AdapterDisabled)?;
+
+        if self.stored.is_none() {
+            let model = casbin::DefaultModel::from_str(&self.model).await?;
+            self.stored = Some(Enforcer::new(model, adapter).await?);
+        }
+
+        Ok(self.stored.as_mut().unwrap())
+    }
+
+    /// returns the stored rules, read again from the database
+    async fn stored_rules(&mut self) -> Result<Vec<StoredRule>, Error> {
+        let stored = self.stored().await?;
+        stored.load_policy().await?;
+
+        let policies = stored.get_policy().into_iter().map(|rule| StoredRule {
+            ptype: "p".to_string(),
+            rule,
+        });
+        let groupings = stored
+            .get_grouping_policy()
+            .into_iter()
+            .map(|rule| StoredRule {
+                ptype: "g".to_string(),
+                rule,
+            });
+
+        Ok(policies.chain(groupings).collect())
+    }
+
+    /// add or remove a stored rule through casbin's management api, then reload.
+    async fn edit_rule(&mut self, rule: StoredRule, add: bool) -> Result<bool, Error> {
+        rule.validate()?;
+
+        let stored = self.stored().await?;
+        stored.load_policy().await?;
+
+        let changed = match (rule.ptype.as_str(), add) {
+            ("p", true) => stored.add_policy(rule.rule).await?,
+            ("p", false) => stored.remove_policy(rule.rule).await?,
+            (_, true) => stored.add_grouping_policy(rule.rule).await?,
+            (_, false) => stored.remove_grouping_policy(rule.rule).await?,
+        };
+
+        if changed {
+            self.reload().await?;
         }
 
+        Ok(changed)
+    }
+
+    /// load the polices and record the outcome in the health status.
+    ///
+    /// on failure the current snapshot is kept.
+    async fn reload(&mut self) -> Result<(), Error> {
+        let result = self.load_polices().await;
+        let previous = self.health.load_full();
+
+        let health = match &result {
+            Ok(()) => RbacHealth {
+                status: RbacStatus::Ready,
+                version: self.snapshot.load().version().to_string(),
+                loaded_at: Some(chrono::Utc::now().timestamp()),
+                last_error: None,
+                failures: 0,
+            },
+            Err(err) => RbacHealth {
+                status: RbacStatus::Degraded,
+                last_error: Some(err.to_string()),
+                failures: previous.failures + 1,
+                ..(*previous).clone()
+            },
+        };
+        self.health.store(Arc::new(health));
+
+        result
+    }
+
+    /// try to load the polices up to `backoff.attempts` times.
+    async fn reload_with_retry(&mut self) -> Result<(), Error> {
+        let mut attempt = 1;
+
+        loop {
+            match self.reload().await {
+                Ok(()) => return Ok(()),
+                Err(err) if attempt >= self.backoff.attempts => return Err(err),
+                Err(err) => {
+                    let delay = self.backoff.delay(attempt);
+                    println!(
+                        "load rbac polices failed (attempt {}), retry in {:?}: {}",
+                        attempt, delay, err
+                    );
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    /// load all polices into a new enforcer and publish it as the current snapshot.
+    async fn load_polices(&mut self) -> Result<(), Error> {
+        let mut enforcer = create_enforcer(&self.model).await?;
+
         let all_roles: Vec<Box<dyn RBACRole>> = self.role_fetcher.find_all(&self.database).await?;
         let all_users: Vec<Box<dyn RBACUser>> = self.user_fetcher.find_all(&self.database).await?;
         let roles_len = all_roles.len();
         let users_len = all_users.len();
 
+        let mut lines: Vec<String> = vec![];
+        let mut scopes = HashMap::new();
+
+        if self.adapter.is_some() {
+            for rule in self.stored_rules().await? {
+                lines.push(format!("{},{}", rule.ptype, rule.rule.join(",")));
+                match rule.ptype.as_str() {
+                    "p" => enforcer.add_policy(rule.rule).await?,
+                    _ => enforcer.add_grouping_policy(rule.rule).await?,
+                };
+            }
+        }
+
         for role in all_roles {
             for policy in role.to_casbin_policy() {
                 println!("policy: {:?}", policy);
-                self.enforcer.add_policy(policy).await?;
+                lines.push(format!("p,{}", policy.join(",")));
+                enforcer.add_policy(policy).await?;
             }
+
+            let (tenant, name, scope) = role.data_scope();
+            lines.push(format!(
+                "s,{},{},{}",
+                tenant,
+                name,
+                serde_json::to_string(&scope).unwrap_or_default()
+            ));
+            scopes.insert((tenant, name), scope);
         }
 
         for user in all_users {
-            self.enforcer
-                .add_role_for_user(&user.account(), &user.role_name(), None)
-                .await?;
+            let account = user.account();
+            for (tenant, role) in user.roles() {
+                lines.push(format!("g,{},{},{}", account, role, tenant));
+                enforcer
+                    .add_role_for_user(&account, &role, Some(&tenant))
+                    .await?;
+            }
         }
 
-        println!("load {} roles and {} users", roles_len, users_len);
+        let version = policy_digest(lines);
+
+        println!(
+            "load {} roles and {} users, policy version: {}",
+            roles_len, users_len, version
+        );
+
+        self.snapshot.store(Arc::new(PolicySnapshot {
+            enforcer,
+            version,
+            scopes,
+        }));
 
         Ok(())
     }
 
-    async fn handle_message(&mut self, command: Command) -> Result<(), Error> {
+    async fn handle_message(&mut self, command: Command) {
         match command {
-            Command::CheckPermission {
-                user,
-                action,
-                respond_to,
-            } => {
-                let is_ok = self.enforcer.enforce((user, action))?;
-
-                respond_to.send(is_ok).map_err(|err| err.to_string())?;
+            // the callers may have given up waiting
+            Command::Reset(reply) => {
+                let _ = reply.send(self.reload().await);
+            }
+            Command::Rules(reply) => {
+                let _ = reply.send(self.stored_rules().await);
+            }
+            Command::AddRule(rule, reply) => {
+                let _ = reply.send(self.edit_rule(rule, true).await);
+            }
+            Command::RemoveRule(rule, reply) => {
+                let _ = reply.send(self.edit_rule(rule, false).await);
             }
-
-            Command::Reset => self.load_polices().await?,
         }
-
-        Ok(())
     }
 }
 
+/// handle commands, and while the last load failed retry it in the background with backoff.
 async fn run_actor<R: RBACRoleFetcher, U: RBACUserFetcher>(mut actor: RbacActor<R, U>) {
-    while let Some(command) = actor.receiver.recv().await {
-        if let Err(err) = actor.handle_message(command).await {
-            println!("Failed to handle message: {}", err);
+    loop {
+        let health = actor.health.load_full();
+        let retry = health.status == RbacStatus::Degraded;
+        let delay = actor.backoff.delay(health.failures);
+
+        tokio::select! {
+            command = actor.receiver.recv() => match command {
+                Some(command) => actor.handle_message(command).await,
+                None => return,
+            },
+            _ = tokio::time::sleep(delay), if retry => {
+                if actor.reload().await.is_ok() {
+                    println!("rbac polices recovered, version: {}", actor.health.load().version);
+                }
+            }
         }
     }
 }
@@ -142,80 +598,243 @@ async fn run_actor<R: RBACRoleFetcher, U: RBACUserFetcher>(mut actor: RbacActor<
 #[derive(Clone)]
 pub struct RbacActorHandler {
     sender: mpsc::Sender<Command>,
+    snapshot: Arc<ArcSwap<PolicySnapshot>>,
+    health: Arc<ArcSwap<RbacHealth>>,
 }
 
 impl RbacActorHandler {
     /// returns a handler for the [RbacActor]
     ///
-    /// # Panics
+    /// loading the polices is retried with backoff. when every attempt fails the handler
+    /// starts in degraded mode, denying every request except the superuser's, and the
+    /// actor keeps retrying in the background.
+    ///
+    /// with an `adapter`, rules stored in the database are loaded together with the rules
+    /// derived from roles and users, and can be edited with [RbacActorHandler::add_rule].
+    ///
+    /// # Errors
     ///
-    /// Panics if
-    /// - casbin enforcer create failed.
-    /// - load polices from database failed.
-    pub async fn new<R, U>(database: Database, role_fetcher: R, user_fetcher: U) -> Self
+    /// This function will return an error if the casbin enforcer can not be created from the model.
+    pub async fn new<R, U>(
+        database: Database,
+        model: String,
+        backoff: Backoff,
+        adapter: Option<MongoAdapter>,
+        role_fetcher: R,
+        user_fetcher: U,
+    ) -> Result<Self, Error>
     where
         R: RBACRoleFetcher + 'static,
         U: RBACUserFetcher + 'static,
     {
         let (sender, receiver) = tokio::sync::mpsc::channel(100);
-        let casbin_enforcer = create_enforcer().await.unwrap();
+        let snapshot = Arc::new(ArcSwap::from_pointee(PolicySnapshot {
+            enforcer: create_enforcer(&model).await?,
+            version: String::new(),
+            scopes: HashMap::new(),
+        }));
+        let health = Arc::new(ArcSwap::from_pointee(RbacHealth::default()));
         let mut actor = RbacActor::new(
             receiver,
             database,
-            casbin_enforcer,
+            model,
+            snapshot.clone(),
+            health.clone(),
+            backoff,
+            adapter,
             role_fetcher,
             user_fetcher,
         );
-        actor.load_polices().await.unwrap();
+
+        if let Err(err) = actor.reload_with_retry().await {
+            println!(
+                "load rbac polices failed, starting in degraded mode: {}",
+                err
+            );
+        }
 
         tokio::spawn(run_actor(actor));
 
-        RbacActorHandler { sender }
+        Ok(RbacActorHandler {
+            sender,
+            snapshot,
+            health,
+        })
     }
 
-    pub async fn check_permission(&self, user: String, action: String) -> Result<bool, String> {
-        let (respond_to, response) = oneshot::channel();
-        self.sender
-            .send(Command::CheckPermission {
-                user,
-                action,
-                respond_to,
-            })
+    /// check the permission against the current policy snapshot.
+    ///
+    /// this does not wait for the actor, so any number of requests can check concurrently.
+    pub fn check_permission(&self, user: &str, tenant: &str, path: &str) -> Result<bool, String> {
+        self.snapshot
+            .load()
+            .check(user, tenant, path)
+            .map_err(|err| format! {"cannot check permission: {0}", err})
+    }
+
+    /// returns the data scopes of the user's roles in the tenant from the current snapshot,
+    /// the roles come from the configured fetchers like the permissions.
+    pub fn data_scopes(&self, user: &str, tenant: &str) -> Vec<DataScope> {
+        self.snapshot.load().data_scopes(user, tenant)
+    }
+
+    /// explain the permission check against the current policy snapshot.
+    pub fn explain(&self, user: &str, tenant: &str, path: &str) -> Result<Explanation, String> {
+        self.snapshot
+            .load()
+            .explain(user, tenant, path)
+            .map_err(|err| format! {"cannot explain permission: {0}", err})
+    }
+
+    /// reload the polices and wait for the result.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the actor is gone or the polices can not be
+    /// loaded, the previous polices stay in use in that case.
+    pub async fn reset(&self) -> Result<(), Error> {
+        let (reply, result) = oneshot::channel();
+        self.send(Command::Reset(reply)).await?;
+
+        result
             .await
-            .map_err(|err| format! {"cannot send message to rbac actor: {0}", err})?;
+            .map_err(|err| format! {"cannot reset rbac polices: {0}", err})?
+    }
+
+    /// returns the rules stored by the policy adapter
+    pub async fn rules(&self) -> Result<Vec<StoredRule>, Error> {
+        let (reply, result) = oneshot::channel();
+        self.send(Command::Rules(reply)).await?;
 
-        let result = response
+        result
             .await
-            .map_err(|err| format! {"cannot receive response from rbac actor: {0}", err})?;
+            .map_err(|err| format! {"cannot list rbac rules: {0}", err})?
+    }
 
-        Ok(result)
+    /// store a rule and reload the polices, returns false if it already exists
+    pub async fn add_rule(&self, rule: StoredRule) -> Result<bool, Error> {
+        let (reply, result) = oneshot::channel();
+        self.send(Command::AddRule(rule, reply)).await?;
+
+        result
+            .await
+            .map_err(|err| format! {"cannot add rbac rule: {0}", err})?
+    }
+
+    /// remove a stored rule and reload the polices, returns false if it does not exist
+    pub async fn remove_rule(&self, rule: StoredRule) -> Result<bool, Error> {
+        let (reply, result) = oneshot::channel();
+        self.send(Command::RemoveRule(rule, reply)).await?;
+
+        result
+            .await
+            .map_err(|err| format! {"cannot remove rbac rule: {0}", err})?
     }
 
-    pub async fn reset(&self) -> Result<(), String> {
+    async fn send(&self, command: Command) -> Result<(), Error> {
         self.sender
-            .send(Command::Reset)
+            .send(command)
             .await
-            .map_err(|err| format! {"cannot reset rbac polices: {0}", err})?;
+            .map_err(|err| format! {"rbac actor is gone: {0}", err})?;
+
         Ok(())
     }
+
+    /// returns the state of the policy loading
+    pub fn health(&self) -> RbacHealth {
+        (*self.health.load_full()).clone()
+    }
+
+    /// returns the version of the polices loaded by this instance.
+    ///
+    /// the version is a digest of the loaded polices, all instances that have
+    /// converged on the same data report the same value.
+    pub fn policy_version(&self) -> String {
+        self.snapshot.load().version().to_string()
+    }
+}
+
+/// returns a short sha256 digest of the policy lines, independent of their order.
+fn policy_digest(mut lines: Vec<String>) -> String {
+    lines.sort();
+
+    let mut hasher = Sha256::new();
+    for line in lines {
+        hasher.update(line.as_bytes());
+        hasher.update(b"\n");
+    }
+
+    hasher
+        .finalize()
+        .iter()
+        .take(8)
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// returns the casbin model text, read from the file if a path is given.
+///
+/// the model must accept requests of `sub, dom, path` and policies of `sub, dom, path, eft`,
+/// the way roles are turned into polices.
+///
+/// # Errors
+///
+/// This function will return an error if the file can not be read, the model can not
+/// be parsed or it does not accept the request and policy shape.
+pub async fn load_model(path: Option<&str>) -> Result<String, Error> {
+    let model = match path {
+        Some(path) => tokio::fs::read_to_string(path)
+            .await
+            .map_err(|err| format!("cannot read rbac model {}: {}", path, err))?,
+        None => MODEL.to_string(),
+    };
+
+    let mut enforcer = create_enforcer(&model).await?;
+    enforcer
+        .add_policy(vec![
+            "role".to_string(),
+            "tenant".to_string(),
+            "/path".to_string(),
+            "allow".to_string(),
+        ])
+        .await?;
+    enforcer.enforce(("user", "tenant", "/path"))?;
+
+    Ok(model)
 }
 
-async fn create_enforcer() -> Result<Enforcer, Error> {
-    let model = casbin::DefaultModel::from_str(MODEL).await?;
+async fn create_enforcer(model: &str) -> Result<Enforcer, Error> {
+    let model = casbin::DefaultModel::from_str(model).await?;
     let adapter = casbin::MemoryAdapter::default();
-    let e = Enforcer::new(model, adapter).await?;
+    let mut e = Enforcer::new(model, adapter).await?;
+    e.add_function(
+        "permissionMatch",
+        |permission: ImmutableString, pattern: ImmutableString| {
+            permission_match(permission.as_str(), pattern.as_str())
+        },
+    );
     Ok(e)
 }
 
 #[cfg(test)]
 mod tests {
 
+    use std::time::Instant;
+
+    use futures::future::join_all;
+    use tokio::sync::oneshot;
+
     use super::*;
 
     #[tokio::test]
     async fn test_enforcer_model() {
-        let mut enforcer = create_enforcer().await.unwrap();
-        let policy = vec!["admin".to_string(), "read".to_string()];
+        let mut enforcer = create_enforcer(MODEL).await.unwrap();
+        let policy = vec![
+            "admin".to_string(),
+            "default".to_string(),
+            "read".to_string(),
+            "allow".to_string(),
+        ];
 
         let user = "zhangsan";
 
@@ -223,20 +842,478 @@ mod tests {
         println!("{:?}", enforcer.get_all_policy());
 
         enforcer
-            .add_role_for_user(user, "admin", None)
+            .add_role_for_user(user, "admin", Some("default"))
             .await
             .unwrap();
 
         println!("{:?}", enforcer.get_all_roles());
 
-        let is_ok = enforcer.enforce((user, "read")).unwrap();
+        let is_ok = enforcer.enforce((user, "default", "read")).unwrap();
         assert_eq!(is_ok, true);
 
-        let is_false = enforcer.enforce((user, "write")).unwrap();
+        let is_false = enforcer.enforce((user, "default", "write")).unwrap();
         assert_eq!(is_false, false);
 
         // user not in the role
-        let is_false = enforcer.enforce(("test", "read")).unwrap();
+        let is_false = enforcer.enforce(("test", "default", "read")).unwrap();
         assert_eq!(is_false, false);
     }
+
+    #[tokio::test]
+    async fn test_enforcer_tenants() {
+        let mut enforcer = create_enforcer(MODEL).await.unwrap();
+
+        enforcer
+            .add_policy(vec![
+                "admin".to_string(),
+                "t1".to_string(),
+                "/users".to_string(),
+                "allow".to_string(),
+            ])
+            .await
+            .unwrap();
+        enforcer
+            .add_policy(vec![
+                "viewer".to_string(),
+                "t2".to_string(),
+                "/reports".to_string(),
+                "allow".to_string(),
+            ])
+            .await
+            .unwrap();
+
+        enforcer
+            .add_role_for_user("zhangsan", "admin", Some("t1"))
+            .await
+            .unwrap();
+        enforcer
+            .add_role_for_user("zhangsan", "viewer", Some("t2"))
+            .await
+            .unwrap();
+
+        assert!(enforcer.enforce(("zhangsan", "t1", "/users")).unwrap());
+        assert!(enforcer.enforce(("zhangsan", "t2", "/reports")).unwrap());
+
+        // roles do not leak into other tenants
+        assert!(!enforcer.enforce(("zhangsan", "t2", "/users")).unwrap());
+        assert!(!enforcer.enforce(("zhangsan", "t1", "/reports")).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_deny_overrides_allow() {
+        let mut enforcer = create_enforcer(MODEL).await.unwrap();
+
+        enforcer
+            .add_policy(vec![
+                "admin".to_string(),
+                "default".to_string(),
+                "/admin/*".to_string(),
+                "allow".to_string(),
+            ])
+            .await
+            .unwrap();
+        enforcer
+            .add_policy(vec![
+                "auditor".to_string(),
+                "default".to_string(),
+                "/admin/billing".to_string(),
+                "deny".to_string(),
+            ])
+            .await
+            .unwrap();
+        enforcer
+            .add_role_for_user("zhangsan", "admin", Some("default"))
+            .await
+            .unwrap();
+        enforcer
+            .add_role_for_user("lisi", "admin", Some("default"))
+            .await
+            .unwrap();
+        enforcer
+            .add_role_for_user("lisi", "auditor", Some("default"))
+            .await
+            .unwrap();
+
+        let snapshot = PolicySnapshot {
+            enforcer,
+            version: String::new(),
+            scopes: HashMap::from([(
+                ("default".to_string(), "admin".to_string()),
+                DataScope::Department,
+            )]),
+        };
+
+        assert_eq!(
+            snapshot.data_scopes("lisi", "default"),
+            vec![DataScope::Department]
+        );
+        assert!(snapshot.data_scopes("lisi", "shop").is_empty());
+        assert_eq!(
+            snapshot.data_scopes(SUPERUSER, "shop"),
+            vec![DataScope::All]
+        );
+
+        assert!(snapshot
+            .check("zhangsan", "default", "/admin/billing")
+            .unwrap());
+        assert!(snapshot.check("lisi", "default", "/admin/users").unwrap());
+        assert!(!snapshot.check("lisi", "default", "/admin/billing").unwrap());
+
+        // a deny rule alone grants nothing
+        assert!(!snapshot.check("lisi", "default", "/reports").unwrap());
+
+        assert!(snapshot
+            .check(SUPERUSER, "default", "/admin/billing")
+            .unwrap());
+
+        let explanation = snapshot
+            .explain("lisi", "default", "/admin/billing")
+            .unwrap();
+        assert!(!explanation.allowed);
+        assert!(explanation
+            .rules
+            .iter()
+            .any(|rule| rule.matched && rule.effect == "deny"));
+    }
+
+    #[tokio::test]
+    async fn test_explain() {
+        let mut enforcer = create_enforcer(MODEL).await.unwrap();
+
+        enforcer
+            .add_policy(vec![
+                "admin".to_string(),
+                "t1".to_string(),
+                "/roles/:id".to_string(),
+                "allow".to_string(),
+            ])
+            .await
+            .unwrap();
+        enforcer
+            .add_policy(vec![
+                "admin".to_string(),
+                "t2".to_string(),
+                "/users".to_string(),
+                "allow".to_string(),
+            ])
+            .await
+            .unwrap();
+        enforcer
+            .add_role_for_user("zhangsan", "admin", Some("t1"))
+            .await
+            .unwrap();
+
+        let snapshot = PolicySnapshot {
+            enforcer,
+            version: String::new(),
+            scopes: HashMap::new(),
+        };
+
+        let explanation = snapshot.explain("zhangsan", "t1", "/roles/1").unwrap();
+        assert!(explanation.allowed);
+        assert_eq!(explanation.roles, vec!["admin".to_string()]);
+        assert_eq!(explanation.rules.len(), 2);
+        assert_eq!(
+            explanation
+                .rules
+                .iter()
+                .filter(|rule| rule.matched)
+                .map(|rule| rule.path.as_str())
+                .collect::<Vec<_>>(),
+            vec!["/roles/:id"]
+        );
+
+        let explanation = snapshot.explain("zhangsan", "t1", "/users").unwrap();
+        assert!(!explanation.allowed);
+        assert!(explanation.rules.iter().all(|rule| !rule.matched));
+
+        let explanation = snapshot.explain("lisi", "t1", "/roles/1").unwrap();
+        assert!(!explanation.allowed);
+        assert!(explanation.roles.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_enforcer_path_params() {
+        let mut enforcer = create_enforcer(MODEL).await.unwrap();
+
+        enforcer
+            .add_policy(vec![
+                "admin".to_string(),
+                "default".to_string(),
+                "/roles/:id".to_string(),
+                "allow".to_string(),
+            ])
+            .await
+            .unwrap();
+        enforcer
+            .add_role_for_user("zhangsan", "admin", Some("default"))
+            .await
+            .unwrap();
+
+        assert!(enforcer
+            .enforce(("zhangsan", "default", "/roles/1"))
+            .unwrap());
+        assert!(!enforcer.enforce(("zhangsan", "default", "/roles")).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_enforcer_capabilities() {
+        let mut enforcer = create_enforcer(MODEL).await.unwrap();
+
+        for path in ["role:read", "rbac:*"] {
+            enforcer
+                .add_policy(vec![
+                    "admin".to_string(),
+                    "default".to_string(),
+                    path.to_string(),
+                    "allow".to_string(),
+                ])
+                .await
+                .unwrap();
+        }
+        enforcer
+            .add_role_for_user("zhangsan", "admin", Some("default"))
+            .await
+            .unwrap();
+
+        assert!(enforcer
+            .enforce(("zhangsan", "default", "role:read"))
+            .unwrap());
+        // the colon is not a path parameter for capabilities
+        assert!(!enforcer
+            .enforce(("zhangsan", "default", "role:write"))
+            .unwrap());
+        assert!(enforcer
+            .enforce(("zhangsan", "default", "rbac:write"))
+            .unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_enforcer_route_permissions() {
+        let mut enforcer = create_enforcer(MODEL).await.unwrap();
+
+        for (path, effect) in [
+            ("/admin/*", "allow"),
+            ("/admin/billing", "deny"),
+            ("role:read", "allow"),
+            ("/roles/:id", "deny"),
+        ] {
+            enforcer
+                .add_policy(vec![
+                    "admin".to_string(),
+                    "default".to_string(),
+                    path.to_string(),
+                    effect.to_string(),
+                ])
+                .await
+                .unwrap();
+        }
+        enforcer
+            .add_role_for_user("zhangsan", "admin", Some("default"))
+            .await
+            .unwrap();
+
+        let enforce = |capability: &str, path: &str| {
+            enforcer
+                .enforce(("zhangsan", "default", route_permission(capability, path)))
+                .unwrap()
+        };
+
+        // granted by the path or the capability, refused by a deny rule on either
+        assert!(enforce("user:read", "/admin/users"));
+        assert!(!enforce("billing:read", "/admin/billing"));
+        assert!(enforce("role:read", "/roles"));
+        assert!(!enforce("role:read", "/roles/1"));
+        assert!(!enforce("user:read", "/users"));
+    }
+
+    #[test]
+    fn test_validate_stored_rule() {
+        let rule = |ptype: &str, values: &[&str]| StoredRule {
+            ptype: ptype.to_string(),
+            rule: values.iter().map(|value| value.to_string()).collect(),
+        };
+
+        assert!(rule("p", &["alice", "default", "report:read", "allow"])
+            .validate()
+            .is_ok());
+        assert!(rule("g", &["alice", "admin", "default"]).validate().is_ok());
+        assert!(rule("p", &["alice", "default", "report:read"])
+            .validate()
+            .is_err());
+        assert!(rule("p", &["alice", "default", "report:read", "maybe"])
+            .validate()
+            .is_err());
+        assert!(rule("g2", &["alice", "admin", "default"])
+            .validate()
+            .is_err());
+
+        assert_eq!(
+            rule("p", &["alice", "shop", "report:read", "allow"]).domain(),
+            "shop"
+        );
+        assert_eq!(rule("g", &["alice", "admin", "shop"]).domain(), "shop");
+        assert_eq!(rule("g2", &["alice", "admin", "shop"]).domain(), "");
+    }
+
+    #[test]
+    fn test_backoff_delay() {
+        let backoff = Backoff {
+            attempts: 5,
+            initial: Duration::from_secs(1),
+            max: Duration::from_secs(5),
+        };
+
+        assert_eq!(backoff.delay(1), Duration::from_secs(1));
+        assert_eq!(backoff.delay(3), Duration::from_secs(4));
+        assert_eq!(backoff.delay(4), Duration::from_secs(5));
+        assert_eq!(backoff.delay(100), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn test_permission_match() {
+        assert!(permission_match("/roles/1", "/roles/:id"));
+        assert!(permission_match("user:write", "*"));
+        assert!(permission_match("user:write", "user:*"));
+        assert!(!permission_match("users:write", "user:*"));
+        assert!(!permission_match("user:write", "user:read"));
+        assert!(permission_match("user:write /users", "/users"));
+        assert!(permission_match("user:write /users", "user:*"));
+        assert!(!permission_match("user:write /users", "/roles"));
+    }
+
+    async fn bench_enforcer() -> Enforcer {
+        let mut enforcer = create_enforcer(MODEL).await.unwrap();
+
+        for role in 0..20 {
+            for path in 0..50 {
+                enforcer
+                    .add_policy(vec![
+                        format!("role-{}", role),
+                        "default".to_string(),
+                        format!("/resources/{}", path),
+                        "allow".to_string(),
+                    ])
+                    .await
+                    .unwrap();
+            }
+        }
+
+        for user in 0..200 {
+            enforcer
+                .add_role_for_user(
+                    &format!("user-{}", user),
+                    &format!("role-{}", user % 20),
+                    Some("default"),
+                )
+                .await
+                .unwrap();
+        }
+
+        enforcer
+    }
+
+    /// compares permission checks serialised through a single actor channel
+    /// with checks served from the shared snapshot.
+    ///
+    /// the snapshot scales with the number of cores, the actor is bound to one.
+    ///
+    /// run with `cargo test --release -- --ignored bench_concurrent_checks --nocapture`
+    #[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+    #[ignore]
+    async fn bench_concurrent_checks() {
+        const TASKS: usize = 64;
+        const CHECKS: usize = 500;
+
+        // a single task owning the enforcer behind a bounded channel
+        let (sender, mut receiver) = mpsc::channel::<(String, oneshot::Sender<bool>)>(100);
+        let enforcer = bench_enforcer().await;
+        tokio::spawn(async move {
+            while let Some((path, respond_to)) = receiver.recv().await {
+                let is_ok = enforcer.enforce(("user-1", "default", path)).unwrap();
+                let _ = respond_to.send(is_ok);
+            }
+        });
+
+        let started = Instant::now();
+        let tasks = (0..TASKS).map(|_| {
+            let sender = sender.clone();
+            tokio::spawn(async move {
+                for i in 0..CHECKS {
+                    let (respond_to, response) = oneshot::channel();
+                    sender
+                        .send((format!("/resources/{}", i % 50), respond_to))
+                        .await
+                        .unwrap();
+                    response.await.unwrap();
+                }
+            })
+        });
+        join_all(tasks).await;
+        let actor_elapsed = started.elapsed();
+
+        // every task reads the shared snapshot
+        let snapshot = Arc::new(ArcSwap::from_pointee(PolicySnapshot {
+            enforcer: bench_enforcer().await,
+            version: String::new(),
+            scopes: HashMap::new(),
+        }));
+
+        let started = Instant::now();
+        let tasks = (0..TASKS).map(|_| {
+            let snapshot = snapshot.clone();
+            tokio::spawn(async move {
+                for i in 0..CHECKS {
+                    let path = format!("/resources/{}", i % 50);
+                    snapshot
+                        .load()
+                        .enforcer
+                        .enforce(("user-1", "default", path))
+                        .unwrap();
+                }
+            })
+        });
+        join_all(tasks).await;
+        let snapshot_elapsed = started.elapsed();
+
+        println!(
+            "{} checks: actor {:?}, snapshot {:?}",
+            TASKS * CHECKS,
+            actor_elapsed,
+            snapshot_elapsed
+        );
+    }
+
+    #[tokio::test]
+    async fn test_load_model() {
+        assert_eq!(load_model(None).await.unwrap(), MODEL);
+
+        let path = std::env::temp_dir().join("rbac_test_model.conf");
+        tokio::fs::write(&path, "[request_definition]\nr = sub, path\n")
+            .await
+            .unwrap();
+        assert!(load_model(path.to_str()).await.is_err());
+
+        assert!(load_model(Some("./not-exists.conf")).await.is_err());
+    }
+
+    #[test]
+    fn test_policy_digest_ignores_order() {
+        let a = policy_digest(vec![
+            "p,admin,/a".to_string(),
+            "g,zhangsan,admin".to_string(),
+        ]);
+        let b = policy_digest(vec![
+            "g,zhangsan,admin".to_string(),
+            "p,admin,/a".to_string(),
+        ]);
+        let c = policy_digest(vec![
+            "p,admin,/b".to_string(),
+            "g,zhangsan,admin".to_string(),
+        ]);
+
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_eq!(a.len(), 16);
+    }
 }
@@ -0,0 +1,187 @@
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use mongodb::{
+    bson::{doc, Document},
+    change_stream::{event::ChangeStreamEvent, ChangeStream},
+    options::FindOptions,
+    Database,
+};
+use sha2::{Digest, Sha256};
+
+use super::rbac::{Backoff, RbacActorHandler};
+
+/// fields written without changing the polices, such as the time of the last login.
+///
+/// updates of only these fields are left out of the change stream, they are not
+/// versioned either so polling does not see them.
+const UNWATCHED_FIELDS: [&str; 1] = ["last_login_at"];
+
+/// watch the collections the rbac polices are derived from
+/// and reset the rbac actor when they change.
+///
+/// change streams are used when the server supports them (replica set or sharded cluster).
+/// while the stream can not be opened or after it broke, the collections are polled every
+/// `poll_interval` and the stream is opened again after the delay of the backoff.
+pub fn spawn(
+    database: Database,
+    rbac: RbacActorHandler,
+    collections: Vec<String>,
+    poll_interval: Duration,
+    backoff: Backoff,
+) {
+    tokio::spawn(async move {
+        let mut last = None;
+        let mut failures = 0;
+
+        loop {
+            match open(&database, &collections).await {
+                Ok(stream) => {
+                    println!("rbac change stream opened");
+
+                    // changes made since the last poll were not seen
+                    if failures > 0 {
+                        if let Err(err) = rbac.reset().await {
+                            println!("Failed to reset rbac polices: {}", err);
+                        }
+                    }
+                    failures = 0;
+
+                    if let Err(err) = watch(stream, &rbac).await {
+                        println!("rbac change stream broken: {}", err);
+                    }
+                }
+                Err(err) => println!("rbac change stream unavailable: {}", err),
+            }
+
+            failures += 1;
+            let delay = backoff.delay(failures).max(poll_interval);
+            println!(
+                "polling rbac collections every {:?}, watching again in {:?}",
+                poll_interval, delay
+            );
+
+            let polling = poll(&database, &rbac, &collections, poll_interval, &mut last);
+            let _ = tokio::time::timeout(delay, polling).await;
+        }
+    });
+}
+
+/// opens the change stream of the collections
+async fn open(
+    database: &Database,
+    collections: &[String],
+) -> Result<ChangeStream<ChangeStreamEvent<Document>>, mongodb::error::Error> {
+    let pipeline = vec![doc! {
+        "$match": {
+            "ns.coll": { "$in": collections },
+            "$expr": { "$not": [unwatched_update()] },
+        }
+    }];
+
+    database.watch(pipeline, None).await
+}
+
+/// returns when the change stream is closed or broken.
+async fn watch(
+    mut stream: ChangeStream<ChangeStreamEvent<Document>>,
+    rbac: &RbacActorHandler,
+) -> Result<(), mongodb::error::Error> {
+    while let Some(event) = stream.next().await {
+        let event = event?;
+        println!(
+            "rbac collections changed ({:?}), reloading polices",
+            event.operation_type
+        );
+
+        if let Err(err) = rbac.reset().await {
+            println!("Failed to reset rbac polices: {}", err);
+        }
+    }
+
+    Ok(())
+}
+
+/// returns the expression matching updates that only set [UNWATCHED_FIELDS]
+fn unwatched_update() -> Document {
+    doc! {
+        "$and": [
+            { "$eq": ["$operationType", "update"] },
+            { "$eq": [{ "$size": { "$ifNull": ["$updateDescription.removedFields", []] } }, 0] },
+            { "$setIsSubset": [
+                {
+                    "$map": {
+                        "input": { "$objectToArray": "$updateDescription.updatedFields" },
+                        "in": "$$this.k",
+                    }
+                },
+                UNWATCHED_FIELDS.to_vec(),
+            ] },
+        ]
+    }
+}
+
+/// polls until cancelled, `last` keeps the fingerprint between the periods of polling.
+async fn poll(
+    database: &Database,
+    rbac: &RbacActorHandler,
+    collections: &[String],
+    poll_interval: Duration,
+    last: &mut Option<Vec<u8>>,
+) {
+    let mut interval = tokio::time::interval(poll_interval);
+
+    loop {
+        interval.tick().await;
+
+        let current = match fingerprint(database, collections).await {
+            Ok(current) => current,
+            Err(err) => {
+                println!("Failed to poll rbac collections: {}", err);
+                continue;
+            }
+        };
+
+        if last.as_ref().is_some_and(|last| *last != current) {
+            println!("rbac collections changed, reloading polices");
+            if let Err(err) = rbac.reset().await {
+                println!("Failed to reset rbac polices: {}", err);
+                continue;
+            }
+        }
+
+        *last = Some(current);
+    }
+}
+
+/// returns a sha256 digest of the ids and versions of the documents in every collection.
+///
+/// inserts and deletes change the ids, updates (including soft deletes) bump the version,
+/// so any write to the collections changes the fingerprint, even a delete paired with an insert.
+async fn fingerprint(
+    database: &Database,
+    collections: &[String],
+) -> Result<Vec<u8>, mongodb::error::Error> {
+    let mut hasher = Sha256::new();
+
+    for name in collections {
+        hasher.update(name.as_bytes());
+        hasher.update(b"\n");
+
+        let options = FindOptions::builder()
+            .projection(doc! { "_id": 1, "version": 1 })
+            .sort(doc! { "_id": 1 })
+            .build();
+        let mut cursor = database
+            .collection::<Document>(name.as_str())
+            .find(None, options)
+            .await?;
+
+        while let Some(item) = cursor.next().await {
+            hasher.update(item?.to_string().as_bytes());
+            hasher.update(b"\n");
+        }
+    }
+
+    Ok(hasher.finalize().to_vec())
+}
@@ -0,0 +1,33 @@
+use async_trait::async_trait;
+
+/// a message delivered to a user, such as an invitation
+#[derive(Debug, Clone)]
+pub struct Message {
+    /// email address or phone number of the recipient
+    pub to: String,
+    pub subject: String,
+    pub content: String,
+}
+
+/// delivers messages to users, implement it to send them by email or sms.
+///
+/// errors are described as text, they are shown to admins.
+#[async_trait]
+pub trait MessageSender: Send + Sync {
+    async fn send(&self, message: &Message) -> Result<(), String>;
+}
+
+/// prints messages to the console, for development
+pub struct ConsoleSender;
+
+#[async_trait]
+impl MessageSender for ConsoleSender {
+    async fn send(&self, message: &Message) -> Result<(), String> {
+        println!(
+            "message to {}: {}\n{}",
+            message.to, message.subject, message.content
+        );
+
+        Ok(())
+    }
+}
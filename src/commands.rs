@@ -0,0 +1,86 @@
+use mongodb::Database;
+
+use crate::{
+    actors::id_gen::IDGeneratorHandler,
+    database::repositories::policy_bundle::PolicyBundleRepository,
+    domain::policy_bundle::{BundleFormat, ConflictStrategy, PolicyBundle},
+};
+
+/// write all roles and user-role assignments to the output file
+pub async fn export(database: &Database, output: &str, format: BundleFormat) -> Result<(), String> {
+    let bundle = PolicyBundleRepository::new()
+        .export(database)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    let content = bundle.encode(format).map_err(|err| err.to_string())?;
+    tokio::fs::write(output, content)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    println!(
+        "exported {} roles and {} assignments to {}",
+        bundle.roles.len(),
+        bundle.assignments.len(),
+        output
+    );
+
+    Ok(())
+}
+
+/// import roles and user-role assignments from the input file.
+///
+/// running servers pick up the changes through the rbac watcher.
+pub async fn import(
+    database: &Database,
+    id_gen: &IDGeneratorHandler,
+    input: &str,
+    format: BundleFormat,
+    strategy: ConflictStrategy,
+    dry_run: bool,
+) -> Result<(), String> {
+    let content = tokio::fs::read_to_string(input)
+        .await
+        .map_err(|err| err.to_string())?;
+    let bundle = PolicyBundle::decode(&content, format).map_err(|err| err.to_string())?;
+
+    let repository = PolicyBundleRepository::new();
+    let plan = repository
+        .plan(&bundle, strategy, database)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    let roles = plan
+        .roles
+        .iter()
+        .filter(|change| change.role.is_some())
+        .count();
+    println!(
+        "{} roles and {} users to change, {} entries can not be imported",
+        roles,
+        plan.users.len(),
+        plan.errors.len()
+    );
+    for error in plan.errors.iter() {
+        println!("  {}", error);
+    }
+
+    if dry_run {
+        println!("dry run, nothing imported");
+        return Ok(());
+    }
+
+    if !plan.errors.is_empty() {
+        return Err(format!("{} entries can not be imported", plan.errors.len()));
+    }
+
+    if !plan.has_changes() {
+        println!("nothing to import from {}", input);
+        return Ok(());
+    }
+
+    repository.apply(&plan, id_gen, database).await?;
+    println!("imported {}", input);
+
+    Ok(())
+}
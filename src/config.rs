@@ -1,9 +1,18 @@
+use std::{collections::HashMap, net::IpAddr, sync::Arc, time::Duration};
+
 use mongodb::Collection;
 use serde::Deserialize;
 use tokio::fs;
 
 use crate::{
-    actors::{id_gen::IDGeneratorHandler, rbac::RbacActorHandler},
+    actors::{
+        id_gen::IDGeneratorHandler,
+        rbac::{Backoff, RbacActorHandler},
+        sender::MessageSender,
+    },
+    database::repositories::{collection_names, user::UserRepository},
+    domain::common::{DEFAULT_TENANT, STAFF_REALM},
+    handles::catalogue::RouteCatalogue,
     jwt::Engine,
 };
 #[derive(Clone)]
@@ -13,10 +22,43 @@ pub struct AppState {
     pub config: AppConfig,
     pub id_gen: IDGeneratorHandler,
     pub jwt: Engine,
+    /// rbac engine of the staff realm
+    pub rbac: RbacActorHandler,
+    /// the other user realms by name
+    pub realms: Arc<HashMap<String, RealmState>>,
+    /// permissions that can be granted to roles, filled in when the router is created
+    pub catalogue: RouteCatalogue,
+    /// delivers invitations and other messages to users
+    pub sender: Arc<dyn MessageSender>,
+}
+
+/// a configured user realm with its rbac engine
+#[derive(Clone)]
+pub struct RealmState {
+    pub config: Realm,
     pub rbac: RbacActorHandler,
 }
 
 impl AppState {
+    /// returns the rbac engine of the realm
+    pub fn rbac_of(&self, realm: &str) -> Option<&RbacActorHandler> {
+        match realm {
+            STAFF_REALM => Some(&self.rbac),
+            _ => self.realms.get(realm).map(|item| &item.rbac),
+        }
+    }
+
+    /// returns the repository of the realm's users
+    pub fn users_of(&self, realm: &str) -> Option<UserRepository> {
+        match realm {
+            STAFF_REALM => Some(UserRepository::new()),
+            _ => self
+                .realms
+                .get(realm)
+                .map(|item| UserRepository::with_collection(&item.config.collection)),
+        }
+    }
+
     pub fn get_collection<T>(&self, collection_name: &str) -> Collection<T> {
         self.client
             .database(&self.config.database.db_name)
@@ -39,14 +81,228 @@ pub struct Database {
     pub db_name: String,
 }
 
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct Rbac {
+    /// seconds between two polls when change streams are not available (standalone mongodb)
+    pub poll_interval: u64,
+    /// when enabled, denied responses carry a correlation id of the logged explanation
+    pub debug: bool,
+    /// casbin model file, the built-in model is used if not set
+    pub model_path: Option<String>,
+    /// toml file defining roles and users, they are read from the database if not set
+    pub policy_path: Option<String>,
+    /// attempts to load the polices at startup before starting in degraded mode
+    pub startup_attempts: u32,
+    /// seconds before the first retry of a failed load, doubled on every failure
+    pub retry_interval: u64,
+    /// upper bound of the retry delay in seconds
+    pub max_retry_interval: u64,
+    /// seconds between two sweeps of expired role grants
+    pub sweep_interval: u64,
+    /// store arbitrary casbin rules in the `casbin_rules` collection,
+    /// loaded together with the rules derived from roles and users
+    pub adapter: bool,
+}
+
+impl Rbac {
+    /// check that the collections are polled at a positive interval, a zero interval panics
+    pub fn check(&self) -> Result<(), String> {
+        if self.poll_interval == 0 {
+            return Err("rbac.poll_interval must be greater than 0".to_string());
+        }
+
+        Ok(())
+    }
+
+    pub fn backoff(&self) -> Backoff {
+        Backoff {
+            attempts: self.startup_attempts.max(1),
+            initial: Duration::from_secs(self.retry_interval),
+            max: Duration::from_secs(self.max_retry_interval),
+        }
+    }
+}
+
+impl Default for Rbac {
+    fn default() -> Self {
+        Rbac {
+            poll_interval: 10,
+            debug: false,
+            model_path: None,
+            policy_path: None,
+            startup_attempts: 5,
+            retry_interval: 1,
+            max_retry_interval: 30,
+            sweep_interval: 60,
+            adapter: false,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct Invitation {
+    /// seconds an invitation can be accepted
+    pub ttl: u64,
+    /// page where invitees set their password, `{token}` is replaced by the invitation token
+    pub accept_url: String,
+}
+
+impl Default for Invitation {
+    fn default() -> Self {
+        Invitation {
+            ttl: 3 * 24 * 3600,
+            accept_url: "http://localhost:8000/user/invitation?token={token}".to_string(),
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct LoginHistory {
+    /// days login attempts are kept
+    pub retention_days: u64,
+}
+
+impl LoginHistory {
+    pub fn retention(&self) -> Duration {
+        Duration::from_secs(self.retention_days * 24 * 3600)
+    }
+}
+
+impl Default for LoginHistory {
+    fn default() -> Self {
+        LoginHistory { retention_days: 90 }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+#[serde(default)]
+pub struct Registration {
+    /// accept sign-ups at `/register`
+    pub enabled: bool,
+    /// realm the users sign up to
+    pub realm: String,
+    /// tenant the default role is granted in
+    pub tenant: String,
+    /// role granted once the user is activated, none if empty
+    pub default_role: String,
+    /// keep verified users pending until an admin approves them
+    pub require_approval: bool,
+    /// require the answer of a challenge from `/register/captcha`
+    pub captcha: bool,
+    /// seconds a challenge can be answered
+    pub captcha_ttl: u64,
+    /// seconds the email address can be verified
+    pub verify_ttl: u64,
+    /// page where users verify their email address, `{token}` is replaced by the token
+    pub verify_url: String,
+    /// registrations accepted from an address within `rate_window`
+    pub max_per_ip: u64,
+    /// seconds
+    pub rate_window: u64,
+}
+
+impl Default for Registration {
+    fn default() -> Self {
+        Registration {
+            enabled: false,
+            realm: STAFF_REALM.to_string(),
+            tenant: DEFAULT_TENANT.to_string(),
+            default_role: String::new(),
+            require_approval: false,
+            captcha: true,
+            captcha_ttl: 300,
+            verify_ttl: 24 * 3600,
+            verify_url: "http://localhost:8000/user/verify?token={token}".to_string(),
+            max_per_ip: 5,
+            rate_window: 3600,
+        }
+    }
+}
+
+/// users kept apart from the staff, such as end customers.
+///
+/// a realm signs in at `/{name}/login` and its tokens are only accepted by its own routes.
+/// its roles and users are managed outside of the api, in the policy file or the collections.
+#[derive(Deserialize, Clone)]
+pub struct Realm {
+    /// used in the routes of the realm and as the audience of its tokens
+    pub name: String,
+    /// collection of the users
+    pub collection: String,
+    /// collection of the roles
+    pub role_collection: String,
+    /// casbin model file, the built-in model is used if not set
+    pub model_path: Option<String>,
+    /// toml file defining roles and users, they are read from the collections if not set
+    pub policy_path: Option<String>,
+}
+
 #[derive(Deserialize, Clone)]
 pub struct AppConfig {
     pub database: Database,
     pub secret: String,
     pub statistic_host: String,
+    #[serde(default)]
+    pub rbac: Rbac,
+    #[serde(default)]
+    pub invitation: Invitation,
+    #[serde(default)]
+    pub login_history: LoginHistory,
+    #[serde(default)]
+    pub realms: Vec<Realm>,
+    #[serde(default)]
+    pub registration: Registration,
+    /// addresses of the reverse proxies whose `X-Forwarded-For` and `X-Real-IP` headers
+    /// are trusted, the client address is the peer of the connection otherwise
+    #[serde(default)]
+    pub trusted_proxies: Vec<IpAddr>,
 }
 
 impl AppConfig {
+    /// check that the realms do not share names or collections with each other or the staff
+    pub fn check_realms(&self) -> Result<(), String> {
+        let mut names = vec![STAFF_REALM];
+        let mut collections = collection_names::ALL.to_vec();
+
+        for realm in &self.realms {
+            let valid = !realm.name.is_empty()
+                && realm
+                    .name
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+            if !valid {
+                return Err(format!("invalid realm name: {:?}", realm.name));
+            }
+            if names.contains(&realm.name.as_str()) {
+                return Err(format!("duplicated realm: {}", realm.name));
+            }
+
+            for collection in [&realm.collection, &realm.role_collection] {
+                if collection.is_empty() || collections.contains(&collection.as_str()) {
+                    return Err(format!(
+                        "realm {} can not use the collection {:?}",
+                        realm.name, collection
+                    ));
+                }
+                collections.push(collection);
+            }
+
+            names.push(&realm.name);
+        }
+
+        if self.registration.enabled && !names.contains(&self.registration.realm.as_str()) {
+            return Err(format!(
+                "unknown realm of the registration: {}",
+                self.registration.realm
+            ));
+        }
+
+        Ok(())
+    }
+
     pub fn get_statistic_url(&self, path: &str) -> String {
         let path = path.trim_start_matches("./");
         let path = path.trim_start_matches("/upload");
@@ -65,3 +321,59 @@ pub async fn load_config(path: &str) -> Result<AppConfig, Error> {
 
     Ok(cfg)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn realm(name: &str, collection: &str) -> Realm {
+        Realm {
+            name: name.to_string(),
+            collection: collection.to_string(),
+            role_collection: format!("{}_roles", name),
+            model_path: None,
+            policy_path: None,
+        }
+    }
+
+    #[test]
+    fn test_check_realms() {
+        let mut config: AppConfig = toml::from_str(
+            r#"
+            secret = "secret"
+            statistic_host = ""
+            [database]
+            uri = ""
+            db_name = ""
+            "#,
+        )
+        .unwrap();
+
+        config.realms = vec![realm("customer", "customers")];
+        assert!(config.check_realms().is_ok());
+
+        config.realms = vec![realm("staff", "staffs")];
+        assert!(config.check_realms().is_err());
+
+        config.realms = vec![realm("customer", "customers"), realm("customer", "others")];
+        assert!(config.check_realms().is_err());
+
+        config.realms = vec![realm("customer", "users")];
+        assert!(config.check_realms().is_err());
+
+        config.realms = vec![realm("customer", "registrations")];
+        assert!(config.check_realms().is_err());
+
+        config.realms = vec![realm("Customer", "customers")];
+        assert!(config.check_realms().is_err());
+    }
+
+    #[test]
+    fn test_check_rbac() {
+        let mut rbac = Rbac::default();
+        assert!(rbac.check().is_ok());
+
+        rbac.poll_interval = 0;
+        assert!(rbac.check().is_err());
+    }
+}
@@ -1,3 +1,27 @@
 pub const USER: &str = "users";
 
 pub const ROLE: &str = "roles";
+
+/// casbin rules stored by the policy adapter
+pub const CASBIN_RULE: &str = "casbin_rules";
+
+pub const DEPARTMENT: &str = "departments";
+
+pub const INVITATION: &str = "invitations";
+
+/// login attempts, removed by a ttl index
+pub const LOGIN_RECORD: &str = "login_records";
+
+/// self-registrations of users
+pub const REGISTRATION: &str = "registrations";
+
+/// every collection of the staff, realms can not store their users or roles in them
+pub const ALL: [&str; 7] = [
+    USER,
+    ROLE,
+    CASBIN_RULE,
+    DEPARTMENT,
+    INVITATION,
+    LOGIN_RECORD,
+    REGISTRATION,
+];
@@ -0,0 +1,159 @@
+use futures_util::StreamExt;
+use mongodb::{
+    bson::{doc, to_bson, Document},
+    options::FindOptions,
+    Database,
+};
+
+use crate::{
+    database::errors::{Error, Result},
+    domain::department::Department,
+    impl_repository,
+};
+
+use super::{
+    base::cursor_to_vec,
+    collection_names::DEPARTMENT,
+    macros::{IFilter, IPaginator},
+    Collection,
+};
+
+pub struct DepartmentRepository {
+    pub coll_name: String,
+}
+
+impl DepartmentRepository {
+    pub fn new() -> Self {
+        DepartmentRepository {
+            coll_name: DEPARTMENT.to_string(),
+        }
+    }
+
+    /// returns the departments of the tenant
+    pub async fn find_by_tenant(
+        &self,
+        tenant: &str,
+        database: &Database,
+    ) -> Result<Vec<Department>> {
+        let cursor = database
+            .collection::<Department>(self.coll_name.as_str())
+            .find(doc! { "tenant": tenant, "deleted_at": 0 }, None)
+            .await?;
+
+        cursor_to_vec(cursor).await
+    }
+
+    /// returns the department and all of its descendants
+    pub async fn find_subtree(&self, path: &str, database: &Database) -> Result<Vec<Department>> {
+        let cursor = database
+            .collection::<Department>(self.coll_name.as_str())
+            .find(subtree_filter(path), None)
+            .await?;
+
+        cursor_to_vec(cursor).await
+    }
+
+    /// returns the departments the user leads
+    pub async fn find_by_head(
+        &self,
+        user_id: &str,
+        database: &Database,
+    ) -> Result<Vec<Department>> {
+        let cursor = database
+            .collection::<Department>(self.coll_name.as_str())
+            .find(doc! { "head_ids": user_id }, None)
+            .await?;
+
+        cursor_to_vec(cursor).await
+    }
+
+    /// remove the user from the heads of every department
+    pub async fn remove_head(&self, user_id: &str, database: &Database) -> Result<u64> {
+        let result = database
+            .collection::<Department>(self.coll_name.as_str())
+            .update_many(
+                doc! { "head_ids": user_id },
+                doc! { "$pull": { "head_ids": user_id }, "$inc": { "version": 1 } },
+                None,
+            )
+            .await?;
+
+        Ok(result.modified_count)
+    }
+
+    /// returns the direct children of the department
+    pub async fn find_by_parent(&self, id: &str, database: &Database) -> Result<Vec<Department>> {
+        let cursor = database
+            .collection::<Department>(self.coll_name.as_str())
+            .find(doc! { "parent_id": id, "deleted_at": 0 }, None)
+            .await?;
+
+        cursor_to_vec(cursor).await
+    }
+
+    /// returns the number of direct children of the department
+    pub async fn count_children(&self, id: &str, database: &Database) -> Result<u64> {
+        let count = database
+            .collection::<Department>(self.coll_name.as_str())
+            .count_documents(doc! { "parent_id": id, "deleted_at": 0 }, None)
+            .await?;
+
+        Ok(count)
+    }
+
+    /// returns the department with the name under the parent
+    pub async fn find_sibling_by_name(
+        &self,
+        tenant: &str,
+        parent_id: &str,
+        name: &str,
+        database: &Database,
+    ) -> Result<Option<Department>> {
+        let department = database
+            .collection::<Department>(self.coll_name.as_str())
+            .find_one(
+                doc! { "tenant": tenant, "parent_id": parent_id, "name": name, "deleted_at": 0 },
+                None,
+            )
+            .await?;
+
+        Ok(department)
+    }
+}
+
+/// matches the department with the path and its descendants
+fn subtree_filter(path: &str) -> Document {
+    doc! {
+        "path": { "$regex": format!("^{}", escape_regex(path)) },
+        "deleted_at": 0,
+    }
+}
+
+/// escapes the characters of the value that have a meaning in a regex
+pub fn escape_regex(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if "\\^$.|?*+()[]{}".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+
+    escaped
+}
+
+impl_repository!(DepartmentRepository, Department, DEPARTMENT);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subtree_filter() {
+        assert_eq!(
+            subtree_filter("/1/2/"),
+            doc! { "path": { "$regex": "^/1/2/" }, "deleted_at": 0 }
+        );
+        assert_eq!(escape_regex("/a.b/"), "/a\\.b/");
+    }
+}
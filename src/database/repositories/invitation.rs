@@ -0,0 +1,99 @@
+use futures_util::StreamExt;
+use mongodb::{
+    bson::{doc, to_bson},
+    options::FindOptions,
+    Database,
+};
+
+use crate::{
+    database::errors::{Error, Result},
+    domain::invitation::{hash_token, Invitation},
+    impl_repository,
+};
+
+use super::{
+    base::cursor_to_vec,
+    collection_names::INVITATION,
+    macros::{IFilter, IPaginator},
+    Collection,
+};
+
+pub struct InvitationRepository {
+    pub coll_name: String,
+}
+
+impl InvitationRepository {
+    pub fn new() -> Self {
+        InvitationRepository {
+            coll_name: INVITATION.to_string(),
+        }
+    }
+
+    /// returns the invitation the token was issued for
+    pub async fn find_by_token(
+        &self,
+        token: &str,
+        database: &Database,
+    ) -> Result<Option<Invitation>> {
+        if token.is_empty() {
+            return Ok(None);
+        }
+
+        let invitation = database
+            .collection::<Invitation>(self.coll_name.as_str())
+            .find_one(
+                doc! { "token_hash": hash_token(token), "deleted_at": 0 },
+                None,
+            )
+            .await?;
+
+        Ok(invitation)
+    }
+
+    /// returns the invitations sent to the user
+    pub async fn find_by_user(
+        &self,
+        user_id: &str,
+        database: &Database,
+    ) -> Result<Vec<Invitation>> {
+        let cursor = database
+            .collection::<Invitation>(self.coll_name.as_str())
+            .find(doc! { "user_id": user_id }, None)
+            .await?;
+
+        cursor_to_vec(cursor).await
+    }
+
+    /// returns the invitations the user sent
+    pub async fn find_by_inviter(
+        &self,
+        user_id: &str,
+        database: &Database,
+    ) -> Result<Vec<Invitation>> {
+        let cursor = database
+            .collection::<Invitation>(self.coll_name.as_str())
+            .find(doc! { "invited_by": user_id }, None)
+            .await?;
+
+        cursor_to_vec(cursor).await
+    }
+
+    /// forget where the invitations of the user were sent, their tokens stop working
+    pub async fn anonymise(&self, user_id: &str, database: &Database) -> Result<u64> {
+        let result = database
+            .collection::<Invitation>(self.coll_name.as_str())
+            .update_many(
+                doc! { "user_id": user_id },
+                doc! {
+                    "$set": { "contact": "", "token_hash": "", "last_error": "" },
+                    "$inc": { "version": 1 },
+                },
+                None,
+            )
+            .await?;
+
+        Ok(result.modified_count)
+    }
+}
+
+impl_repository!(InvitationRepository, Invitation, INVITATION);
@@ -0,0 +1,104 @@
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use mongodb::{
+    bson::{doc, to_bson},
+    options::{FindOptions, IndexOptions},
+    Database, IndexModel,
+};
+
+use crate::{
+    database::errors::{Error, Result},
+    domain::login_record::LoginRecord,
+    impl_repository,
+};
+
+use super::{
+    base::cursor_to_vec,
+    collection_names::LOGIN_RECORD,
+    macros::{IFilter, IPaginator},
+    Collection,
+};
+
+const TTL_INDEX: &str = "logged_at_ttl";
+
+pub struct LoginRecordRepository {
+    pub coll_name: String,
+}
+
+impl LoginRecordRepository {
+    pub fn new() -> Self {
+        LoginRecordRepository {
+            coll_name: LOGIN_RECORD.to_string(),
+        }
+    }
+
+    /// let mongodb remove the records older than the retention period.
+    ///
+    /// the index is recreated when the retention period changed.
+    pub async fn ensure_ttl_index(&self, retention: Duration, database: &Database) -> Result<()> {
+        let collection = database.collection::<LoginRecord>(self.coll_name.as_str());
+        let index = || {
+            IndexModel::builder()
+                .keys(doc! { "logged_at": 1 })
+                .options(
+                    IndexOptions::builder()
+                        .name(TTL_INDEX.to_string())
+                        .expire_after(retention)
+                        .build(),
+                )
+                .build()
+        };
+
+        if collection.create_index(index(), None).await.is_err() {
+            collection.drop_index(TTL_INDEX, None).await?;
+            collection.create_index(index(), None).await?;
+        }
+
+        Ok(())
+    }
+
+    /// returns the attempts of the user, and those made with its account before it existed
+    pub async fn find_by_user(
+        &self,
+        user_id: &str,
+        account: &str,
+        database: &Database,
+    ) -> Result<Vec<LoginRecord>> {
+        let cursor = database
+            .collection::<LoginRecord>(self.coll_name.as_str())
+            .find(
+                user_filter(user_id, account),
+                FindOptions::builder().sort(doc! { "logged_at": 1 }).build(),
+            )
+            .await?;
+
+        cursor_to_vec(cursor).await
+    }
+
+    /// replace the account and drop the client details of the user's attempts
+    pub async fn anonymise(
+        &self,
+        user_id: &str,
+        account: &str,
+        replacement: &str,
+        database: &Database,
+    ) -> Result<u64> {
+        let result = database
+            .collection::<LoginRecord>(self.coll_name.as_str())
+            .update_many(
+                user_filter(user_id, account),
+                doc! { "$set": { "account": replacement, "ip": "", "user_agent": "" } },
+                None,
+            )
+            .await?;
+
+        Ok(result.modified_count)
+    }
+}
+
+fn user_filter(user_id: &str, account: &str) -> mongodb::bson::Document {
+    doc! { "$or": [{ "user_id": user_id }, { "account": account }] }
+}
+
+impl_repository!(LoginRecordRepository, LoginRecord, LOGIN_RECORD);
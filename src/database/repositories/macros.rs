@@ -23,11 +23,65 @@ pub trait IPaginator {
 
 #[macro_export]
 macro_rules! impl_repository {
+    // opt in to data scopes: the scoped queries merge the caller's data scope into the filter
+    ($repo:ident, $struct_name:ty, $collection:expr, scoped { owner: $owner:expr, department: $department:expr }) => {
+        impl_repository!($repo, $struct_name, $collection);
+
+        impl $repo {
+            /// fields of the collection that data scopes are matched against
+            pub const SCOPE_FIELDS: $crate::database::repositories::scope::ScopeFields =
+                $crate::database::repositories::scope::ScopeFields {
+                    owner: $owner,
+                    department: $department,
+                };
+
+            pub async fn find_by_id_scoped(
+                &self,
+                id: &str,
+                database: &Database,
+                scope: &$crate::database::repositories::scope::ResolvedScope,
+            ) -> Result<Option<$struct_name>> {
+                let filter = doc! {
+                    "$and": [
+                        { "id": id, "deleted_at": 0 },
+                        scope.to_doc(&Self::SCOPE_FIELDS),
+                    ]
+                };
+
+                let entity = database
+                    .collection::<$struct_name>(self.coll_name.as_str())
+                    .find_one(filter, None)
+                    .await?;
+
+                Ok(entity)
+            }
+
+            pub async fn search_scoped<T>(
+                &self,
+                database: &Database,
+                filter: &T,
+                scope: &$crate::database::repositories::scope::ResolvedScope,
+            ) -> Result<Collection<$struct_name>>
+            where
+                T: IFilter + IPaginator,
+            {
+                let filter = $crate::database::repositories::scope::Scoped::new(
+                    filter,
+                    scope.to_doc(&Self::SCOPE_FIELDS),
+                );
+
+                self.search(database, &filter).await
+            }
+        }
+    };
+
+    // queries go to the repository's `coll_name`, which `$collection` is only the default of,
+    // so a repository can serve another collection of the same documents
     ($repo:ident, $struct_name:ty, $collection:expr) => {
         impl $repo {
             pub async fn create(&self, entity: &$struct_name, database: &Database) -> Result<()> {
                 database
-                    .collection::<$struct_name>($collection)
+                    .collection::<$struct_name>(self.coll_name.as_str())
                     .insert_one(entity, None)
                     .await?;
 
@@ -40,7 +94,7 @@ macro_rules! impl_repository {
                 database: &Database,
             ) -> Result<Option<$struct_name>> {
                 let entity = database
-                    .collection::<$struct_name>($collection)
+                    .collection::<$struct_name>(self.coll_name.as_str())
                     .find_one(doc! { "id": id, "deleted_at":0 }, None)
                     .await?;
 
@@ -53,7 +107,7 @@ macro_rules! impl_repository {
                 database: &Database,
             ) -> Result<Vec<$struct_name>> {
                 let mut cursor = database
-                    .collection::<$struct_name>($collection)
+                    .collection::<$struct_name>(self.coll_name.as_str())
                     .find(doc! { "id":  {"$in": ids}, "deleted_at":0 }, None)
                     .await?;
 
@@ -75,7 +129,7 @@ macro_rules! impl_repository {
                 doc.insert("version", next_version as i64);
 
                 let result = database
-                    .collection::<$struct_name>($collection)
+                    .collection::<$struct_name>(self.coll_name.as_str())
                     .update_one(
                         doc! {
                             "id": entity.base.id.clone(),
@@ -111,7 +165,7 @@ macro_rules! impl_repository {
                 doc.insert("version", next_version as i64);
 
                 let result = database
-                    .collection::<$struct_name>($collection)
+                    .collection::<$struct_name>(self.coll_name.as_str())
                     .update_one_with_session(
                         doc! {
                             "id": entity.base.id.clone(),
@@ -135,7 +189,7 @@ macro_rules! impl_repository {
 
             pub async fn find_all(&self, database: &Database) -> Result<Vec<$struct_name>> {
                 let cursor = database
-                    .collection::<$struct_name>($collection)
+                    .collection::<$struct_name>(self.coll_name.as_str())
                     .find(doc! {"deleted_at": 0}, None)
                     .await?;
 
@@ -174,7 +228,7 @@ macro_rules! impl_repository {
                     .build();
 
                 let cursor = database
-                    .collection::<$struct_name>($collection)
+                    .collection::<$struct_name>(self.coll_name.as_str())
                     .find(filter.to_doc(), find_options)
                     .await?;
 
@@ -186,16 +240,58 @@ macro_rules! impl_repository {
                 T: IFilter,
             {
                 let count = database
-                    .collection::<$struct_name>($collection)
+                    .collection::<$struct_name>(self.coll_name.as_str())
                     .count_documents(filter.to_doc(), None)
                     .await?;
 
                 Ok(count)
             }
+
+            /// returns the soft-deleted entity
+            pub async fn find_deleted_by_id(
+                &self,
+                id: &str,
+                database: &Database,
+            ) -> Result<Option<$struct_name>> {
+                let entity = database
+                    .collection::<$struct_name>(self.coll_name.as_str())
+                    .find_one(doc! { "id": id, "deleted_at": { "$gt": 0 } }, None)
+                    .await?;
+
+                Ok(entity)
+            }
+
+            /// undo the soft delete, returns false if the entity is not deleted
+            pub async fn restore(&self, id: &str, database: &Database) -> Result<bool> {
+                let result = database
+                    .collection::<$struct_name>(self.coll_name.as_str())
+                    .update_one(
+                        doc! { "id": id, "deleted_at": { "$gt": 0 } },
+                        doc! {
+                            "$set": { "deleted_at": 0 },
+                            "$inc": { "version": 1 },
+                        },
+                        None,
+                    )
+                    .await?;
+
+                Ok(result.modified_count > 0)
+            }
+
+            /// permanently remove a soft-deleted entity, returns false if it is not deleted
+            pub async fn purge(&self, id: &str, database: &Database) -> Result<bool> {
+                let result = database
+                    .collection::<$struct_name>(self.coll_name.as_str())
+                    .delete_one(doc! { "id": id, "deleted_at": { "$gt": 0 } }, None)
+                    .await?;
+
+                Ok(result.deleted_count > 0)
+            }
         }
     };
 }
 
+#[macro_export]
 macro_rules! impl_paginator {
     ($struct_name:ty) => {
         impl IPaginator for $struct_name {
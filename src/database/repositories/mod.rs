@@ -1,8 +1,16 @@
 mod base;
 pub mod collection_names;
+pub mod department;
+pub mod invitation;
+pub mod login_record;
 mod macros;
+pub mod policy_bundle;
+pub mod registration;
 pub mod role;
+pub mod scope;
 pub mod user;
 
 pub use base::Collection;
 pub use base::NumberItem;
+pub use base::{default_page, default_page_size};
+pub use macros::{IFilter, IPaginator};
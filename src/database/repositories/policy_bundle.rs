@@ -0,0 +1,87 @@
+use mongodb::Database;
+
+use crate::{
+    actors::id_gen::IDGeneratorHandler,
+    database::errors::Result,
+    domain::policy_bundle::{ConflictStrategy, ImportPlan, PolicyBundle},
+};
+
+use super::{role::RoleRepository, user::UserRepository};
+
+/// reads and writes roles and user-role assignments as a [PolicyBundle]
+pub struct PolicyBundleRepository {
+    roles: RoleRepository,
+    users: UserRepository,
+}
+
+impl PolicyBundleRepository {
+    pub fn new() -> Self {
+        PolicyBundleRepository {
+            roles: RoleRepository::new(),
+            users: UserRepository::new(),
+        }
+    }
+
+    /// returns all roles and user-role assignments of every tenant
+    pub async fn export(&self, database: &Database) -> Result<PolicyBundle> {
+        let roles = self.roles.find_all(database).await?;
+        let users = self.users.find_all(database).await?;
+
+        Ok(PolicyBundle::from_state(&roles, &users))
+    }
+
+    /// returns the changes importing the bundle would make
+    pub async fn plan(
+        &self,
+        bundle: &PolicyBundle,
+        strategy: ConflictStrategy,
+        database: &Database,
+    ) -> Result<ImportPlan> {
+        let roles = self.roles.find_all(database).await?;
+        let users = self.users.find_all(database).await?;
+
+        Ok(bundle.plan(&roles, &users, strategy))
+    }
+
+    /// save the roles and users of the plan, new roles get their ids here.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if an id can not be generated or a write fails,
+    /// the writes done before are kept.
+    pub async fn apply(
+        &self,
+        plan: &ImportPlan,
+        id_gen: &IDGeneratorHandler,
+        database: &Database,
+    ) -> std::result::Result<(), String> {
+        for change in plan.roles.iter() {
+            let Some(role) = &change.role else {
+                continue;
+            };
+
+            if role.base.id.is_empty() {
+                let mut role = role.clone();
+                role.base.id = id_gen.next_id().await?;
+                self.roles
+                    .create(&role, database)
+                    .await
+                    .map_err(|err| err.to_string())?;
+            } else {
+                self.roles
+                    .update(role, database)
+                    .await
+                    .map_err(|err| err.to_string())?;
+            }
+        }
+
+        for user in plan.users.iter() {
+            self.users
+                .update(user, database)
+                .await
+                .map_err(|err| err.to_string())?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,151 @@
+use futures_util::StreamExt;
+use mongodb::{
+    bson::{doc, to_bson},
+    options::FindOptions,
+    Database,
+};
+
+use crate::{
+    database::errors::{Error, Result},
+    domain::{invitation::hash_token, registration::Registration},
+    impl_repository,
+};
+
+use super::{
+    base::cursor_to_vec,
+    collection_names::REGISTRATION,
+    macros::{IFilter, IPaginator},
+    Collection,
+};
+
+pub struct RegistrationRepository {
+    pub coll_name: String,
+}
+
+impl RegistrationRepository {
+    pub fn new() -> Self {
+        RegistrationRepository {
+            coll_name: REGISTRATION.to_string(),
+        }
+    }
+
+    /// returns the registration the verification token was issued for
+    pub async fn find_by_token(
+        &self,
+        token: &str,
+        database: &Database,
+    ) -> Result<Option<Registration>> {
+        if token.is_empty() {
+            return Ok(None);
+        }
+
+        let registration = database
+            .collection::<Registration>(self.coll_name.as_str())
+            .find_one(
+                doc! { "token_hash": hash_token(token), "deleted_at": 0 },
+                None,
+            )
+            .await?;
+
+        Ok(registration)
+    }
+
+    /// returns the registrations of the realm holding the account or email address that
+    /// were not verified in time
+    pub async fn find_expired(
+        &self,
+        realm: &str,
+        account: &str,
+        email: &str,
+        now: u64,
+        database: &Database,
+    ) -> Result<Vec<Registration>> {
+        let cursor = database
+            .collection::<Registration>(self.coll_name.as_str())
+            .find(
+                doc! {
+                    "deleted_at": 0,
+                    "realm": realm,
+                    "$or": [{ "account": account }, { "email": email }],
+                    "verified_at": 0,
+                    "decided_at": 0,
+                    "expires_at": { "$lte": now as i64 },
+                },
+                None,
+            )
+            .await?;
+
+        cursor_to_vec(cursor).await
+    }
+
+    /// returns the registrations of the user, deleted ones included
+    pub async fn find_by_user(
+        &self,
+        user_id: &str,
+        database: &Database,
+    ) -> Result<Vec<Registration>> {
+        let cursor = database
+            .collection::<Registration>(self.coll_name.as_str())
+            .find(doc! { "user_id": user_id }, None)
+            .await?;
+
+        cursor_to_vec(cursor).await
+    }
+
+    /// returns the registrations the user approved or rejected
+    pub async fn find_by_decider(
+        &self,
+        user_id: &str,
+        database: &Database,
+    ) -> Result<Vec<Registration>> {
+        let cursor = database
+            .collection::<Registration>(self.coll_name.as_str())
+            .find(doc! { "decided_by": user_id }, None)
+            .await?;
+
+        cursor_to_vec(cursor).await
+    }
+
+    /// clear the contact details of the user's registrations, returns the number changed
+    pub async fn anonymise(
+        &self,
+        user_id: &str,
+        replacement: &str,
+        database: &Database,
+    ) -> Result<u64> {
+        let result = database
+            .collection::<Registration>(self.coll_name.as_str())
+            .update_many(
+                doc! { "user_id": user_id },
+                doc! {
+                    "$set": {
+                        "account": replacement,
+                        "email": "",
+                        "ip": "",
+                        "token_hash": "",
+                        "reason": "",
+                    },
+                    "$inc": { "version": 1 },
+                },
+                None,
+            )
+            .await?;
+
+        Ok(result.modified_count)
+    }
+
+    /// returns the number of registrations sent from the address since the timestamp
+    pub async fn count_from_ip(&self, ip: &str, since: u64, database: &Database) -> Result<u64> {
+        let count = database
+            .collection::<Registration>(self.coll_name.as_str())
+            .count_documents(
+                doc! { "ip": ip, "created_at": { "$gte": since as i64 } },
+                None,
+            )
+            .await?;
+
+        Ok(count)
+    }
+}
+
+impl_repository!(RegistrationRepository, Registration, REGISTRATION);
@@ -1,17 +1,26 @@
-use mongodb::{bson::doc, Database};
-use serde::de::DeserializeOwned;
+use futures_util::StreamExt;
+use mongodb::{
+    bson::{doc, to_bson},
+    options::FindOptions,
+    Database,
+};
 
 use crate::{
-    actors::fetcher::{self, Error, RBACRole},
+    actors::fetcher::{self, RBACRole},
+    database::errors::{Error, Result},
     domain::role::Role,
+    impl_repository,
 };
 
-use super::collection_names::ROLE;
+use super::{
+    base::cursor_to_vec,
+    collection_names::ROLE,
+    macros::{IFilter, IPaginator},
+    Collection,
+};
 
 use async_trait::async_trait;
 
-use futures_util::StreamExt;
-
 pub struct RoleRepository {
     pub coll_name: String,
 }
@@ -22,11 +31,59 @@ impl RoleRepository {
             coll_name: ROLE.to_string(),
         }
     }
+
+    /// the roles of another realm, kept in their own collection
+    pub fn with_collection(coll_name: &str) -> Self {
+        RoleRepository {
+            coll_name: coll_name.to_string(),
+        }
+    }
+
+    /// returns the role with the name inside the tenant
+    pub async fn find_by_name(
+        &self,
+        tenant: &str,
+        name: &str,
+        database: &Database,
+    ) -> Result<Option<Role>> {
+        let role = database
+            .collection::<Role>(self.coll_name.as_str())
+            .find_one(
+                doc! { "tenant": tenant, "name": name, "deleted_at": 0 },
+                None,
+            )
+            .await?;
+
+        Ok(role)
+    }
+
+    /// returns the roles with the names inside the tenant
+    pub async fn find_by_names(
+        &self,
+        tenant: &str,
+        names: &[String],
+        database: &Database,
+    ) -> Result<Vec<Role>> {
+        let cursor = database
+            .collection::<Role>(self.coll_name.as_str())
+            .find(
+                doc! { "tenant": tenant, "name": { "$in": names }, "deleted_at": 0 },
+                None,
+            )
+            .await?;
+
+        cursor_to_vec(cursor).await
+    }
 }
 
+impl_repository!(RoleRepository, Role, ROLE);
+
 #[async_trait]
 impl fetcher::RBACRoleFetcher for RoleRepository {
-    async fn find_all(&self, database: &Database) -> Result<Vec<Box<dyn RBACRole>>, Error> {
+    async fn find_all(
+        &self,
+        database: &Database,
+    ) -> std::result::Result<Vec<Box<dyn RBACRole>>, fetcher::Error> {
         let mut items = database
             .collection::<Role>(self.coll_name.as_str())
             .find(
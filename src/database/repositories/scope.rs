@@ -0,0 +1,221 @@
+use mongodb::{
+    bson::{doc, Document},
+    Database,
+};
+
+use crate::{
+    actors::rbac::{RbacActorHandler, SUPERUSER},
+    database::errors::Result,
+    domain::role::DataScope,
+};
+
+use super::{
+    macros::{IFilter, IPaginator},
+    user::UserRepository,
+};
+
+/// fields of a scoped collection that data scopes are matched against
+pub struct ScopeFields {
+    /// field holding the id of the user owning the document
+    pub owner: &'static str,
+    /// field holding the department id of the document
+    pub department: &'static str,
+}
+
+/// the data scopes of the caller, resolved from the roles held in the active tenant
+#[derive(Debug, Clone, Default)]
+pub struct ResolvedScope {
+    pub scopes: Vec<DataScope>,
+    pub user_id: String,
+    pub department_id: String,
+}
+
+impl ResolvedScope {
+    /// a scope without any restriction
+    pub fn unrestricted() -> Self {
+        ResolvedScope {
+            scopes: vec![DataScope::All],
+            ..Default::default()
+        }
+    }
+
+    /// returns the filter restricting a scoped collection to the caller's documents.
+    ///
+    /// the scopes of several roles are combined with `$or`, a role with [DataScope::All]
+    /// lifts the restriction. without any role nothing is visible, neither is anything
+    /// through [DataScope::Department] when the caller has no department.
+    pub fn to_doc(&self, fields: &ScopeFields) -> Document {
+        if self.scopes.contains(&DataScope::All) {
+            return Document::new();
+        }
+
+        let conditions: Vec<Document> = self
+            .scopes
+            .iter()
+            .filter_map(|scope| match scope {
+                DataScope::All => Some(Document::new()),
+                // documents without a department are not the caller's department
+                DataScope::Department if self.department_id.is_empty() => None,
+                DataScope::Department => {
+                    Some(doc! { fields.department: self.department_id.clone() })
+                }
+                DataScope::Own => Some(doc! { fields.owner: self.user_id.clone() }),
+                DataScope::Custom(filter) => Some(filter.clone()),
+            })
+            .collect();
+
+        match conditions.len() {
+            // matches nothing
+            0 => doc! { "_id": { "$exists": false } },
+            1 => conditions.into_iter().next().unwrap(),
+            _ => doc! { "$or": conditions },
+        }
+    }
+}
+
+/// resolve the data scope of the account inside the tenant.
+///
+/// the roles and their scopes are taken from the rbac engine, so they come from the
+/// policy file when one is configured. the user is read from the realm's users.
+///
+/// # Errors
+///
+/// This function will return an error if the user can not be read.
+pub async fn resolve(
+    account: &str,
+    tenant: &str,
+    rbac: &RbacActorHandler,
+    users: &UserRepository,
+    database: &Database,
+) -> Result<ResolvedScope> {
+    if account == SUPERUSER {
+        return Ok(ResolvedScope::unrestricted());
+    }
+
+    let user = match users.find_by_account(account, database).await? {
+        Some(user) => user,
+        None => return Ok(ResolvedScope::default()),
+    };
+
+    Ok(ResolvedScope {
+        scopes: rbac.data_scopes(account, tenant),
+        user_id: user.base.id,
+        department_id: user.department_id,
+    })
+}
+
+/// a filter restricted by a data scope, the scope is merged into [IFilter::to_doc]
+pub struct Scoped<'a, F> {
+    inner: &'a F,
+    scope: Document,
+}
+
+impl<'a, F> Scoped<'a, F> {
+    pub fn new(inner: &'a F, scope: Document) -> Self {
+        Scoped { inner, scope }
+    }
+}
+
+impl<'a, F: IFilter> IFilter for Scoped<'a, F> {
+    fn to_doc(&self) -> Document {
+        let filter = self.inner.to_doc();
+
+        if self.scope.is_empty() {
+            return filter;
+        }
+
+        doc! { "$and": [filter, self.scope.clone()] }
+    }
+}
+
+impl<'a, F: IPaginator> IPaginator for Scoped<'a, F> {
+    fn skip(&self) -> u64 {
+        self.inner.skip()
+    }
+
+    fn limit(&self) -> i64 {
+        self.inner.limit()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIELDS: ScopeFields = ScopeFields {
+        owner: "owner_id",
+        department: "department_id",
+    };
+
+    fn resolved(scopes: Vec<DataScope>) -> ResolvedScope {
+        ResolvedScope {
+            scopes,
+            user_id: "u1".to_string(),
+            department_id: "d1".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_all_lifts_restriction() {
+        let scope = resolved(vec![DataScope::Own, DataScope::All]);
+        assert!(scope.to_doc(&FIELDS).is_empty());
+    }
+
+    #[test]
+    fn test_scopes_are_combined() {
+        assert_eq!(
+            resolved(vec![DataScope::Own]).to_doc(&FIELDS),
+            doc! { "owner_id": "u1" }
+        );
+
+        assert_eq!(
+            resolved(vec![
+                DataScope::Department,
+                DataScope::Custom(doc! { "level": 1 })
+            ])
+            .to_doc(&FIELDS),
+            doc! { "$or": [{ "department_id": "d1" }, { "level": 1 }] }
+        );
+
+        assert_eq!(
+            resolved(vec![]).to_doc(&FIELDS),
+            doc! { "_id": { "$exists": false } }
+        );
+    }
+
+    #[test]
+    fn test_department_without_department_matches_nothing() {
+        let scope = ResolvedScope {
+            department_id: String::new(),
+            ..resolved(vec![DataScope::Department])
+        };
+        assert_eq!(scope.to_doc(&FIELDS), doc! { "_id": { "$exists": false } });
+
+        let scope = ResolvedScope {
+            scopes: vec![DataScope::Department, DataScope::Own],
+            ..scope
+        };
+        assert_eq!(scope.to_doc(&FIELDS), doc! { "owner_id": "u1" });
+    }
+
+    struct Filter;
+
+    impl IFilter for Filter {
+        fn to_doc(&self) -> Document {
+            doc! { "deleted_at": 0 }
+        }
+    }
+
+    #[test]
+    fn test_scoped_filter() {
+        assert_eq!(
+            Scoped::new(&Filter, Document::new()).to_doc(),
+            doc! { "deleted_at": 0 }
+        );
+
+        assert_eq!(
+            Scoped::new(&Filter, doc! { "owner_id": "u1" }).to_doc(),
+            doc! { "$and": [{ "deleted_at": 0 }, { "owner_id": "u1" }] }
+        );
+    }
+}
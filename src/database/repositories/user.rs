@@ -1,18 +1,31 @@
-use mongodb::{bson::doc, Database};
+use mongodb::{
+    bson::{doc, to_bson, Document},
+    options::FindOptions,
+    Database,
+};
 
 use crate::{
-    actors::{fetcher, rbac},
-    domain::{common::Secret, role::Role, user::User, BaseModel},
+    actors::fetcher,
+    database::errors::{Error, Result},
+    domain::{
+        common::{Secret, DEFAULT_TENANT},
+        user::{AccountStatus, User, SYSTEM_ACTOR},
+        BaseModel,
+    },
+    impl_repository,
 };
 
-use super::{base, collection_names::USER};
+use super::{
+    base::cursor_to_vec,
+    collection_names::USER,
+    macros::{IFilter, IPaginator},
+    Collection,
+};
 
 use async_trait::async_trait;
 
 use futures_util::StreamExt;
 
-use super::super::errors::Result;
-
 pub struct UserRepository {
     pub coll_name: String,
 }
@@ -23,6 +36,13 @@ impl UserRepository {
             coll_name: USER.to_string(),
         }
     }
+
+    /// the users of another realm, kept in their own collection
+    pub fn with_collection(coll_name: &str) -> Self {
+        UserRepository {
+            coll_name: coll_name.to_string(),
+        }
+    }
 }
 
 impl UserRepository {
@@ -31,28 +51,310 @@ impl UserRepository {
         account: &str,
         database: &Database,
     ) -> Result<Option<User>> {
-        // fake account. for test
-        if account == "qqwweeasf" {
+        // fake account of the staff. for test
+        if account == "qqwweeasf" && self.coll_name == USER {
             return Ok(Some(User {
                 base: BaseModel::fake(),
                 secret: Secret::fake(),
                 name: "fake".to_string(),
                 age: 18,
                 avatar: "".to_string(),
-                is_active: true,
+                phone: "".to_string(),
+                email: "".to_string(),
+                status: AccountStatus::Active,
+                status_changes: vec![],
+                expires_at: None,
+                last_login_at: 0,
+                department_id: "".to_string(),
                 role_name: "admin".to_string(),
+                tenant_roles: vec![],
+                erased_at: 0,
             }));
         }
 
         let collection = database.collection::<User>(self.coll_name.as_str());
         let user = collection
-            .find_one(doc! { "account": account, "deleted_at": 0 }, None)
+            .find_one(doc! { "secret.account": account, "deleted_at": 0 }, None)
             .await?;
 
         Ok(user)
     }
+
+    /// returns the user with the email address
+    pub async fn find_by_email(&self, email: &str, database: &Database) -> Result<Option<User>> {
+        let user = database
+            .collection::<User>(self.coll_name.as_str())
+            .find_one(doc! { "email": email, "deleted_at": 0 }, None)
+            .await?;
+
+        Ok(user)
+    }
+
+    /// returns the users with the accounts
+    pub async fn find_by_accounts(
+        &self,
+        accounts: &[String],
+        database: &Database,
+    ) -> Result<Vec<User>> {
+        let cursor = database
+            .collection::<User>(self.coll_name.as_str())
+            .find(
+                doc! { "secret.account": { "$in": accounts }, "deleted_at": 0 },
+                None,
+            )
+            .await?;
+
+        cursor_to_vec(cursor).await
+    }
+
+    /// insert the users inside the session's transaction
+    pub async fn create_many_with_session(
+        &self,
+        users: &[User],
+        database: &Database,
+        session: &mut mongodb::ClientSession,
+    ) -> Result<()> {
+        database
+            .collection::<User>(self.coll_name.as_str())
+            .insert_many_with_session(users, None, session)
+            .await?;
+
+        Ok(())
+    }
+
+    /// returns the users whose status was changed by the user
+    pub async fn find_changed_by(&self, actor: &str, database: &Database) -> Result<Vec<User>> {
+        let cursor = database
+            .collection::<User>(self.coll_name.as_str())
+            .find(doc! { "status_changes.actor": actor }, None)
+            .await?;
+
+        cursor_to_vec(cursor).await
+    }
+
+    /// record the time of a successful login, without touching the version
+    pub async fn set_last_login(&self, id: &str, at: u64, database: &Database) -> Result<()> {
+        database
+            .collection::<User>(self.coll_name.as_str())
+            .update_one(
+                doc! { "id": id },
+                doc! { "$set": { "last_login_at": at as i64 } },
+                None,
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// returns the filter matching the members of the tenant, see [User::belongs_to]
+    pub fn membership(tenant: &str) -> Document {
+        if tenant != DEFAULT_TENANT {
+            return doc! { "tenant_roles.tenant": tenant };
+        }
+
+        doc! {
+            "$or": [
+                { "role_name": { "$nin": ["", null] } },
+                { "tenant_roles.tenant": tenant },
+                { "role_name": { "$in": ["", null] }, "tenant_roles.0": { "$exists": false } },
+            ]
+        }
+    }
+
+    /// returns the number of users holding the role inside the tenant
+    pub async fn count_by_role(
+        &self,
+        tenant: &str,
+        role_name: &str,
+        database: &Database,
+    ) -> Result<u64> {
+        let mut conditions = vec![doc! {
+            "tenant_roles": { "$elemMatch": { "tenant": tenant, "role_name": role_name } }
+        }];
+
+        if tenant == DEFAULT_TENANT {
+            conditions.push(doc! { "role_name": role_name });
+        }
+
+        let count = database
+            .collection::<User>(self.coll_name.as_str())
+            .count_documents(doc! { "deleted_at": 0, "$or": conditions }, None)
+            .await?;
+
+        Ok(count)
+    }
+
+    /// returns the number of users in the departments
+    pub async fn count_by_departments(
+        &self,
+        department_ids: &[String],
+        database: &Database,
+    ) -> Result<u64> {
+        let count = database
+            .collection::<User>(self.coll_name.as_str())
+            .count_documents(
+                doc! { "deleted_at": 0, "department_id": { "$in": department_ids } },
+                None,
+            )
+            .await?;
+
+        Ok(count)
+    }
+
+    /// move every user of a department to another one inside the session's transaction,
+    /// returns the number of users moved.
+    pub async fn move_department_with_session(
+        &self,
+        from: &str,
+        to: &str,
+        database: &Database,
+        session: &mut mongodb::ClientSession,
+    ) -> Result<u64> {
+        let result = database
+            .collection::<User>(self.coll_name.as_str())
+            .update_many_with_session(
+                doc! { "deleted_at": 0, "department_id": from },
+                doc! {
+                    "$set": { "department_id": to },
+                    "$inc": { "version": 1 },
+                },
+                None,
+                session,
+            )
+            .await?;
+
+        Ok(result.modified_count)
+    }
+
+    /// remove the grants that expired at the timestamp, returns the number of users changed.
+    pub async fn remove_expired_grants(&self, now: u64, database: &Database) -> Result<u64> {
+        let result = database
+            .collection::<User>(self.coll_name.as_str())
+            .update_many(
+                doc! { "deleted_at": 0, "tenant_roles.expires_at": { "$lte": now as i64 } },
+                doc! {
+                    "$pull": { "tenant_roles": { "expires_at": { "$lte": now as i64 } } },
+                    "$inc": { "version": 1 },
+                    "$set": { "updated_at": now as i64 },
+                },
+                None,
+            )
+            .await?;
+
+        Ok(result.modified_count)
+    }
+
+    /// move the active accounts past their expiry date to expired, returns how many expired
+    pub async fn expire_accounts(&self, now: u64, database: &Database) -> Result<u64> {
+        let change = doc! {
+            "from": AccountStatus::Active.as_str(),
+            "to": AccountStatus::Expired.as_str(),
+            "reason": "账号到期",
+            "actor": SYSTEM_ACTOR,
+            "at": now as i64,
+        };
+
+        let result = database
+            .collection::<User>(self.coll_name.as_str())
+            .update_many(
+                doc! {
+                    "deleted_at": 0,
+                    "status": AccountStatus::Active.as_str(),
+                    "expires_at": { "$lte": now as i64 },
+                },
+                doc! {
+                    "$set": { "status": AccountStatus::Expired.as_str(), "updated_at": now as i64 },
+                    "$push": { "status_changes": change },
+                    "$inc": { "version": 1 },
+                },
+                None,
+            )
+            .await?;
+
+        Ok(result.modified_count)
+    }
+
+    /// replace the `is_active` flag of the users stored before the account status existed,
+    /// returns the number of users migrated.
+    ///
+    /// inactive users without a password were invited and are still pending.
+    pub async fn migrate_status(&self, database: &Database) -> Result<u64> {
+        let collection = database.collection::<User>(self.coll_name.as_str());
+        let steps = [
+            (doc! { "is_active": true }, AccountStatus::Active),
+            (
+                doc! { "is_active": false, "secret.password": "" },
+                AccountStatus::Pending,
+            ),
+            (doc! { "is_active": false }, AccountStatus::Disabled),
+        ];
+
+        let mut migrated = 0;
+        for (filter, status) in steps {
+            let result = collection
+                .update_many(
+                    filter,
+                    doc! {
+                        "$set": { "status": status.as_str() },
+                        "$unset": { "is_active": "" },
+                    },
+                    None,
+                )
+                .await?;
+            migrated += result.modified_count;
+        }
+
+        Ok(migrated)
+    }
+
+    /// returns the number of users with a grant taking effect after `since`, up to `now`
+    pub async fn count_started_grants(
+        &self,
+        since: u64,
+        now: u64,
+        database: &Database,
+    ) -> Result<u64> {
+        let count = database
+            .collection::<User>(self.coll_name.as_str())
+            .count_documents(
+                doc! {
+                    "deleted_at": 0,
+                    "tenant_roles": { "$elemMatch": {
+                        "starts_at": { "$gt": since as i64, "$lte": now as i64 }
+                    } },
+                },
+                None,
+            )
+            .await?;
+
+        Ok(count)
+    }
+
+    /// returns the users holding a grant that expires before the timestamp
+    pub async fn find_expiring(&self, before: u64, database: &Database) -> Result<Vec<User>> {
+        let cursor = database
+            .collection::<User>(self.coll_name.as_str())
+            .find(
+                doc! { "deleted_at": 0, "tenant_roles.expires_at": { "$lte": before as i64 } },
+                None,
+            )
+            .await?;
+
+        cursor_to_vec(cursor).await
+    }
 }
 
+// a user owns its own document
+impl_repository!(
+    UserRepository,
+    User,
+    USER,
+    scoped {
+        owner: "id",
+        department: "department_id"
+    }
+);
+
 #[async_trait]
 impl fetcher::RBACUserFetcher for UserRepository {
     async fn find_all(
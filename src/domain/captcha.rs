@@ -0,0 +1,93 @@
+use hmac::{Hmac, Mac};
+use rand::Rng;
+use sha2::Sha256;
+
+/// an arithmetic question for public forms.
+///
+/// the answer is not stored: the token carries its expiry and a signature of the answer,
+/// so a token can be replayed until it expires, which the rate limit of the form bounds.
+pub struct Challenge {
+    pub question: String,
+    pub token: String,
+}
+
+impl Challenge {
+    pub fn new(secret: &str, now: u64, ttl: u64) -> Self {
+        let mut rng = rand::thread_rng();
+        let (a, b): (u32, u32) = (rng.gen_range(1..20), rng.gen_range(1..20));
+        let nonce: u64 = rng.gen();
+        let expires_at = now + ttl;
+
+        Challenge {
+            question: format!("{} + {} = ?", a, b),
+            token: format!(
+                "{:x}.{}.{}",
+                nonce,
+                expires_at,
+                sign(
+                    secret,
+                    &format!("{:x}", nonce),
+                    expires_at,
+                    &(a + b).to_string()
+                )
+            ),
+        }
+    }
+}
+
+/// returns true if the answer is the one of the unexpired token
+pub fn verify(secret: &str, token: &str, answer: &str, now: u64) -> bool {
+    let mut parts = token.split('.');
+    let (Some(nonce), Some(expires_at), Some(signature), None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return false;
+    };
+
+    let Ok(expires_at) = expires_at.parse::<u64>() else {
+        return false;
+    };
+
+    now < expires_at && sign(secret, nonce, expires_at, answer.trim()) == signature
+}
+
+fn sign(secret: &str, nonce: &str, expires_at: u64, answer: &str) -> String {
+    // hmac accepts keys of any length
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(format!("{}.{}.{}", nonce, expires_at, answer).as_bytes());
+
+    mac.finalize()
+        .into_bytes()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_answer() {
+        let challenge = Challenge::new("secret", 100, 60);
+        let answer = challenge
+            .question
+            .trim_end_matches(" = ?")
+            .split(" + ")
+            .map(|item| item.parse::<u32>().unwrap())
+            .sum::<u32>()
+            .to_string();
+
+        assert!(verify("secret", &challenge.token, &answer, 120));
+        assert!(verify(
+            "secret",
+            &challenge.token,
+            &format!(" {} ", answer),
+            120
+        ));
+        assert!(!verify("secret", &challenge.token, "100", 120));
+        assert!(!verify("other", &challenge.token, &answer, 120));
+        assert!(!verify("secret", &challenge.token, &answer, 160));
+        assert!(!verify("secret", "broken", &answer, 120));
+    }
+}
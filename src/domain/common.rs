@@ -3,6 +3,17 @@ use serde::{Deserialize, Serialize};
 
 pub type Period = (u64, u64);
 
+/// tenant used when a role or token does not name one
+pub const DEFAULT_TENANT: &str = "default";
+
+pub fn default_tenant() -> String {
+    DEFAULT_TENANT.to_string()
+}
+
+/// realm of the back-office staff, the users of the `users` collection.
+/// tokens that do not name an audience belong to it
+pub const STAFF_REALM: &str = "staff";
+
 #[derive(Debug, Serialize, Deserialize, Clone, Copy)]
 pub struct DateRange {
     pub start: Option<u64>,
@@ -0,0 +1,212 @@
+use serde::{Deserialize, Serialize};
+
+use super::{
+    common::default_tenant,
+    errors::{Error, Result},
+    BaseModel,
+};
+
+/// a node of the organisation tree
+///
+/// `path` is the materialised path of ids from the root down to the department itself,
+/// such as `/1/5/9/`, a subtree is every department whose path starts with the root's path.
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct Department {
+    #[serde(flatten)]
+    pub base: BaseModel,
+    #[serde(default = "default_tenant")]
+    pub tenant: String,
+    pub name: String,
+    /// empty for a root department
+    pub parent_id: String,
+    pub path: String,
+    /// ids of the users leading the department, they are members of it
+    pub head_ids: Vec<String>,
+    /// position among the siblings, ascending
+    pub sort: i32,
+}
+
+impl Department {
+    pub fn new(
+        id: String,
+        tenant: String,
+        name: String,
+        parent: Option<&Department>,
+        sort: i32,
+    ) -> Self {
+        let mut department = Department {
+            base: BaseModel::new(id),
+            tenant,
+            name,
+            sort,
+            ..Default::default()
+        };
+        department.set_parent(parent);
+        department
+    }
+
+    /// returns true if the department is the other one or one of its ancestors
+    pub fn contains(&self, other: &Department) -> bool {
+        other.path.starts_with(&self.path)
+    }
+
+    /// check the department can be moved under the parent, `None` moves it to the root
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the parent is the department or one of its
+    /// descendants.
+    pub fn check_move(&self, parent: Option<&Department>) -> Result<()> {
+        if let Some(parent) = parent {
+            if self.contains(parent) {
+                return Err(Error::LogicError(
+                    "不能移动到自己或下级部门之下".to_string(),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// move a descendant along with its moved ancestor, whose path changed from `old_prefix`
+    /// to `new_prefix`.
+    pub fn rebase(&mut self, old_prefix: &str, new_prefix: &str) {
+        if let Some(rest) = self.path.strip_prefix(old_prefix) {
+            self.path = format!("{}{}", new_prefix, rest);
+        }
+    }
+
+    fn set_parent(&mut self, parent: Option<&Department>) {
+        match parent {
+            Some(parent) => {
+                self.parent_id = parent.base.id.clone();
+                self.path = format!("{}{}/", parent.path, self.base.id);
+            }
+            None => {
+                self.parent_id = String::new();
+                self.path = format!("/{}/", self.base.id);
+            }
+        }
+    }
+
+    /// move the department under the parent, after [Department::check_move],
+    /// returns the old and new path that descendants are rebased with.
+    pub fn move_under(&mut self, parent: Option<&Department>) -> (String, String) {
+        let old_path = self.path.clone();
+        self.set_parent(parent);
+
+        (old_path, self.path.clone())
+    }
+}
+
+/// a department with its children, for browsing the tree
+#[derive(Debug, Serialize)]
+pub struct DepartmentNode {
+    #[serde(flatten)]
+    pub department: Department,
+    pub children: Vec<DepartmentNode>,
+}
+
+/// build the trees of the departments, the departments without a parent among them are roots.
+///
+/// siblings are ordered by `sort`, then by name.
+pub fn build_tree(mut departments: Vec<Department>) -> Vec<DepartmentNode> {
+    departments.sort_by(|a, b| a.sort.cmp(&b.sort).then_with(|| a.name.cmp(&b.name)));
+
+    let ids: Vec<String> = departments
+        .iter()
+        .map(|item| item.base.id.clone())
+        .collect();
+    let (roots, rest): (Vec<Department>, Vec<Department>) = departments
+        .into_iter()
+        .partition(|item| !ids.contains(&item.parent_id));
+
+    let mut rest = rest;
+    roots
+        .into_iter()
+        .map(|root| attach_children(root, &mut rest))
+        .collect()
+}
+
+fn attach_children(department: Department, rest: &mut Vec<Department>) -> DepartmentNode {
+    let (children, others): (Vec<Department>, Vec<Department>) = std::mem::take(rest)
+        .into_iter()
+        .partition(|item| item.parent_id == department.base.id);
+    *rest = others;
+
+    DepartmentNode {
+        department,
+        children: children
+            .into_iter()
+            .map(|child| attach_children(child, rest))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn department(id: &str, parent: Option<&Department>) -> Department {
+        Department::new(
+            id.to_string(),
+            "default".to_string(),
+            format!("d{}", id),
+            parent,
+            0,
+        )
+    }
+
+    #[test]
+    fn test_materialised_path() {
+        let root = department("1", None);
+        let child = department("2", Some(&root));
+        let grandchild = department("3", Some(&child));
+
+        assert_eq!(grandchild.path, "/1/2/3/");
+        assert!(root.contains(&grandchild));
+        assert!(!grandchild.contains(&root));
+    }
+
+    #[test]
+    fn test_move_rejects_cycles() {
+        let root = department("1", None);
+        let child = department("2", Some(&root));
+        let other = department("4", None);
+
+        assert!(root.check_move(Some(&child)).is_err());
+        assert!(root.check_move(Some(&root)).is_err());
+        assert!(child.check_move(Some(&other)).is_ok());
+    }
+
+    #[test]
+    fn test_move_rebases_descendants() {
+        let root = department("1", None);
+        let mut child = department("2", Some(&root));
+        let mut grandchild = department("3", Some(&child));
+        let other = department("4", None);
+
+        let (old_path, new_path) = child.move_under(Some(&other));
+        grandchild.rebase(&old_path, &new_path);
+
+        assert_eq!(child.path, "/4/2/");
+        assert_eq!(grandchild.path, "/4/2/3/");
+    }
+
+    #[test]
+    fn test_build_tree() {
+        let root = department("1", None);
+        let child = department("2", Some(&root));
+        let grandchild = department("3", Some(&child));
+
+        let tree = build_tree(vec![grandchild, root, child]);
+
+        assert_eq!(tree.len(), 1);
+        assert_eq!(tree[0].children[0].children[0].department.base.id, "3");
+
+        // a subtree without its ancestors
+        let tree = build_tree(vec![department("3", Some(&department("2", None)))]);
+        assert_eq!(tree[0].department.base.id, "3");
+    }
+}
@@ -0,0 +1,196 @@
+use rand::{distributions::Alphanumeric, Rng};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::{
+    common::default_tenant,
+    errors::{Error, Result},
+    BaseModel,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InvitationStatus {
+    Pending,
+    Accepted,
+    Revoked,
+    Expired,
+}
+
+/// an invitation for a pending user to set their password and activate the account.
+///
+/// only the hash of the token is stored, the token itself is only sent to the invitee.
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct Invitation {
+    #[serde(flatten)]
+    pub base: BaseModel,
+    #[serde(default = "default_tenant")]
+    pub tenant: String,
+    /// the pending user
+    pub user_id: String,
+    /// email address the invitation is sent to
+    pub contact: String,
+    /// id of the admin who sent the invitation
+    pub invited_by: String,
+    pub token_hash: String,
+    pub expires_at: u64,
+    pub accepted_at: u64,
+    pub revoked_at: u64,
+    /// times the invitation was sent
+    pub sent_count: u32,
+    /// error of the last failed delivery, empty once delivered
+    pub last_error: String,
+}
+
+/// returns the stored form of an invitation token
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+/// returns a random token to send in a link, only its hash should be stored
+pub fn generate_token() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(40)
+        .map(char::from)
+        .collect()
+}
+
+impl Invitation {
+    pub fn new(
+        id: String,
+        tenant: String,
+        user_id: String,
+        contact: String,
+        invited_by: String,
+    ) -> Self {
+        Invitation {
+            base: BaseModel::new(id),
+            tenant,
+            user_id,
+            contact,
+            invited_by,
+            ..Default::default()
+        }
+    }
+
+    pub fn status(&self, now: u64) -> InvitationStatus {
+        if self.accepted_at > 0 {
+            InvitationStatus::Accepted
+        } else if self.revoked_at > 0 {
+            InvitationStatus::Revoked
+        } else if self.expires_at <= now {
+            InvitationStatus::Expired
+        } else {
+            InvitationStatus::Pending
+        }
+    }
+
+    /// generate a new token valid for `ttl` seconds, the previous token stops working.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the invitation is accepted or revoked.
+    pub fn issue(&mut self, now: u64, ttl: u64) -> Result<String> {
+        self.check_open(now, true)?;
+
+        let token = generate_token();
+
+        self.token_hash = hash_token(&token);
+        self.expires_at = now + ttl;
+        self.sent_count += 1;
+
+        Ok(token)
+    }
+
+    /// use the invitation, a token works only once.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the invitation is not pending.
+    pub fn accept(&mut self, now: u64) -> Result<()> {
+        self.check_open(now, false)?;
+        self.accepted_at = now;
+        self.token_hash = String::new();
+
+        Ok(())
+    }
+
+    /// # Errors
+    ///
+    /// This function will return an error if the invitation is accepted or revoked.
+    pub fn revoke(&mut self, now: u64) -> Result<()> {
+        self.check_open(now, true)?;
+        self.revoked_at = now;
+        self.token_hash = String::new();
+
+        Ok(())
+    }
+
+    fn check_open(&self, now: u64, allow_expired: bool) -> Result<()> {
+        match self.status(now) {
+            InvitationStatus::Pending => Ok(()),
+            InvitationStatus::Expired if allow_expired => Ok(()),
+            InvitationStatus::Expired => Err(Error::LogicError("邀请已过期".to_string())),
+            InvitationStatus::Accepted => Err(Error::LogicError("邀请已被接受".to_string())),
+            InvitationStatus::Revoked => Err(Error::LogicError("邀请已被撤销".to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invitation() -> Invitation {
+        Invitation::new(
+            "1".to_string(),
+            "default".to_string(),
+            "2".to_string(),
+            "alice@example.com".to_string(),
+            "3".to_string(),
+        )
+    }
+
+    #[test]
+    fn test_issue_replaces_token() {
+        let mut invitation = invitation();
+
+        let first = invitation.issue(100, 60).unwrap();
+        let second = invitation.issue(100, 60).unwrap();
+
+        assert_ne!(first, second);
+        assert_eq!(invitation.token_hash, hash_token(&second));
+        assert_eq!(invitation.sent_count, 2);
+        assert_eq!(invitation.status(159), InvitationStatus::Pending);
+        assert_eq!(invitation.status(160), InvitationStatus::Expired);
+    }
+
+    #[test]
+    fn test_accept_once() {
+        let mut invitation = invitation();
+        invitation.issue(100, 60).unwrap();
+
+        assert!(invitation.accept(200).is_err());
+
+        invitation.issue(200, 60).unwrap();
+        assert!(invitation.accept(210).is_ok());
+        assert_eq!(invitation.status(210), InvitationStatus::Accepted);
+        assert!(invitation.token_hash.is_empty());
+        assert!(invitation.accept(210).is_err());
+        assert!(invitation.issue(210, 60).is_err());
+        assert!(invitation.revoke(210).is_err());
+    }
+
+    #[test]
+    fn test_revoke() {
+        let mut invitation = invitation();
+        invitation.issue(100, 60).unwrap();
+
+        invitation.revoke(110).unwrap();
+
+        assert_eq!(invitation.status(110), InvitationStatus::Revoked);
+        assert!(invitation.accept(110).is_err());
+    }
+}
@@ -0,0 +1,84 @@
+use mongodb::bson;
+use serde::{Deserialize, Serialize};
+
+use super::{user::AccountStatus, BaseModel};
+
+/// why a login attempt was refused
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LoginFailure {
+    UnknownAccount,
+    WrongPassword,
+    Pending,
+    Locked,
+    Disabled,
+    Expired,
+    NotInTenant,
+}
+
+impl LoginFailure {
+    /// returns the failure for an account in the status, none if it may sign in
+    pub fn for_status(status: AccountStatus) -> Option<Self> {
+        match status {
+            AccountStatus::Active => None,
+            AccountStatus::Pending => Some(LoginFailure::Pending),
+            AccountStatus::Locked => Some(LoginFailure::Locked),
+            AccountStatus::Disabled => Some(LoginFailure::Disabled),
+            AccountStatus::Expired => Some(LoginFailure::Expired),
+        }
+    }
+}
+
+/// a login attempt, kept for the retention period of the login history
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LoginRecord {
+    #[serde(flatten)]
+    pub base: BaseModel,
+    /// empty when the account does not exist
+    pub user_id: String,
+    pub account: String,
+    pub tenant: String,
+    pub success: bool,
+    pub failure: Option<LoginFailure>,
+    pub ip: String,
+    pub user_agent: String,
+    /// time of the attempt, records expire by this date
+    pub logged_at: bson::DateTime,
+}
+
+impl LoginRecord {
+    pub fn new(
+        id: String,
+        account: String,
+        tenant: String,
+        ip: String,
+        user_agent: String,
+    ) -> Self {
+        let base = BaseModel::new(id);
+        let logged_at = bson::DateTime::from_millis(base.created_at as i64 * 1000);
+
+        LoginRecord {
+            base,
+            user_id: String::new(),
+            account,
+            tenant,
+            success: false,
+            failure: None,
+            ip,
+            user_agent,
+            logged_at,
+        }
+    }
+
+    pub fn succeed(&mut self, user_id: String) {
+        self.user_id = user_id;
+        self.success = true;
+        self.failure = None;
+    }
+
+    pub fn fail(&mut self, user_id: String, failure: LoginFailure) {
+        self.user_id = user_id;
+        self.success = false;
+        self.failure = Some(failure);
+    }
+}
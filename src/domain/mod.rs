@@ -1,8 +1,16 @@
 mod base;
+pub mod captcha;
 pub mod common;
+pub mod department;
 pub mod errors;
+pub mod invitation;
+pub mod login_record;
+pub mod personal_data;
+pub mod policy_bundle;
+pub mod registration;
 pub mod role;
 pub mod user;
+pub mod user_import;
 
 pub use base::BaseModel;
 pub use base::Model;
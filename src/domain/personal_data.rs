@@ -0,0 +1,174 @@
+use std::io::{Cursor, Write};
+
+use serde::Serialize;
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+use super::{
+    errors::{Error, Result},
+    invitation::Invitation,
+    login_record::LoginRecord,
+    registration::Registration,
+    user::User,
+};
+
+/// a record of someone else that refers to the user by id
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Reference {
+    pub collection: String,
+    pub id: String,
+    /// the field holding the user id
+    pub field: String,
+}
+
+impl Reference {
+    pub fn new(collection: &str, id: &str, field: &str) -> Self {
+        Reference {
+            collection: collection.to_string(),
+            id: id.to_string(),
+            field: field.to_string(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Manifest<'a> {
+    user_id: &'a str,
+    exported_at: u64,
+    files: Vec<&'a str>,
+    notes: Vec<&'a str>,
+}
+
+/// everything stored about a user, answering a data subject access request.
+///
+/// secrets are left out: the password hash and the hashes of invitation and
+/// verification tokens.
+pub struct PersonalData {
+    pub exported_at: u64,
+    pub profile: User,
+    pub login_history: Vec<LoginRecord>,
+    pub invitations: Vec<Invitation>,
+    pub registrations: Vec<Registration>,
+    pub references: Vec<Reference>,
+}
+
+impl PersonalData {
+    pub fn new(
+        exported_at: u64,
+        mut profile: User,
+        login_history: Vec<LoginRecord>,
+        mut invitations: Vec<Invitation>,
+        mut registrations: Vec<Registration>,
+        references: Vec<Reference>,
+    ) -> Self {
+        profile.secret.password = String::new();
+        for invitation in invitations.iter_mut() {
+            invitation.token_hash = String::new();
+        }
+        for registration in registrations.iter_mut() {
+            registration.token_hash = String::new();
+        }
+
+        PersonalData {
+            exported_at,
+            profile,
+            login_history,
+            invitations,
+            registrations,
+            references,
+        }
+    }
+
+    /// returns the zip archive with one json file per kind of data
+    pub fn to_archive(&self) -> Result<Vec<u8>> {
+        let files: Vec<(&str, Vec<u8>)> = vec![
+            ("profile.json", to_json(&self.profile)?),
+            ("login_history.json", to_json(&self.login_history)?),
+            ("invitations.json", to_json(&self.invitations)?),
+            ("registrations.json", to_json(&self.registrations)?),
+            ("references.json", to_json(&self.references)?),
+        ];
+
+        let manifest = to_json(&Manifest {
+            user_id: &self.profile.base.id,
+            exported_at: self.exported_at,
+            files: files.iter().map(|(name, _)| *name).collect(),
+            notes: vec![
+                "登录令牌不在服务端保存, 没有会话记录",
+                "references.json 列出其他记录中引用该用户ID的位置",
+            ],
+        })?;
+
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        let mut writer = ZipWriter::new(Cursor::new(vec![]));
+
+        for (name, content) in std::iter::once(("manifest.json", manifest)).chain(files) {
+            writer.start_file(name, options).map_err(archive_error)?;
+            writer.write_all(&content).map_err(archive_error)?;
+        }
+
+        let cursor = writer.finish().map_err(archive_error)?;
+        Ok(cursor.into_inner())
+    }
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<Vec<u8>> {
+    serde_json::to_vec_pretty(value)
+        .map_err(|err| Error::LogicError(format!("无法导出JSON: {}", err)))
+}
+
+fn archive_error<E: std::fmt::Display>(err: E) -> Error {
+    Error::LogicError(format!("无法生成压缩包: {}", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use crate::domain::common::Secret;
+
+    use super::*;
+
+    #[test]
+    fn test_archive_leaves_out_secrets() {
+        let mut user = User {
+            secret: Secret::new("alice".to_string(), "secret".to_string()).unwrap(),
+            name: "Alice".to_string(),
+            ..Default::default()
+        };
+        user.base.id = "1".to_string();
+
+        let mut registration = Registration::new(
+            "2".to_string(),
+            "staff".to_string(),
+            "1".to_string(),
+            "alice".to_string(),
+            "alice@example.com".to_string(),
+            "127.0.0.1".to_string(),
+        );
+        registration.issue(100, 60);
+
+        let data = PersonalData::new(100, user, vec![], vec![], vec![registration], vec![]);
+        let archive = data.to_archive().unwrap();
+
+        let mut archive = zip::ZipArchive::new(Cursor::new(archive)).unwrap();
+        assert_eq!(archive.len(), 6);
+
+        let mut profile = String::new();
+        archive
+            .by_name("profile.json")
+            .unwrap()
+            .read_to_string(&mut profile)
+            .unwrap();
+        assert!(profile.contains("Alice"));
+        assert!(profile.contains(r#""password": """#));
+
+        let mut registrations = String::new();
+        archive
+            .by_name("registrations.json")
+            .unwrap()
+            .read_to_string(&mut registrations)
+            .unwrap();
+        assert!(registrations.contains("127.0.0.1"));
+        assert!(registrations.contains(r#""token_hash": """#));
+    }
+}
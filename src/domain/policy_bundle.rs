@@ -0,0 +1,670 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    str::FromStr,
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    errors::{Error, Result},
+    role::{DataScope, Effect, Role, RouteItem},
+    user::User,
+};
+
+/// a role of a policy bundle
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BundleRole {
+    pub tenant: String,
+    pub name: String,
+    #[serde(default)]
+    pub permissions: Vec<RouteItem>,
+    #[serde(default)]
+    pub data_scope: DataScope,
+}
+
+/// a role held by an account inside a tenant
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BundleAssignment {
+    pub account: String,
+    pub tenant: String,
+    pub role_name: String,
+}
+
+/// all roles and user-role assignments, portable between deployments
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PolicyBundle {
+    #[serde(default)]
+    pub roles: Vec<BundleRole>,
+    #[serde(default)]
+    pub assignments: Vec<BundleAssignment>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BundleFormat {
+    Json,
+    Csv,
+}
+
+impl FromStr for BundleFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(BundleFormat::Json),
+            "csv" => Ok(BundleFormat::Csv),
+            _ => Err(format!(
+                "unknown bundle format: {}, expected json or csv",
+                s
+            )),
+        }
+    }
+}
+
+/// what to do with roles and assignments that already exist with different content
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictStrategy {
+    /// keep the existing data
+    Skip,
+    /// replace the existing data with the bundle
+    Overwrite,
+    /// add the permissions and roles of the bundle to the existing data
+    Merge,
+}
+
+impl FromStr for ConflictStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "skip" => Ok(ConflictStrategy::Skip),
+            "overwrite" => Ok(ConflictStrategy::Overwrite),
+            "merge" => Ok(ConflictStrategy::Merge),
+            _ => Err(format!(
+                "unknown conflict strategy: {}, expected skip, overwrite or merge",
+                s
+            )),
+        }
+    }
+}
+
+/// one line of the csv format, `kind` is one of `role`, `permission` and `assignment`
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CsvRow {
+    kind: String,
+    tenant: String,
+    role: String,
+    #[serde(default)]
+    account: String,
+    #[serde(default)]
+    module: String,
+    #[serde(default)]
+    path: String,
+    #[serde(default)]
+    description: String,
+    #[serde(default)]
+    effect: String,
+    /// json encoded [DataScope] of a role
+    #[serde(default)]
+    data_scope: String,
+}
+
+impl PolicyBundle {
+    /// returns the bundle of the roles and the role assignments of the users
+    pub fn from_state(roles: &[Role], users: &[User]) -> Self {
+        let roles = roles
+            .iter()
+            .map(|role| BundleRole {
+                tenant: role.tenant.clone(),
+                name: role.name.clone(),
+                permissions: role.permissions.clone(),
+                data_scope: role.data_scope.clone(),
+            })
+            .collect();
+
+        let assignments = users
+            .iter()
+            .flat_map(|user| {
+                user.assignments().into_iter().map(|item| BundleAssignment {
+                    account: user.secret.account.clone(),
+                    tenant: item.tenant,
+                    role_name: item.role_name,
+                })
+            })
+            .collect();
+
+        PolicyBundle { roles, assignments }
+    }
+
+    /// returns the roles and assignments of the tenant, the rest of the bundle is left out
+    pub fn of_tenant(&self, tenant: &str) -> Self {
+        PolicyBundle {
+            roles: self
+                .roles
+                .iter()
+                .filter(|role| role.tenant == tenant)
+                .cloned()
+                .collect(),
+            assignments: self
+                .assignments
+                .iter()
+                .filter(|assignment| assignment.tenant == tenant)
+                .cloned()
+                .collect(),
+        }
+    }
+
+    pub fn encode(&self, format: BundleFormat) -> Result<String> {
+        match format {
+            BundleFormat::Json => serde_json::to_string_pretty(self)
+                .map_err(|err| Error::LogicError(format!("无法导出JSON: {}", err))),
+            BundleFormat::Csv => self.to_csv(),
+        }
+    }
+
+    pub fn decode(content: &str, format: BundleFormat) -> Result<Self> {
+        match format {
+            BundleFormat::Json => serde_json::from_str(content)
+                .map_err(|err| Error::LogicError(format!("无法解析JSON: {}", err))),
+            BundleFormat::Csv => Self::from_csv(content),
+        }
+    }
+
+    fn to_csv(&self) -> Result<String> {
+        let mut rows = vec![];
+
+        for role in self.roles.iter() {
+            let data_scope = serde_json::to_string(&role.data_scope)
+                .map_err(|err| Error::LogicError(format!("无法导出数据范围: {}", err)))?;
+
+            rows.push(CsvRow {
+                kind: "role".to_string(),
+                tenant: role.tenant.clone(),
+                role: role.name.clone(),
+                data_scope,
+                ..Default::default()
+            });
+
+            for permission in role.permissions.iter() {
+                rows.push(CsvRow {
+                    kind: "permission".to_string(),
+                    tenant: role.tenant.clone(),
+                    role: role.name.clone(),
+                    module: permission.module.clone(),
+                    path: permission.path.clone(),
+                    description: permission.description.clone(),
+                    effect: permission.effect.as_str().to_string(),
+                    ..Default::default()
+                });
+            }
+        }
+
+        for assignment in self.assignments.iter() {
+            rows.push(CsvRow {
+                kind: "assignment".to_string(),
+                tenant: assignment.tenant.clone(),
+                role: assignment.role_name.clone(),
+                account: assignment.account.clone(),
+                ..Default::default()
+            });
+        }
+
+        let mut writer = csv::Writer::from_writer(vec![]);
+        for row in rows {
+            writer
+                .serialize(row)
+                .map_err(|err| Error::LogicError(format!("无法导出CSV: {}", err)))?;
+        }
+
+        let bytes = writer
+            .into_inner()
+            .map_err(|err| Error::LogicError(format!("无法导出CSV: {}", err)))?;
+
+        String::from_utf8(bytes).map_err(|err| Error::LogicError(format!("无法导出CSV: {}", err)))
+    }
+
+    fn from_csv(content: &str) -> Result<Self> {
+        let mut roles: Vec<BundleRole> = vec![];
+        let mut assignments = vec![];
+
+        let mut reader = csv::Reader::from_reader(content.as_bytes());
+        for (index, row) in reader.deserialize::<CsvRow>().enumerate() {
+            // the header is line 1
+            let line = index + 2;
+            let row = row.map_err(|err| Error::LogicError(format!("无法解析CSV: {}", err)))?;
+
+            match row.kind.as_str() {
+                "role" => {
+                    let data_scope = match row.data_scope.is_empty() {
+                        true => DataScope::All,
+                        false => serde_json::from_str(&row.data_scope).map_err(|err| {
+                            Error::LogicError(format!("第{}行数据范围错误: {}", line, err))
+                        })?,
+                    };
+
+                    roles.push(BundleRole {
+                        tenant: row.tenant,
+                        name: row.role,
+                        permissions: vec![],
+                        data_scope,
+                    });
+                }
+                "permission" => {
+                    let effect = match row.effect.as_str() {
+                        "" | "allow" => Effect::Allow,
+                        "deny" => Effect::Deny,
+                        other => {
+                            return Err(Error::LogicError(format!(
+                                "第{}行权限效果错误: {}",
+                                line, other
+                            )))
+                        }
+                    };
+
+                    let role = roles
+                        .iter_mut()
+                        .find(|role| role.tenant == row.tenant && role.name == row.role)
+                        .ok_or(Error::LogicError(format!(
+                            "第{}行引用了未定义的角色: {}",
+                            line, row.role
+                        )))?;
+
+                    role.permissions.push(RouteItem {
+                        module: row.module,
+                        path: row.path,
+                        description: row.description,
+                        effect,
+                    });
+                }
+                "assignment" => assignments.push(BundleAssignment {
+                    account: row.account,
+                    tenant: row.tenant,
+                    role_name: row.role,
+                }),
+                other => {
+                    return Err(Error::LogicError(format!(
+                        "第{}行类型错误: {}",
+                        line, other
+                    )))
+                }
+            }
+        }
+
+        Ok(PolicyBundle { roles, assignments })
+    }
+
+    /// compare the bundle with the current roles and users.
+    ///
+    /// only the roles and the (account, tenant) pairs named in the bundle are touched,
+    /// everything else is kept as is.
+    pub fn plan(&self, roles: &[Role], users: &[User], strategy: ConflictStrategy) -> ImportPlan {
+        let mut plan = ImportPlan {
+            strategy,
+            ..Default::default()
+        };
+
+        for item in self.roles.iter() {
+            let existing = roles
+                .iter()
+                .find(|role| role.tenant == item.tenant && role.name == item.name);
+
+            let change = match existing {
+                None => RoleChange::new(
+                    item,
+                    ChangeAction::Create,
+                    &[],
+                    Some(Role::new(
+                        String::new(),
+                        item.tenant.clone(),
+                        item.name.clone(),
+                        item.permissions.clone(),
+                        item.data_scope.clone(),
+                    )),
+                ),
+                Some(role) => {
+                    let mut target = role.clone();
+                    match strategy {
+                        ConflictStrategy::Skip => {}
+                        ConflictStrategy::Overwrite => {
+                            target.permissions = item.permissions.clone();
+                            target.data_scope = item.data_scope.clone();
+                        }
+                        ConflictStrategy::Merge => {
+                            for permission in item.permissions.iter() {
+                                if !target.permissions.iter().any(|p| p.path == permission.path) {
+                                    target.permissions.push(permission.clone());
+                                }
+                            }
+                        }
+                    }
+
+                    let conflicted =
+                        role.permissions != item.permissions || role.data_scope != item.data_scope;
+
+                    if target.permissions != role.permissions
+                        || target.data_scope != role.data_scope
+                    {
+                        RoleChange::new(
+                            &bundle_role(&target),
+                            ChangeAction::Update,
+                            &role.permissions,
+                            Some(target),
+                        )
+                    } else if conflicted && strategy == ConflictStrategy::Skip {
+                        RoleChange::new(item, ChangeAction::Skip, &role.permissions, None)
+                    } else {
+                        RoleChange::new(item, ChangeAction::Unchanged, &role.permissions, None)
+                    }
+                }
+            };
+
+            plan.roles.push(change);
+        }
+
+        // roles requested per (account, tenant)
+        let mut requested: BTreeMap<(String, String), BTreeSet<String>> = BTreeMap::new();
+        for item in self.assignments.iter() {
+            let role_exists = roles
+                .iter()
+                .any(|role| role.tenant == item.tenant && role.name == item.role_name)
+                || self
+                    .roles
+                    .iter()
+                    .any(|role| role.tenant == item.tenant && role.name == item.role_name);
+
+            if !role_exists {
+                plan.errors.push(format!(
+                    "用户{}引用了租户{}中不存在的角色{}",
+                    item.account, item.tenant, item.role_name
+                ));
+                continue;
+            }
+
+            requested
+                .entry((item.account.clone(), item.tenant.clone()))
+                .or_default()
+                .insert(item.role_name.clone());
+        }
+
+        let mut changed_users: BTreeMap<String, User> = BTreeMap::new();
+        for ((account, tenant), role_names) in requested {
+            let user = match changed_users.get(&account) {
+                Some(user) => user.clone(),
+                None => match users.iter().find(|user| user.secret.account == account) {
+                    Some(user) => user.clone(),
+                    None => {
+                        plan.errors.push(format!("用户{}不存在", account));
+                        continue;
+                    }
+                },
+            };
+
+            let before: BTreeSet<String> = user.roles_in(&tenant).into_iter().collect();
+            let after: BTreeSet<String> = match strategy {
+                ConflictStrategy::Skip if !before.is_empty() => before.clone(),
+                ConflictStrategy::Skip | ConflictStrategy::Overwrite => role_names.clone(),
+                ConflictStrategy::Merge => before.union(&role_names).cloned().collect(),
+            };
+
+            let action = if after != before {
+                ChangeAction::Update
+            } else if before != role_names {
+                ChangeAction::Skip
+            } else {
+                ChangeAction::Unchanged
+            };
+
+            if action == ChangeAction::Update {
+                let mut user = user;
+                user.set_roles_in(&tenant, &after.iter().cloned().collect::<Vec<_>>());
+                changed_users.insert(account.clone(), user);
+            }
+
+            plan.assignments.push(AssignmentChange {
+                account,
+                tenant,
+                before: before.into_iter().collect(),
+                after: after.into_iter().collect(),
+                action,
+            });
+        }
+
+        plan.users = changed_users.into_values().collect();
+        plan
+    }
+}
+
+fn bundle_role(role: &Role) -> BundleRole {
+    BundleRole {
+        tenant: role.tenant.clone(),
+        name: role.name.clone(),
+        permissions: role.permissions.clone(),
+        data_scope: role.data_scope.clone(),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeAction {
+    Create,
+    Update,
+    Unchanged,
+    /// differs from the bundle, but kept because of the skip strategy
+    Skip,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RoleChange {
+    pub tenant: String,
+    pub name: String,
+    pub action: ChangeAction,
+    /// permissions in the bundle but not in the existing role, as `effect:path`
+    pub added_permissions: Vec<String>,
+    /// permissions in the existing role but not in the bundle, as `effect:path`
+    pub removed_permissions: Vec<String>,
+    /// the role to save, ids of new roles are empty
+    #[serde(skip)]
+    pub role: Option<Role>,
+}
+
+impl RoleChange {
+    fn new(
+        item: &BundleRole,
+        action: ChangeAction,
+        existing: &[RouteItem],
+        role: Option<Role>,
+    ) -> Self {
+        let key = |p: &RouteItem| format!("{}:{}", p.effect.as_str(), p.path);
+        let wanted: BTreeSet<String> = item.permissions.iter().map(key).collect();
+        let current: BTreeSet<String> = existing.iter().map(key).collect();
+
+        RoleChange {
+            tenant: item.tenant.clone(),
+            name: item.name.clone(),
+            action,
+            added_permissions: wanted.difference(&current).cloned().collect(),
+            removed_permissions: current.difference(&wanted).cloned().collect(),
+            role,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct AssignmentChange {
+    pub account: String,
+    pub tenant: String,
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+    pub action: ChangeAction,
+}
+
+/// the changes an import makes, returned as is by a dry run
+#[derive(Debug, Serialize)]
+pub struct ImportPlan {
+    pub strategy: ConflictStrategy,
+    pub roles: Vec<RoleChange>,
+    pub assignments: Vec<AssignmentChange>,
+    /// entries of the bundle that can not be imported
+    pub errors: Vec<String>,
+    /// users whose roles change
+    #[serde(skip)]
+    pub users: Vec<User>,
+}
+
+impl Default for ImportPlan {
+    fn default() -> Self {
+        ImportPlan {
+            strategy: ConflictStrategy::Skip,
+            roles: vec![],
+            assignments: vec![],
+            errors: vec![],
+            users: vec![],
+        }
+    }
+}
+
+impl ImportPlan {
+    /// returns true if applying the plan changes anything
+    pub fn has_changes(&self) -> bool {
+        self.roles.iter().any(|change| change.role.is_some()) || !self.users.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::common::Secret;
+
+    use super::*;
+
+    fn route(path: &str, effect: Effect) -> RouteItem {
+        RouteItem {
+            module: "角色管理".to_string(),
+            path: path.to_string(),
+            description: "".to_string(),
+            effect,
+        }
+    }
+
+    fn state() -> (Vec<Role>, Vec<User>) {
+        let roles = vec![Role::new(
+            "1".to_string(),
+            "default".to_string(),
+            "admin".to_string(),
+            vec![route("/roles", Effect::Allow)],
+            DataScope::All,
+        )];
+
+        let user = User {
+            secret: Secret {
+                account: "zhangsan".to_string(),
+                password: "".to_string(),
+            },
+            role_name: "admin".to_string(),
+            ..Default::default()
+        };
+
+        (roles, vec![user])
+    }
+
+    fn bundle() -> PolicyBundle {
+        PolicyBundle {
+            roles: vec![
+                BundleRole {
+                    tenant: "default".to_string(),
+                    name: "admin".to_string(),
+                    permissions: vec![route("/roles/:id", Effect::Deny)],
+                    data_scope: DataScope::Own,
+                },
+                BundleRole {
+                    tenant: "default".to_string(),
+                    name: "viewer".to_string(),
+                    permissions: vec![route("/roles", Effect::Allow)],
+                    data_scope: DataScope::All,
+                },
+            ],
+            assignments: vec![
+                BundleAssignment {
+                    account: "zhangsan".to_string(),
+                    tenant: "default".to_string(),
+                    role_name: "viewer".to_string(),
+                },
+                BundleAssignment {
+                    account: "lisi".to_string(),
+                    tenant: "default".to_string(),
+                    role_name: "viewer".to_string(),
+                },
+                BundleAssignment {
+                    account: "zhangsan".to_string(),
+                    tenant: "default".to_string(),
+                    role_name: "ghost".to_string(),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_csv_round_trip() {
+        let bundle = bundle();
+        let csv = bundle.encode(BundleFormat::Csv).unwrap();
+
+        assert_eq!(
+            PolicyBundle::decode(&csv, BundleFormat::Csv).unwrap(),
+            bundle
+        );
+    }
+
+    #[test]
+    fn test_of_tenant() {
+        let mut bundle = bundle();
+        assert_eq!(bundle.of_tenant("default"), bundle);
+
+        bundle.assignments[0].tenant = "shop".to_string();
+        let default = bundle.of_tenant("default");
+        assert_eq!(default.roles.len(), 2);
+        assert_eq!(default.assignments.len(), 2);
+        assert!(bundle.of_tenant("shop").roles.is_empty());
+    }
+
+    #[test]
+    fn test_plan_skip() {
+        let (roles, users) = state();
+        let plan = bundle().plan(&roles, &users, ConflictStrategy::Skip);
+
+        assert_eq!(plan.roles[0].action, ChangeAction::Skip);
+        assert_eq!(plan.roles[1].action, ChangeAction::Create);
+        assert_eq!(plan.assignments[0].action, ChangeAction::Skip);
+        assert!(plan.users.is_empty());
+        // unknown account and unknown role
+        assert_eq!(plan.errors.len(), 2);
+    }
+
+    #[test]
+    fn test_plan_overwrite() {
+        let (roles, users) = state();
+        let plan = bundle().plan(&roles, &users, ConflictStrategy::Overwrite);
+
+        assert_eq!(plan.roles[0].action, ChangeAction::Update);
+        assert_eq!(plan.roles[0].added_permissions, vec!["deny:/roles/:id"]);
+        assert_eq!(plan.roles[0].removed_permissions, vec!["allow:/roles"]);
+        assert_eq!(
+            plan.roles[0].role.as_ref().unwrap().data_scope,
+            DataScope::Own
+        );
+
+        assert_eq!(plan.users.len(), 1);
+        assert_eq!(plan.users[0].roles_in("default"), vec!["viewer"]);
+    }
+
+    #[test]
+    fn test_plan_merge() {
+        let (roles, users) = state();
+        let plan = bundle().plan(&roles, &users, ConflictStrategy::Merge);
+
+        let role = plan.roles[0].role.as_ref().unwrap();
+        assert_eq!(role.permissions.len(), 2);
+        assert_eq!(role.data_scope, DataScope::All);
+
+        assert_eq!(plan.users[0].roles_in("default"), vec!["admin", "viewer"]);
+    }
+}
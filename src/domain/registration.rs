@@ -0,0 +1,193 @@
+use serde::{Deserialize, Serialize};
+
+use super::{
+    errors::{Error, Result},
+    invitation::{generate_token, hash_token},
+    BaseModel,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RegistrationStatus {
+    /// the email address is not verified yet
+    Unverified,
+    /// verified, waiting for an admin to approve or reject it
+    AwaitingApproval,
+    Approved,
+    Rejected,
+    /// not verified in time
+    Expired,
+}
+
+/// a sign-up of a user, the user stays pending until the email address is verified
+/// and, if required, an admin approved the registration.
+///
+/// only the hash of the verification token is stored.
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+#[serde(default)]
+pub struct Registration {
+    #[serde(flatten)]
+    pub base: BaseModel,
+    /// realm the user signed up to
+    pub realm: String,
+    pub user_id: String,
+    pub account: String,
+    pub email: String,
+    /// address the registration was sent from, registrations are rate limited by it
+    pub ip: String,
+    pub token_hash: String,
+    pub expires_at: u64,
+    pub verified_at: u64,
+    pub decided_at: u64,
+    pub approved: bool,
+    /// id of the admin who approved or rejected it, `system` if no approval is required
+    pub decided_by: String,
+    pub reason: String,
+}
+
+impl Registration {
+    pub fn new(
+        id: String,
+        realm: String,
+        user_id: String,
+        account: String,
+        email: String,
+        ip: String,
+    ) -> Self {
+        Registration {
+            base: BaseModel::new(id),
+            realm,
+            user_id,
+            account,
+            email,
+            ip,
+            ..Default::default()
+        }
+    }
+
+    pub fn status(&self, now: u64) -> RegistrationStatus {
+        if self.decided_at > 0 && self.approved {
+            RegistrationStatus::Approved
+        } else if self.decided_at > 0 {
+            RegistrationStatus::Rejected
+        } else if self.verified_at > 0 {
+            RegistrationStatus::AwaitingApproval
+        } else if self.expires_at <= now {
+            RegistrationStatus::Expired
+        } else {
+            RegistrationStatus::Unverified
+        }
+    }
+
+    /// generate the verification token, valid for `ttl` seconds
+    pub fn issue(&mut self, now: u64, ttl: u64) -> String {
+        let token = generate_token();
+
+        self.token_hash = hash_token(&token);
+        self.expires_at = now + ttl;
+
+        token
+    }
+
+    /// verify the email address, a token works only once.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the registration is verified or expired.
+    pub fn verify(&mut self, now: u64) -> Result<()> {
+        match self.status(now) {
+            RegistrationStatus::Unverified => {}
+            RegistrationStatus::Expired => {
+                return Err(Error::LogicError("验证链接已过期".to_string()))
+            }
+            _ => return Err(Error::LogicError("邮箱已经验证".to_string())),
+        }
+
+        self.verified_at = now;
+        self.token_hash = String::new();
+
+        Ok(())
+    }
+
+    /// approve the verified registration, or reject a registration that is not decided yet,
+    /// such as one that was never verified
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the registration can not be decided so.
+    pub fn decide(&mut self, approved: bool, actor: &str, reason: &str, now: u64) -> Result<()> {
+        let open = match self.status(now) {
+            RegistrationStatus::AwaitingApproval => true,
+            RegistrationStatus::Unverified | RegistrationStatus::Expired => !approved,
+            RegistrationStatus::Approved | RegistrationStatus::Rejected => false,
+        };
+        if !open {
+            return Err(Error::LogicError("注册申请不在待审批状态".to_string()));
+        }
+
+        self.decided_at = now;
+        self.approved = approved;
+        self.decided_by = actor.to_string();
+        self.reason = reason.to_string();
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registration() -> Registration {
+        Registration::new(
+            "1".to_string(),
+            "staff".to_string(),
+            "2".to_string(),
+            "alice".to_string(),
+            "alice@example.com".to_string(),
+            "127.0.0.1".to_string(),
+        )
+    }
+
+    #[test]
+    fn test_verify_once() {
+        let mut registration = registration();
+        let token = registration.issue(100, 60);
+
+        assert_eq!(registration.token_hash, hash_token(&token));
+        assert!(registration.clone().verify(160).is_err());
+
+        registration.verify(110).unwrap();
+        assert_eq!(
+            registration.status(110),
+            RegistrationStatus::AwaitingApproval
+        );
+        assert!(registration.token_hash.is_empty());
+        assert!(registration.verify(110).is_err());
+    }
+
+    #[test]
+    fn test_decide_after_verify() {
+        let mut registration = registration();
+        registration.issue(100, 60);
+
+        assert!(registration.decide(true, "3", "", 110).is_err());
+
+        registration.verify(110).unwrap();
+        registration.decide(true, "3", "", 120).unwrap();
+
+        assert_eq!(registration.status(120), RegistrationStatus::Approved);
+        assert!(registration.decide(false, "3", "", 130).is_err());
+    }
+
+    #[test]
+    fn test_reject_unverified() {
+        let mut registration = registration();
+        registration.issue(100, 60);
+
+        registration.decide(false, "3", "spam", 200).unwrap();
+
+        assert_eq!(registration.status(200), RegistrationStatus::Rejected);
+        assert_eq!(registration.reason, "spam");
+    }
+}
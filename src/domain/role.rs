@@ -1,31 +1,90 @@
-use axum::Form;
+use mongodb::bson::Document;
 use serde::{Deserialize, Serialize};
 
 use crate::actors::{fetcher, rbac};
 
-use super::BaseModel;
+use super::{common::default_tenant, BaseModel};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// precedence of role permissions, shown to admins building roles
+pub const PRECEDENCE_RULES: [&str; 6] = [
+    "拒绝优先: 只要有一条匹配的拒绝规则, 请求即被拒绝",
+    "默认拒绝: 没有匹配的允许规则时, 请求被拒绝",
+    "规则在用户当前租户内的所有角色之间合并计算",
+    "权限名中 * 匹配任意权限, role:* 匹配角色模块的所有权限",
+    "路径中 * 匹配任意后续路径, :name 匹配单个路径段",
+    "请求同时按接口所需的权限和请求路径判定, 任一被允许即允许, 任一被拒绝即拒绝",
+];
+
+/// whether a permission grants or carves out access
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Effect {
+    #[default]
+    Allow,
+    Deny,
+}
+
+impl Effect {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Effect::Allow => "allow",
+            Effect::Deny => "deny",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct RouteItem {
     pub module: String,
+    /// a capability such as `role:write`, or a route path such as `/roles/:id`
     pub path: String,
     pub description: String,
+    #[serde(default)]
+    pub effect: Effect,
+}
+
+/// the documents of scoped collections a role can see
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "filter", rename_all = "snake_case")]
+pub enum DataScope {
+    /// no restriction
+    #[default]
+    All,
+    /// documents of the user's department
+    Department,
+    /// documents owned by the user
+    Own,
+    /// a mongodb filter merged into the query as is
+    Custom(Document),
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Role {
     #[serde(flatten)]
     pub base: BaseModel,
+    /// role names are unique inside a tenant only
+    #[serde(default = "default_tenant")]
+    pub tenant: String,
     pub name: String,
     pub permissions: Vec<RouteItem>,
+    #[serde(default)]
+    pub data_scope: DataScope,
 }
 
 impl Role {
-    pub fn new(id: String, name: String, permissions: Vec<RouteItem>) -> Self {
+    pub fn new(
+        id: String,
+        tenant: String,
+        name: String,
+        permissions: Vec<RouteItem>,
+        data_scope: DataScope,
+    ) -> Self {
         Role {
             base: BaseModel::new(id),
+            tenant,
             name,
             permissions,
+            data_scope,
         }
     }
 }
@@ -34,10 +93,23 @@ impl fetcher::RBACRole for Role {
     fn to_casbin_policy(&self) -> Vec<Vec<String>> {
         let mut out: Vec<Vec<String>> = vec![];
 
-        self.permissions
-            .iter()
-            .for_each(|p| out.push(vec![self.name.clone(), p.path.clone()]));
+        self.permissions.iter().for_each(|p| {
+            out.push(vec![
+                self.name.clone(),
+                self.tenant.clone(),
+                p.path.clone(),
+                p.effect.as_str().to_string(),
+            ])
+        });
 
         out
     }
+
+    fn data_scope(&self) -> (String, String, DataScope) {
+        (
+            self.tenant.clone(),
+            self.name.clone(),
+            self.data_scope.clone(),
+        )
+    }
 }
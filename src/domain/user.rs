@@ -1,8 +1,115 @@
+use chrono::Utc;
 use serde::{Deserialize, Serialize};
 
 use crate::actors::{fetcher, rbac};
 
-use super::{common::Secret, BaseModel};
+use super::{
+    common::{Secret, DEFAULT_TENANT},
+    errors::{Error, Result},
+    BaseModel,
+};
+
+/// actor recorded for the transitions made by the system, such as account expiry
+pub const SYSTEM_ACTOR: &str = "system";
+
+/// the lifecycle of an account, only active accounts may sign in
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AccountStatus {
+    /// created but not activated yet, such as an invited user
+    #[default]
+    Pending,
+    Active,
+    /// blocked temporarily, for example after suspicious activity
+    Locked,
+    Disabled,
+    /// the account passed its expiry date
+    Expired,
+}
+
+impl AccountStatus {
+    /// returns true if an account may change from the status to the other one
+    pub fn can_become(self, to: AccountStatus) -> bool {
+        use AccountStatus::*;
+
+        matches!(
+            (self, to),
+            (Pending, Active)
+                | (Pending, Disabled)
+                | (Active, Locked)
+                | (Active, Disabled)
+                | (Active, Expired)
+                | (Locked, Active)
+                | (Locked, Disabled)
+                | (Disabled, Active)
+                | (Expired, Active)
+                | (Expired, Disabled)
+        )
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AccountStatus::Pending => "pending",
+            AccountStatus::Active => "active",
+            AccountStatus::Locked => "locked",
+            AccountStatus::Disabled => "disabled",
+            AccountStatus::Expired => "expired",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            AccountStatus::Pending => "待激活",
+            AccountStatus::Active => "正常",
+            AccountStatus::Locked => "已锁定",
+            AccountStatus::Disabled => "已禁用",
+            AccountStatus::Expired => "已过期",
+        }
+    }
+
+    /// returns why an account in the status may not sign in, none if it may
+    pub fn refusal(self) -> Option<&'static str> {
+        match self {
+            AccountStatus::Active => None,
+            AccountStatus::Pending => Some("账号尚未激活"),
+            AccountStatus::Locked => Some("账号已被锁定"),
+            AccountStatus::Disabled => Some("用户已被禁用"),
+            AccountStatus::Expired => Some("账号已过期"),
+        }
+    }
+}
+
+/// a change of the account status
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
+pub struct StatusChange {
+    pub from: AccountStatus,
+    pub to: AccountStatus,
+    pub reason: String,
+    /// id of the user who made the change, or `system`
+    pub actor: String,
+    pub at: u64,
+}
+
+/// a role granted to a user inside a tenant
+#[derive(Debug, Serialize, Deserialize, Default, Clone, PartialEq)]
+pub struct RoleAssignment {
+    pub tenant: String,
+    pub role_name: String,
+    /// unix timestamp the grant takes effect, immediately if not set
+    #[serde(default)]
+    pub starts_at: Option<u64>,
+    /// unix timestamp the grant ends, never if not set
+    #[serde(default)]
+    pub expires_at: Option<u64>,
+}
+
+impl RoleAssignment {
+    /// returns true if the grant is in effect at the timestamp
+    pub fn is_active_at(&self, now: u64) -> bool {
+        self.starts_at.is_none_or(|starts_at| starts_at <= now)
+            && self.expires_at.is_none_or(|expires_at| now < expires_at)
+    }
+}
 
 #[derive(Debug, Serialize, Deserialize, Default, Clone)]
 #[serde(default)]
@@ -13,16 +120,357 @@ pub struct User {
     pub name: String,
     pub age: u8,
     pub avatar: String,
-    pub is_active: bool,
+    pub phone: String,
+    /// where invitations and notices are delivered
+    pub email: String,
+    pub status: AccountStatus,
+    /// the transitions of the status, oldest first
+    pub status_changes: Vec<StatusChange>,
+    /// unix timestamp the account expires, never if not set
+    pub expires_at: Option<u64>,
+    /// unix timestamp of the last successful login, 0 if never
+    pub last_login_at: u64,
+    pub department_id: String,
+    /// role in the default tenant
     pub role_name: String,
+    /// roles in the other tenants
+    pub tenant_roles: Vec<RoleAssignment>,
+    /// unix timestamp the personal data was erased, 0 if never.
+    /// erased users stay deleted, they are not in the recycle bin
+    pub erased_at: u64,
+}
+
+impl User {
+    /// returns the status at the timestamp, an active account past its expiry is expired
+    pub fn status_at(&self, now: u64) -> AccountStatus {
+        match self.expires_at {
+            Some(expires_at) if self.status == AccountStatus::Active && expires_at <= now => {
+                AccountStatus::Expired
+            }
+            _ => self.status,
+        }
+    }
+
+    /// move the account to the status, recording who did it and why.
+    ///
+    /// reactivating an expired account clears its expiry date.
+    pub fn transition(
+        &mut self,
+        to: AccountStatus,
+        reason: &str,
+        actor: &str,
+        now: u64,
+    ) -> Result<()> {
+        let from = self.status_at(now);
+        if from == to {
+            return Err(Error::LogicError(format!("账号已经是{}状态", to.label())));
+        }
+        if !from.can_become(to) {
+            return Err(Error::LogicError(format!(
+                "账号不能从{}变为{}",
+                from.label(),
+                to.label()
+            )));
+        }
+
+        if to == AccountStatus::Active && self.expires_at.is_some_and(|at| at <= now) {
+            self.expires_at = None;
+        }
+
+        self.status = to;
+        self.status_changes.push(StatusChange {
+            from,
+            to,
+            reason: reason.to_string(),
+            actor: actor.to_string(),
+            at: now,
+        });
+
+        Ok(())
+    }
+
+    /// replace the personal data by placeholders and delete the user.
+    ///
+    /// the id is kept so the records referring to it stay consistent,
+    /// the account can not sign in anymore.
+    pub fn erase(&mut self, actor: &str, now: u64) {
+        let from = self.status_at(now);
+        if from != AccountStatus::Disabled {
+            self.status = AccountStatus::Disabled;
+            self.status_changes.push(StatusChange {
+                from,
+                to: AccountStatus::Disabled,
+                reason: String::new(),
+                actor: actor.to_string(),
+                at: now,
+            });
+        }
+
+        // reasons may have been written about the person
+        for change in self.status_changes.iter_mut() {
+            change.reason = String::new();
+        }
+
+        self.secret = Secret {
+            account: format!("erased-{}", self.base.id),
+            password: String::new(),
+        };
+        self.name = "已注销用户".to_string();
+        self.age = 0;
+        self.avatar = String::new();
+        self.phone = String::new();
+        self.email = String::new();
+        self.department_id = String::new();
+        self.role_name = String::new();
+        self.tenant_roles = vec![];
+        self.expires_at = None;
+        self.last_login_at = 0;
+        self.erased_at = now;
+        self.base.delete();
+    }
+
+    /// returns all roles of the user, including the one in the default tenant
+    pub fn assignments(&self) -> Vec<RoleAssignment> {
+        let mut out = vec![];
+
+        if !self.role_name.is_empty() {
+            out.push(RoleAssignment {
+                tenant: DEFAULT_TENANT.to_string(),
+                role_name: self.role_name.clone(),
+                ..Default::default()
+            });
+        }
+
+        out.extend(self.tenant_roles.iter().cloned());
+        out
+    }
+
+    /// returns the roles of the user that are in effect at the timestamp
+    pub fn active_assignments(&self, now: u64) -> Vec<RoleAssignment> {
+        self.assignments()
+            .into_iter()
+            .filter(|item| item.is_active_at(now))
+            .collect()
+    }
+
+    /// returns the names of the roles the user holds in the tenant, including
+    /// grants that are not in effect
+    pub fn roles_in(&self, tenant: &str) -> Vec<String> {
+        self.assignments()
+            .into_iter()
+            .filter(|item| item.tenant == tenant)
+            .map(|item| item.role_name)
+            .collect()
+    }
+
+    /// returns true if the user holds a role in effect in the tenant
+    pub fn in_tenant(&self, tenant: &str) -> bool {
+        self.active_assignments(now())
+            .iter()
+            .any(|item| item.tenant == tenant)
+    }
+
+    /// returns true if the user is a member of the tenant and may be managed by its admins.
+    ///
+    /// members hold a role in the tenant, in effect or not. users without any role
+    /// belong to the default tenant.
+    pub fn belongs_to(&self, tenant: &str) -> bool {
+        let assignments = self.assignments();
+        if tenant == DEFAULT_TENANT && assignments.is_empty() {
+            return true;
+        }
+
+        assignments.iter().any(|item| item.tenant == tenant)
+    }
+
+    /// returns true if the user is a member of the tenant and of no other one.
+    ///
+    /// users are shared by the tenants they hold roles in, only the admins of their
+    /// single tenant may change the account itself.
+    pub fn belongs_only_to(&self, tenant: &str) -> bool {
+        self.belongs_to(tenant) && self.assignments().iter().all(|item| item.tenant == tenant)
+    }
+
+    /// grant the role inside the tenant, nothing happens if the user already holds it
+    pub fn grant(&mut self, tenant: &str, role_name: &str) {
+        if self.roles_in(tenant).iter().any(|name| name == role_name) {
+            return;
+        }
+
+        if tenant == DEFAULT_TENANT && self.role_name.is_empty() {
+            self.role_name = role_name.to_string();
+            return;
+        }
+
+        self.tenant_roles.push(RoleAssignment {
+            tenant: tenant.to_string(),
+            role_name: role_name.to_string(),
+            ..Default::default()
+        });
+    }
+
+    /// grant the role inside the tenant for a period, replacing a previous grant of it.
+    pub fn grant_between(
+        &mut self,
+        tenant: &str,
+        role_name: &str,
+        starts_at: Option<u64>,
+        expires_at: Option<u64>,
+    ) {
+        if tenant == DEFAULT_TENANT && self.role_name == role_name {
+            self.role_name = String::new();
+        }
+        self.tenant_roles
+            .retain(|item| item.tenant != tenant || item.role_name != role_name);
+
+        self.tenant_roles.push(RoleAssignment {
+            tenant: tenant.to_string(),
+            role_name: role_name.to_string(),
+            starts_at,
+            expires_at,
+        });
+    }
+
+    /// replace the roles the user holds inside the tenant,
+    /// the period of roles that are kept does not change.
+    pub fn set_roles_in(&mut self, tenant: &str, role_names: &[String]) {
+        if tenant == DEFAULT_TENANT && !role_names.contains(&self.role_name) {
+            self.role_name = String::new();
+        }
+        self.tenant_roles
+            .retain(|item| item.tenant != tenant || role_names.contains(&item.role_name));
+
+        for role_name in role_names {
+            self.grant(tenant, role_name);
+        }
+    }
+}
+
+fn now() -> u64 {
+    Utc::now().timestamp() as u64
 }
 
 impl fetcher::RBACUser for User {
     fn account(&self) -> String {
-        self.name.clone()
+        self.secret.account.clone()
     }
 
-    fn role_name(&self) -> String {
-        self.role_name.clone()
+    fn roles(&self) -> Vec<(String, String)> {
+        self.active_assignments(now())
+            .into_iter()
+            .map(|item| (item.tenant, item.role_name))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assignment_window() {
+        let assignment = RoleAssignment {
+            tenant: DEFAULT_TENANT.to_string(),
+            role_name: "contractor".to_string(),
+            starts_at: Some(100),
+            expires_at: Some(200),
+        };
+
+        assert!(!assignment.is_active_at(99));
+        assert!(assignment.is_active_at(100));
+        assert!(!assignment.is_active_at(200));
+        assert!(RoleAssignment::default().is_active_at(0));
+    }
+
+    #[test]
+    fn test_tenant_membership() {
+        let mut user = User::default();
+        assert!(user.belongs_to(DEFAULT_TENANT));
+        assert!(!user.belongs_to("shop"));
+
+        assert!(user.belongs_only_to(DEFAULT_TENANT));
+
+        user.grant_between("shop", "clerk", Some(100), None);
+        assert!(user.belongs_to("shop"));
+        assert!(!user.belongs_to(DEFAULT_TENANT));
+        assert!(user.belongs_only_to("shop"));
+
+        user.grant(DEFAULT_TENANT, "admin");
+        assert!(user.belongs_to("shop") && !user.belongs_only_to("shop"));
+        assert!(!user.belongs_only_to(DEFAULT_TENANT));
+    }
+
+    #[test]
+    fn test_status_transition() {
+        let mut user = User::default();
+
+        assert!(user.transition(AccountStatus::Locked, "", "1", 10).is_err());
+        user.transition(AccountStatus::Active, "invited", "2", 10)
+            .unwrap();
+        assert!(user.transition(AccountStatus::Active, "", "1", 20).is_err());
+        user.transition(AccountStatus::Locked, "too many attempts", "1", 20)
+            .unwrap();
+
+        assert_eq!(user.status, AccountStatus::Locked);
+        assert_eq!(user.status_changes.len(), 2);
+        assert_eq!(user.status_changes[1].from, AccountStatus::Active);
+        assert_eq!(user.status_changes[1].reason, "too many attempts");
+    }
+
+    #[test]
+    fn test_status_expiry() {
+        let mut user = User {
+            status: AccountStatus::Active,
+            expires_at: Some(100),
+            ..Default::default()
+        };
+
+        assert_eq!(user.status_at(99), AccountStatus::Active);
+        assert_eq!(user.status_at(100), AccountStatus::Expired);
+
+        user.transition(AccountStatus::Active, "renewed", "1", 150)
+            .unwrap();
+        assert_eq!(user.status_changes[0].from, AccountStatus::Expired);
+        assert_eq!(user.expires_at, None);
+        assert_eq!(user.status_at(200), AccountStatus::Active);
+    }
+
+    #[test]
+    fn test_erase() {
+        let mut user = User {
+            secret: Secret::new("alice".to_string(), "secret".to_string()).unwrap(),
+            name: "Alice".to_string(),
+            email: "alice@example.com".to_string(),
+            role_name: "admin".to_string(),
+            ..Default::default()
+        };
+        user.base.id = "1".to_string();
+        user.transition(AccountStatus::Active, "hired", "2", 10)
+            .unwrap();
+
+        user.erase("2", 20);
+
+        assert_eq!(user.secret.account, "erased-1");
+        assert!(!user.secret.is_match(""));
+        assert!(user.email.is_empty() && user.assignments().is_empty());
+        assert_eq!(user.status, AccountStatus::Disabled);
+        assert!(user
+            .status_changes
+            .iter()
+            .all(|change| change.reason.is_empty()));
+        assert!(user.base.deleted_at > 0);
+        assert_eq!(user.erased_at, 20);
+    }
+
+    #[test]
+    fn test_set_roles_keeps_period() {
+        let mut user = User::default();
+        user.grant("shop", "viewer");
+        user.grant_between("shop", "contractor", None, Some(200));
+
+        user.set_roles_in("shop", &["contractor".to_string(), "admin".to_string()]);
+
+        assert_eq!(user.roles_in("shop"), vec!["contractor", "admin"]);
+        assert_eq!(user.tenant_roles[0].expires_at, Some(200));
     }
 }
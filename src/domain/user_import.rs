@@ -0,0 +1,493 @@
+use std::io::{Cursor, Read};
+
+use quick_xml::{events::Event, Reader};
+use serde::Serialize;
+
+use super::errors::{Error, Result};
+
+/// roles of a row are separated by `|`
+pub const ROLE_SEPARATOR: char = '|';
+
+/// the most users a file may import
+pub const MAX_ROWS: usize = 10_000;
+
+/// the last column and row of a worksheet, `XFD1048576`
+const MAX_SHEET_COLUMNS: usize = 16_384;
+const MAX_SHEET_ROWS: usize = 1_048_576;
+
+/// the most cells kept while reading a worksheet, blank rows included
+const MAX_CELLS: usize = 1 << 20;
+
+/// the most bytes read from a decompressed entry of the workbook
+const MAX_ENTRY_SIZE: u64 = 32 << 20;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImportFormat {
+    Csv,
+    Xlsx,
+}
+
+impl ImportFormat {
+    /// returns the format of the uploaded file by its extension
+    pub fn from_file_name(name: &str) -> Option<Self> {
+        let extension = name.rsplit_once('.')?.1.to_lowercase();
+
+        match extension.as_str() {
+            "csv" => Some(ImportFormat::Csv),
+            "xlsx" => Some(ImportFormat::Xlsx),
+            _ => None,
+        }
+    }
+}
+
+/// a user row of an import file, values are kept as text until the row is validated.
+///
+/// the first row of the file names the columns, `account`, `password` and `name` are
+/// required, `age`, `phone`, `email`, `department_id` and `roles` are optional.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ImportRow {
+    /// line of the row in the file, the header is line 1
+    pub line: usize,
+    pub account: String,
+    pub password: String,
+    pub name: String,
+    pub age: String,
+    pub phone: String,
+    pub email: String,
+    pub department_id: String,
+    pub roles: String,
+}
+
+impl ImportRow {
+    /// returns the role names of the row
+    pub fn role_names(&self) -> Vec<String> {
+        self.roles
+            .split(ROLE_SEPARATOR)
+            .map(|name| name.trim())
+            .filter(|name| !name.is_empty())
+            .map(|name| name.to_string())
+            .collect()
+    }
+
+    fn from_cells(line: usize, header: &[String], cells: &[String]) -> Self {
+        let value = |column: &str| {
+            header
+                .iter()
+                .position(|name| name == column)
+                .and_then(|index| cells.get(index))
+                .map(|value| value.trim().to_string())
+                .unwrap_or_default()
+        };
+
+        ImportRow {
+            line,
+            account: value("account"),
+            password: value("password"),
+            name: value("name"),
+            age: value("age"),
+            phone: value("phone"),
+            email: value("email"),
+            department_id: value("department_id"),
+            roles: value("roles"),
+        }
+    }
+}
+
+/// parse the users of an import file, blank rows are skipped.
+///
+/// # Errors
+///
+/// This function will return an error if the file can not be read, the header lacks
+/// the `account`, `password` or `name` column, or there are more than [MAX_ROWS] users.
+pub fn parse(content: &[u8], format: ImportFormat) -> Result<Vec<ImportRow>> {
+    let table = match format {
+        ImportFormat::Csv => read_csv(content)?,
+        ImportFormat::Xlsx => read_xlsx(content)?,
+    };
+
+    let mut lines = table.into_iter().enumerate();
+    let header: Vec<String> = match lines.next() {
+        Some((_, header)) => header
+            .into_iter()
+            .map(|name| name.trim().trim_start_matches('\u{feff}').to_lowercase())
+            .collect(),
+        None => return Err(Error::LogicError("导入文件为空".to_string())),
+    };
+
+    let missing: Vec<&str> = ["account", "password", "name"]
+        .into_iter()
+        .filter(|column| !header.iter().any(|name| name == column))
+        .collect();
+    if !missing.is_empty() {
+        return Err(Error::LogicError(format!(
+            "导入文件缺少列: {}",
+            missing.join(", ")
+        )));
+    }
+
+    let rows: Vec<ImportRow> = lines
+        .filter(|(_, cells)| cells.iter().any(|cell| !cell.trim().is_empty()))
+        .map(|(index, cells)| ImportRow::from_cells(index + 1, &header, &cells))
+        .collect();
+    if rows.len() > MAX_ROWS {
+        return Err(Error::LogicError(format!(
+            "导入文件最多包含{}个用户",
+            MAX_ROWS
+        )));
+    }
+
+    Ok(rows)
+}
+
+fn read_csv(content: &[u8]) -> Result<Vec<Vec<String>>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(content);
+
+    let mut table = vec![];
+    for record in reader.records() {
+        let record = record.map_err(|err| Error::LogicError(format!("无法解析CSV: {}", err)))?;
+        table.push(record.iter().map(|cell| cell.to_string()).collect());
+    }
+
+    Ok(table)
+}
+
+fn xlsx_error(err: impl std::fmt::Display) -> Error {
+    Error::LogicError(format!("无法解析XLSX: {}", err))
+}
+
+/// read the first worksheet of the workbook
+fn read_xlsx(content: &[u8]) -> Result<Vec<Vec<String>>> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(content)).map_err(xlsx_error)?;
+
+    let shared_strings = match read_entry(&mut archive, "xl/sharedStrings.xml")? {
+        Some(xml) => read_shared_strings(&xml)?,
+        None => vec![],
+    };
+
+    let workbook = read_entry(&mut archive, "xl/workbook.xml")?
+        .ok_or_else(|| xlsx_error("缺少 xl/workbook.xml"))?;
+    let relations = read_entry(&mut archive, "xl/_rels/workbook.xml.rels")?
+        .ok_or_else(|| xlsx_error("缺少 xl/_rels/workbook.xml.rels"))?;
+    let path = first_sheet_path(&workbook, &relations)?;
+
+    let xml = read_entry(&mut archive, &path)?
+        .ok_or_else(|| xlsx_error(format!("缺少工作表 {}", path)))?;
+
+    read_sheet(&xml, &shared_strings)
+}
+
+/// returns the text of an entry of the archive, at most [MAX_ENTRY_SIZE] bytes once
+/// decompressed
+fn read_entry(archive: &mut zip::ZipArchive<Cursor<&[u8]>>, name: &str) -> Result<Option<String>> {
+    let file = match archive.by_name(name) {
+        Ok(file) => file,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(err) => return Err(xlsx_error(err)),
+    };
+
+    let mut xml = String::new();
+    file.take(MAX_ENTRY_SIZE + 1)
+        .read_to_string(&mut xml)
+        .map_err(xlsx_error)?;
+    if xml.len() as u64 > MAX_ENTRY_SIZE {
+        return Err(xlsx_error(format!(
+            "{} 超过{}MB",
+            name,
+            MAX_ENTRY_SIZE >> 20
+        )));
+    }
+
+    Ok(Some(xml))
+}
+
+/// returns the path in the archive of the first sheet listed by the workbook,
+/// following its relationship
+fn first_sheet_path(workbook: &str, relations: &str) -> Result<String> {
+    let mut id = None;
+    let mut reader = Reader::from_str(workbook);
+    loop {
+        match reader.read_event().map_err(xlsx_error)? {
+            Event::Start(e) | Event::Empty(e) if e.local_name().as_ref() == b"sheet" => {
+                id = attribute(&e, b"id")?;
+                break;
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    let id = id.ok_or_else(|| xlsx_error("工作簿没有工作表"))?;
+
+    let mut reader = Reader::from_str(relations);
+    loop {
+        match reader.read_event().map_err(xlsx_error)? {
+            Event::Start(e) | Event::Empty(e)
+                if e.local_name().as_ref() == b"Relationship"
+                    && attribute(&e, b"Id")?.as_deref() == Some(id.as_str()) =>
+            {
+                let target =
+                    attribute(&e, b"Target")?.ok_or_else(|| xlsx_error("工作表关系缺少路径"))?;
+
+                // targets are relative to the workbook unless they are absolute
+                return Ok(match target.strip_prefix('/') {
+                    Some(target) => target.to_string(),
+                    None => format!("xl/{}", target),
+                });
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Err(xlsx_error(format!("找不到工作表关系 {}", id)))
+}
+
+/// returns the value of the attribute, matched by its local name
+fn attribute(element: &quick_xml::events::BytesStart, name: &[u8]) -> Result<Option<String>> {
+    for attribute in element.attributes() {
+        let attribute = attribute.map_err(xlsx_error)?;
+        if attribute.key.local_name().as_ref() == name {
+            let value = attribute.unescape_value().map_err(xlsx_error)?;
+            return Ok(Some(value.to_string()));
+        }
+    }
+
+    Ok(None)
+}
+
+/// returns the texts of the shared string table, without phonetic hints
+fn read_shared_strings(xml: &str) -> Result<Vec<String>> {
+    let mut reader = Reader::from_str(xml);
+    let mut strings = vec![];
+    let mut current = String::new();
+    let (mut in_text, mut in_phonetic) = (false, false);
+
+    loop {
+        match reader.read_event().map_err(xlsx_error)? {
+            Event::Start(e) => match e.name().as_ref() {
+                b"si" => current.clear(),
+                b"t" => in_text = true,
+                b"rPh" => in_phonetic = true,
+                _ => {}
+            },
+            Event::Text(text) if in_text && !in_phonetic => {
+                current.push_str(&text.unescape().map_err(xlsx_error)?);
+            }
+            Event::End(e) => match e.name().as_ref() {
+                b"si" => strings.push(std::mem::take(&mut current)),
+                b"t" => in_text = false,
+                b"rPh" => in_phonetic = false,
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(strings)
+}
+
+/// returns the zero based column of a cell reference such as `C12`,
+/// none past the last column `XFD`
+fn column_index(reference: &str) -> Option<usize> {
+    let letters: Vec<u8> = reference
+        .bytes()
+        .take_while(|c| c.is_ascii_alphabetic())
+        .collect();
+    if letters.is_empty() || letters.len() > 3 {
+        return None;
+    }
+
+    let index = letters.iter().fold(0, |index, c| {
+        index * 26 + (c.to_ascii_uppercase() - b'A' + 1) as usize
+    });
+    if index > MAX_SHEET_COLUMNS {
+        return None;
+    }
+
+    Some(index - 1)
+}
+
+fn read_sheet(xml: &str, shared_strings: &[String]) -> Result<Vec<Vec<String>>> {
+    let mut reader = Reader::from_str(xml);
+    let mut table = vec![];
+    let mut row: Vec<String> = vec![];
+    // the column and type of the current cell
+    let mut cell: Option<(usize, String)> = None;
+    let mut value = String::new();
+    let mut in_value = false;
+    let mut cells = 0;
+
+    loop {
+        match reader.read_event().map_err(xlsx_error)? {
+            Event::Start(e) => match e.name().as_ref() {
+                b"row" => {
+                    // keep line numbers of sparse sheets, rows are numbered from 1
+                    let number = e
+                        .try_get_attribute("r")
+                        .map_err(xlsx_error)?
+                        .and_then(|attribute| {
+                            attribute.unescape_value().ok()?.parse::<usize>().ok()
+                        })
+                        .unwrap_or(table.len() + 1);
+                    if number > MAX_SHEET_ROWS {
+                        return Err(xlsx_error(format!("行号超出范围: {}", number)));
+                    }
+                    while table.len() + 1 < number {
+                        table.push(vec![]);
+                    }
+                    row.clear();
+                }
+                b"c" => {
+                    let (mut reference, mut kind) = (String::new(), String::new());
+                    for attribute in e.attributes() {
+                        let attribute = attribute.map_err(xlsx_error)?;
+                        let text = attribute.unescape_value().map_err(xlsx_error)?.to_string();
+                        match attribute.key.as_ref() {
+                            b"r" => reference = text,
+                            b"t" => kind = text,
+                            _ => {}
+                        }
+                    }
+
+                    let column = match reference.is_empty() {
+                        true => row.len(),
+                        false => column_index(&reference)
+                            .ok_or_else(|| xlsx_error(format!("单元格引用无效: {}", reference)))?,
+                    };
+                    if column >= MAX_SHEET_COLUMNS {
+                        return Err(xlsx_error("单元格超出最后一列"));
+                    }
+
+                    cell = Some((column, kind));
+                    value.clear();
+                }
+                b"v" | b"t" => in_value = true,
+                _ => {}
+            },
+            Event::Text(text) if in_value => {
+                value.push_str(&text.unescape().map_err(xlsx_error)?);
+            }
+            Event::End(e) => match e.name().as_ref() {
+                b"v" | b"t" => in_value = false,
+                b"c" => {
+                    if let Some((column, kind)) = cell.take() {
+                        let text = match kind.as_str() {
+                            "s" => value
+                                .trim()
+                                .parse::<usize>()
+                                .ok()
+                                .and_then(|index| shared_strings.get(index))
+                                .cloned()
+                                .unwrap_or_default(),
+                            _ => value.clone(),
+                        };
+
+                        if row.len() <= column {
+                            cells += column + 1 - row.len();
+                            if cells > MAX_CELLS {
+                                return Err(xlsx_error("工作表的单元格过多"));
+                            }
+                            row.resize(column + 1, String::new());
+                        }
+                        row[column] = text;
+                    }
+                }
+                b"row" => table.push(std::mem::take(&mut row)),
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+
+    Ok(table)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_csv() {
+        let content = "\u{feff}Account,password,name,roles\nalice,secret1,Alice,admin|editor\n,,,\nbob,secret2,Bob,\n";
+        let rows = parse(content.as_bytes(), ImportFormat::Csv).unwrap();
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].line, 2);
+        assert_eq!(rows[0].role_names(), vec!["admin", "editor"]);
+        assert_eq!(rows[1].line, 4);
+        assert_eq!(rows[1].account, "bob");
+        assert!(rows[1].role_names().is_empty());
+    }
+
+    #[test]
+    fn test_parse_rejects_missing_columns() {
+        assert!(parse(b"account,name\nalice,Alice\n", ImportFormat::Csv).is_err());
+        assert!(parse(b"", ImportFormat::Csv).is_err());
+    }
+
+    #[test]
+    fn test_read_sheet() {
+        let shared_strings = read_shared_strings(
+            r#"<sst><si><t>account</t></si><si><r><t>Al</t></r><r><t>ice</t></r><rPh><t>x</t></rPh></si></sst>"#,
+        )
+        .unwrap();
+        assert_eq!(shared_strings, vec!["account", "Alice"]);
+
+        let table = read_sheet(
+            r#"<worksheet><sheetData>
+                <row r="1"><c r="A1" t="s"><v>0</v></c><c r="C1" t="inlineStr"><is><t>name</t></is></c></row>
+                <row r="3"><c r="C3" t="s"><v>1</v></c><c r="D3"><v>18</v></c></row>
+            </sheetData></worksheet>"#,
+            &shared_strings,
+        )
+        .unwrap();
+
+        assert_eq!(table[0], vec!["account", "", "name"]);
+        assert!(table[1].is_empty());
+        assert_eq!(table[2], vec!["", "", "Alice", "18"]);
+    }
+
+    #[test]
+    fn test_read_sheet_rejects_out_of_range_cells() {
+        assert_eq!(column_index("XFD1"), Some(16_383));
+        assert_eq!(column_index("XFE1"), None);
+        assert_eq!(column_index("AAAAAAAAAAAAAA1"), None);
+
+        assert!(read_sheet(r#"<sheetData><row r="100000000"></row></sheetData>"#, &[]).is_err());
+        assert!(read_sheet(
+            r#"<sheetData><row r="1"><c r="AAAAAAAAAAAAAA1"><v>1</v></c></row></sheetData>"#,
+            &[]
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_first_sheet_path() {
+        let workbook = r#"<workbook xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships">
+            <sheets><sheet name="Users" sheetId="3" r:id="rId7"/><sheet name="Other" sheetId="1" r:id="rId1"/></sheets>
+        </workbook>"#;
+        let relations = r#"<Relationships>
+            <Relationship Id="rId1" Target="worksheets/sheet1.xml"/>
+            <Relationship Id="rId7" Target="worksheets/users.xml"/>
+        </Relationships>"#;
+
+        assert_eq!(
+            first_sheet_path(workbook, relations).unwrap(),
+            "xl/worksheets/users.xml"
+        );
+        assert!(first_sheet_path("<workbook><sheets/></workbook>", relations).is_err());
+    }
+
+    #[test]
+    fn test_format_from_file_name() {
+        assert_eq!(
+            ImportFormat::from_file_name("users.XLSX"),
+            Some(ImportFormat::Xlsx)
+        );
+        assert_eq!(ImportFormat::from_file_name("users"), None);
+    }
+}
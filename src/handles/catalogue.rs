@@ -0,0 +1,221 @@
+use std::{collections::BTreeMap, sync::Arc};
+
+use axum::{routing::MethodRouter, Router};
+use serde::Serialize;
+
+use crate::{
+    actors::rbac::permission_match,
+    config::AppState,
+    domain::{
+        errors::Error,
+        role::{Effect, RouteItem},
+    },
+};
+
+/// routes of a module in the catalogue
+#[derive(Debug, Clone, Serialize)]
+pub struct CatalogueModule {
+    pub module: String,
+    pub routes: Vec<RouteItem>,
+}
+
+/// every permission that can be granted to roles: the routes registered through
+/// [CatalogueRouter] and the capabilities they require
+#[derive(Debug, Clone, Default)]
+pub struct RouteCatalogue {
+    items: Arc<Vec<RouteItem>>,
+}
+
+impl RouteCatalogue {
+    pub fn new(items: Vec<RouteItem>) -> Self {
+        RouteCatalogue {
+            items: Arc::new(items),
+        }
+    }
+
+    /// returns all routes grouped by module, modules and routes are sorted by name.
+    pub fn grouped(&self) -> Vec<CatalogueModule> {
+        let mut modules: BTreeMap<String, Vec<RouteItem>> = BTreeMap::new();
+
+        for item in self.items.iter() {
+            modules
+                .entry(item.module.clone())
+                .or_default()
+                .push(item.clone());
+        }
+
+        modules
+            .into_iter()
+            .map(|(module, mut routes)| {
+                routes.sort_by(|a, b| a.path.cmp(&b.path));
+                CatalogueModule { module, routes }
+            })
+            .collect()
+    }
+
+    /// returns true if the permission is in the catalogue,
+    /// or a wildcard pattern matching at least one of them.
+    pub fn contains(&self, path: &str) -> bool {
+        self.items.iter().any(|item| {
+            item.path == path || (path.contains('*') && permission_match(&item.path, path))
+        })
+    }
+
+    /// check that every permission references a permission of the catalogue.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if any permission is not in the catalogue.
+    pub fn validate(&self, permissions: &[RouteItem]) -> Result<(), Error> {
+        let unknown: Vec<&str> = permissions
+            .iter()
+            .filter(|item| !self.contains(&item.path))
+            .map(|item| item.path.as_str())
+            .collect();
+
+        if !unknown.is_empty() {
+            return Err(Error::LogicError(format!(
+                "权限引用了不存在的权限项: {}",
+                unknown.join(", ")
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// a router that records the routes registered on it and the capabilities they require.
+pub struct CatalogueRouter {
+    router: Router<AppState>,
+    items: Vec<RouteItem>,
+}
+
+impl CatalogueRouter {
+    pub fn new() -> Self {
+        CatalogueRouter {
+            router: Router::new(),
+            items: vec![],
+        }
+    }
+
+    /// register a route together with the capabilities its handlers require.
+    ///
+    /// the path is listed under the module of the first capability, granting it
+    /// grants every handler of the route.
+    pub fn route(
+        mut self,
+        path: &str,
+        method_router: MethodRouter<AppState>,
+        capabilities: &[RouteItem],
+    ) -> Self {
+        self.router = self.router.route(path, method_router);
+
+        let names: Vec<&str> = capabilities.iter().map(|item| item.path.as_str()).collect();
+        let route = RouteItem {
+            module: capabilities
+                .first()
+                .map(|item| item.module.clone())
+                .unwrap_or_default(),
+            path: path.to_string(),
+            description: format!("访问该路由的所有接口, 等同于 {}", names.join(", ")),
+            effect: Effect::Allow,
+        };
+
+        // capabilities are shared by several routes, keep each once.
+        for item in std::iter::once(route).chain(capabilities.iter().cloned()) {
+            if !self.items.iter().any(|known| known.path == item.path) {
+                self.items.push(item);
+            }
+        }
+
+        self
+    }
+
+    pub fn into_parts(self) -> (Router<AppState>, RouteCatalogue) {
+        (self.router, RouteCatalogue::new(self.items))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::role::Effect;
+
+    use super::*;
+
+    fn item(module: &str, path: &str) -> RouteItem {
+        RouteItem {
+            module: module.to_string(),
+            path: path.to_string(),
+            description: "".to_string(),
+            effect: Effect::Allow,
+        }
+    }
+
+    fn catalogue() -> RouteCatalogue {
+        RouteCatalogue::new(vec![
+            item("角色", "role:read"),
+            item("角色", "role:write"),
+            item("权限", "rbac:read"),
+            item("管理", "/admin/*"),
+            item("管理", "/admin/billing"),
+        ])
+    }
+
+    #[test]
+    fn test_router_lists_routes_and_capabilities() {
+        let read = item("角色", "role:read");
+        let write = item("角色", "role:write");
+
+        let (_, catalogue) = CatalogueRouter::new()
+            .route(
+                "/roles",
+                axum::routing::get(|| async {}),
+                &[read.clone(), write],
+            )
+            .route("/roles/:id", axum::routing::get(|| async {}), &[read])
+            .into_parts();
+        let grouped = catalogue.grouped();
+
+        assert_eq!(grouped.len(), 1);
+        let paths: Vec<&str> = grouped[0]
+            .routes
+            .iter()
+            .map(|item| item.path.as_str())
+            .collect();
+        assert_eq!(
+            paths,
+            vec!["/roles", "/roles/:id", "role:read", "role:write"]
+        );
+    }
+
+    #[test]
+    fn test_grouped_by_module() {
+        let grouped = catalogue().grouped();
+
+        assert_eq!(grouped.len(), 3);
+        assert_eq!(grouped[0].module, "权限");
+        assert_eq!(grouped[2].module, "角色");
+        assert_eq!(grouped[2].routes.len(), 2);
+    }
+
+    #[test]
+    fn test_validate_rejects_unknown_permissions() {
+        let catalogue = catalogue();
+        let known = item("角色", "role:read");
+        let typo = item("角色", "role:raed");
+        let wildcard = RouteItem {
+            effect: Effect::Deny,
+            ..item("角色", "role:*")
+        };
+        let unmatched_wildcard = item("用户", "user:*");
+        let path = RouteItem {
+            effect: Effect::Deny,
+            ..item("管理", "/admin/billing")
+        };
+
+        assert!(catalogue.validate(&[known.clone(), wildcard]).is_ok());
+        assert!(catalogue.validate(&[known.clone(), path]).is_ok());
+        assert!(catalogue.validate(&[known.clone(), typo]).is_err());
+        assert!(catalogue.validate(&[known, unmatched_wildcard]).is_err());
+    }
+}
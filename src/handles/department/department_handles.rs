@@ -0,0 +1,414 @@
+use axum::{
+    extract::{Path, Query, State},
+    Extension, Json,
+};
+use mongodb::ClientSession;
+use validator::Validate;
+
+use crate::{
+    config::AppState,
+    database::repositories::{department::DepartmentRepository, user::UserRepository, Collection},
+    domain::department::{build_tree, Department, DepartmentNode},
+    handles::{
+        middlewares::{DataScope, Tenant},
+        permissions::{DepartmentRead, DepartmentWrite, RequirePermission},
+        response::{api_ok, api_ok_with_data},
+        user::{UserResponse, UserSearchRequest},
+    },
+};
+
+use super::super::errors::{Error, Result};
+
+use super::types::{
+    CreateDepartmentRequest, DepartmentRequest, HeadsRequest, MembersQuery, MergeRequest,
+    MoveRequest,
+};
+
+/// returns the department trees of the active tenant
+pub async fn tree(
+    _: RequirePermission<DepartmentRead>,
+    State(state): State<AppState>,
+    Extension(Tenant(tenant)): Extension<Tenant>,
+) -> Result<Vec<DepartmentNode>> {
+    let departments = DepartmentRepository::new()
+        .find_by_tenant(&tenant, &state.db)
+        .await?;
+
+    api_ok_with_data(build_tree(departments))
+}
+
+/// returns the department with its descendants
+pub async fn subtree(
+    _: RequirePermission<DepartmentRead>,
+    State(state): State<AppState>,
+    Extension(Tenant(tenant)): Extension<Tenant>,
+    Path(id): Path<String>,
+) -> Result<DepartmentNode> {
+    let department = find_department(&state, &tenant, &id).await?;
+
+    let departments = DepartmentRepository::new()
+        .find_subtree(&department.path, &state.db)
+        .await?;
+    let node = build_tree(departments)
+        .into_iter()
+        .find(|node| node.department.base.id == department.base.id)
+        .ok_or(Error::NotFound)?;
+
+    api_ok_with_data(node)
+}
+
+/// list the users of the department inside the caller's data scope,
+/// and the users of its descendants with `include_subtree`.
+pub async fn users(
+    _: RequirePermission<DepartmentRead>,
+    State(state): State<AppState>,
+    Extension(Tenant(tenant)): Extension<Tenant>,
+    DataScope(scope): DataScope,
+    Path(id): Path<String>,
+    Query(members): Query<MembersQuery>,
+    Query(mut request): Query<UserSearchRequest>,
+) -> Result<Collection<UserResponse>> {
+    let department = find_department(&state, &tenant, &id).await?;
+
+    let department_ids = if members.include_subtree {
+        DepartmentRepository::new()
+            .find_subtree(&department.path, &state.db)
+            .await?
+            .into_iter()
+            .map(|item| item.base.id)
+            .collect()
+    } else {
+        vec![department.base.id]
+    };
+
+    request.tenant = tenant.clone();
+    request.department_ids = Some(department_ids);
+
+    let users = UserRepository::new()
+        .search_scoped(&state.db, &request, &scope)
+        .await?;
+
+    api_ok_with_data(Collection {
+        items: users
+            .items
+            .into_iter()
+            .map(|user| UserResponse::in_tenant(user, &tenant))
+            .collect(),
+        total: users.total,
+    })
+}
+
+pub async fn create(
+    _: RequirePermission<DepartmentWrite>,
+    State(state): State<AppState>,
+    Extension(Tenant(tenant)): Extension<Tenant>,
+    Json(request): Json<CreateDepartmentRequest>,
+) -> Result<()> {
+    request.validate()?;
+
+    let parent = find_parent(&state, &tenant, &request.parent_id).await?;
+    check_name(&state, &tenant, &request.parent_id, &request.name, None).await?;
+
+    let id = state.id_gen.next_id().await?;
+    let department = Department::new(id, tenant, request.name, parent.as_ref(), request.sort);
+    DepartmentRepository::new()
+        .create(&department, &state.db)
+        .await?;
+
+    api_ok()
+}
+
+pub async fn update(
+    _: RequirePermission<DepartmentWrite>,
+    State(state): State<AppState>,
+    Extension(Tenant(tenant)): Extension<Tenant>,
+    Path(id): Path<String>,
+    Json(request): Json<DepartmentRequest>,
+) -> Result<()> {
+    request.validate()?;
+
+    let mut department = find_department(&state, &tenant, &id).await?;
+    check_name(
+        &state,
+        &tenant,
+        &department.parent_id,
+        &request.name,
+        Some(&department.base.id),
+    )
+    .await?;
+
+    department.name = request.name;
+    department.sort = request.sort;
+    DepartmentRepository::new()
+        .update(&department, &state.db)
+        .await?;
+
+    api_ok()
+}
+
+/// delete an empty department, children and users must be moved out first.
+pub async fn delete(
+    _: RequirePermission<DepartmentWrite>,
+    State(state): State<AppState>,
+    Extension(Tenant(tenant)): Extension<Tenant>,
+    Path(id): Path<String>,
+) -> Result<()> {
+    let mut department = find_department(&state, &tenant, &id).await?;
+
+    let repository = DepartmentRepository::new();
+    if repository.count_children(&id, &state.db).await? > 0 {
+        return Err(Error::BadRequest("部门下还有子部门, 不能删除".to_string()));
+    }
+
+    let members = UserRepository::new()
+        .count_by_departments(&[id], &state.db)
+        .await?;
+    if members > 0 {
+        return Err(Error::BadRequest(format!(
+            "部门下还有{}个用户, 不能删除",
+            members
+        )));
+    }
+
+    department.base.delete();
+    repository.update(&department, &state.db).await?;
+
+    api_ok()
+}
+
+/// move the department with its descendants under another parent
+pub async fn move_to(
+    _: RequirePermission<DepartmentWrite>,
+    State(state): State<AppState>,
+    Extension(Tenant(tenant)): Extension<Tenant>,
+    Path(id): Path<String>,
+    Json(request): Json<MoveRequest>,
+) -> Result<()> {
+    let department = find_department(&state, &tenant, &id).await?;
+    if department.parent_id == request.parent_id {
+        return api_ok();
+    }
+
+    let parent = find_parent(&state, &tenant, &request.parent_id).await?;
+    department
+        .check_move(parent.as_ref())
+        .map_err(|err| Error::BadRequest(err.to_string()))?;
+    check_name(
+        &state,
+        &tenant,
+        &request.parent_id,
+        &department.name,
+        Some(&department.base.id),
+    )
+    .await?;
+
+    let mut session = state.client.start_session(None).await?;
+    session.start_transaction(None).await?;
+    if let Err(err) = move_subtree(&state, department, parent.as_ref(), &mut session).await {
+        session.abort_transaction().await?;
+        return Err(err);
+    }
+    session.commit_transaction().await?;
+
+    api_ok()
+}
+
+/// merge the department into the target: its children and users move to the target,
+/// its heads join the target's heads, then it is deleted.
+pub async fn merge(
+    _: RequirePermission<DepartmentWrite>,
+    State(state): State<AppState>,
+    Extension(Tenant(tenant)): Extension<Tenant>,
+    Path(id): Path<String>,
+    Json(request): Json<MergeRequest>,
+) -> Result<()> {
+    let mut source = find_department(&state, &tenant, &id).await?;
+    let mut target = find_department(&state, &tenant, &request.target_id).await?;
+    if source.contains(&target) {
+        return Err(Error::BadRequest("不能合并到自己或下级部门".to_string()));
+    }
+
+    let children = DepartmentRepository::new()
+        .find_by_parent(&source.base.id, &state.db)
+        .await?;
+    for child in &children {
+        check_name(
+            &state,
+            &tenant,
+            &target.base.id,
+            &child.name,
+            Some(&child.base.id),
+        )
+        .await?;
+    }
+
+    for head_id in source.head_ids.drain(..) {
+        if !target.head_ids.contains(&head_id) {
+            target.head_ids.push(head_id);
+        }
+    }
+    source.base.delete();
+
+    let mut session = state.client.start_session(None).await?;
+    session.start_transaction(None).await?;
+    if let Err(err) = merge_with_session(&state, &source, &target, children, &mut session).await {
+        session.abort_transaction().await?;
+        return Err(err);
+    }
+    session.commit_transaction().await?;
+
+    api_ok()
+}
+
+/// write the merge of the source into the target inside the session's transaction
+async fn merge_with_session(
+    state: &AppState,
+    source: &Department,
+    target: &Department,
+    children: Vec<Department>,
+    session: &mut ClientSession,
+) -> std::result::Result<(), Error> {
+    let repository = DepartmentRepository::new();
+    for child in children {
+        move_subtree(state, child, Some(target), session).await?;
+    }
+
+    UserRepository::new()
+        .move_department_with_session(&source.base.id, &target.base.id, &state.db, session)
+        .await?;
+
+    repository
+        .update_with_session(target, &state.db, session)
+        .await?;
+    repository
+        .update_with_session(source, &state.db, session)
+        .await?;
+
+    Ok(())
+}
+
+/// replace the heads of the department, every head must be a member.
+pub async fn set_heads(
+    _: RequirePermission<DepartmentWrite>,
+    State(state): State<AppState>,
+    Extension(Tenant(tenant)): Extension<Tenant>,
+    Path(id): Path<String>,
+    Json(mut request): Json<HeadsRequest>,
+) -> Result<()> {
+    let mut department = find_department(&state, &tenant, &id).await?;
+
+    request.head_ids.sort();
+    request.head_ids.dedup();
+
+    let members = UserRepository::new()
+        .find_by_ids(&request.head_ids, &state.db)
+        .await?;
+    let outsiders: Vec<&str> = request
+        .head_ids
+        .iter()
+        .filter(|head_id| {
+            !members
+                .iter()
+                .any(|user| &user.base.id == *head_id && user.department_id == id)
+        })
+        .map(|head_id| head_id.as_str())
+        .collect();
+    if !outsiders.is_empty() {
+        return Err(Error::BadRequest(format!(
+            "部门负责人必须是部门成员: {}",
+            outsiders.join(", ")
+        )));
+    }
+
+    department.head_ids = request.head_ids;
+    DepartmentRepository::new()
+        .update(&department, &state.db)
+        .await?;
+
+    api_ok()
+}
+
+/// move the department under the parent and rewrite the paths of its descendants
+/// inside the session's transaction
+async fn move_subtree(
+    state: &AppState,
+    mut department: Department,
+    parent: Option<&Department>,
+    session: &mut ClientSession,
+) -> std::result::Result<(), Error> {
+    let repository = DepartmentRepository::new();
+    let descendants = repository.find_subtree(&department.path, &state.db).await?;
+
+    let (old_path, new_path) = department.move_under(parent);
+    repository
+        .update_with_session(&department, &state.db, session)
+        .await?;
+
+    for mut descendant in descendants {
+        if descendant.base.id == department.base.id {
+            continue;
+        }
+
+        descendant.rebase(&old_path, &new_path);
+        repository
+            .update_with_session(&descendant, &state.db, session)
+            .await?;
+    }
+
+    Ok(())
+}
+
+/// returns the department if it exists in the tenant
+async fn find_department(
+    state: &AppState,
+    tenant: &str,
+    id: &str,
+) -> std::result::Result<Department, Error> {
+    let department = DepartmentRepository::new()
+        .find_by_id(id, &state.db)
+        .await?
+        .filter(|department| department.tenant == tenant)
+        .ok_or(Error::NotFound)?;
+
+    Ok(department)
+}
+
+/// returns the parent department, none for an empty id
+async fn find_parent(
+    state: &AppState,
+    tenant: &str,
+    parent_id: &str,
+) -> std::result::Result<Option<Department>, Error> {
+    if parent_id.is_empty() {
+        return Ok(None);
+    }
+
+    let parent = find_department(state, tenant, parent_id)
+        .await
+        .map_err(|err| match err {
+            Error::NotFound => Error::BadRequest("上级部门不存在".to_string()),
+            err => err,
+        })?;
+
+    Ok(Some(parent))
+}
+
+/// check that no other sibling has the name
+async fn check_name(
+    state: &AppState,
+    tenant: &str,
+    parent_id: &str,
+    name: &str,
+    id: Option<&str>,
+) -> std::result::Result<(), Error> {
+    if let Some(other) = DepartmentRepository::new()
+        .find_sibling_by_name(tenant, parent_id, name, &state.db)
+        .await?
+    {
+        if Some(other.base.id.as_str()) != id {
+            return Err(Error::BadRequest("同级部门名称已存在".to_string()));
+        }
+    }
+
+    Ok(())
+}
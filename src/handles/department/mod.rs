@@ -0,0 +1,4 @@
+mod department_handles;
+mod types;
+
+pub use department_handles::*;
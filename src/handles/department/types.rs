@@ -0,0 +1,46 @@
+use serde::Deserialize;
+use validator::Validate;
+
+#[derive(Deserialize, Validate)]
+pub struct CreateDepartmentRequest {
+    #[validate(length(min = 1, max = 32, message = "部门名称长度为1-32"))]
+    pub name: String,
+    /// a root department if empty
+    #[serde(default)]
+    pub parent_id: String,
+    #[serde(default)]
+    pub sort: i32,
+}
+
+#[derive(Deserialize, Validate)]
+pub struct DepartmentRequest {
+    #[validate(length(min = 1, max = 32, message = "部门名称长度为1-32"))]
+    pub name: String,
+    #[serde(default)]
+    pub sort: i32,
+}
+
+#[derive(Deserialize)]
+pub struct MoveRequest {
+    /// moves the department to the root if empty
+    #[serde(default)]
+    pub parent_id: String,
+}
+
+#[derive(Deserialize)]
+pub struct MergeRequest {
+    /// the department receiving the children, users and heads
+    pub target_id: String,
+}
+
+#[derive(Deserialize)]
+pub struct HeadsRequest {
+    pub head_ids: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub struct MembersQuery {
+    /// include the users of the descendants
+    #[serde(default)]
+    pub include_subtree: bool,
+}
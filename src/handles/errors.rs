@@ -1,6 +1,6 @@
 use axum::{extract::multipart::MultipartError, http::StatusCode, response::IntoResponse, Json};
 
-use crate::{database, domain, jwt};
+use crate::{actors::rbac, database, domain, jwt};
 
 use super::response::ApiResponse;
 
@@ -41,6 +41,9 @@ pub enum Error {
 
     #[error("数据库错误: {0}")]
     DatabaseError(#[from] mongodb::error::Error),
+
+    #[error("权限策略错误: {0}")]
+    RbacError(#[from] rbac::Error),
 }
 
 impl From<String> for Error {
@@ -71,6 +74,8 @@ impl IntoResponse for Error {
             Error::FileUploadError(_) => 400,
             Error::FileError(_) => 500,
             Error::DatabaseError(_) => 500,
+            Error::RbacError(rbac::Error::InvalidRule(_) | rbac::Error::AdapterDisabled) => 400,
+            Error::RbacError(_) => 500,
         };
 
         let body = ApiResponse::<()> {
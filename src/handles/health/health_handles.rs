@@ -0,0 +1,32 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+};
+
+use crate::{config::AppState, handles::response::ApiResponse};
+
+use super::types::HealthResponse;
+
+/// readiness check, answers 503 until the rbac polices have been loaded once.
+///
+/// a degraded rbac engine keeps serving the last good polices and stays ready,
+/// the status and last error are reported in the body.
+pub async fn ready(State(state): State<AppState>) -> Response {
+    let rbac = state.rbac.health();
+    let ready = rbac.is_ready();
+
+    let (status, message) = match ready {
+        true => (StatusCode::OK, "OK"),
+        false => (StatusCode::SERVICE_UNAVAILABLE, "Service unavailable"),
+    };
+
+    let body = ApiResponse {
+        status: status.as_u16(),
+        message: message.to_string(),
+        data: Some(HealthResponse { ready, rbac }),
+        success: ready,
+    };
+
+    (status, body).into_response()
+}
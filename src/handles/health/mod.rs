@@ -0,0 +1,4 @@
+mod health_handles;
+mod types;
+
+pub use health_handles::*;
@@ -0,0 +1,10 @@
+use serde::Serialize;
+
+use crate::actors::rbac::RbacHealth;
+
+#[derive(Serialize)]
+pub struct HealthResponse {
+    /// true when the server can authorize requests
+    pub ready: bool,
+    pub rbac: RbacHealth,
+}
@@ -0,0 +1,257 @@
+use axum::{
+    extract::{Path, Query, State},
+    Extension, Json,
+};
+use chrono::{DateTime, Utc};
+use validator::Validate;
+
+use crate::{
+    actors::sender::Message,
+    config::AppState,
+    database::repositories::{invitation::InvitationRepository, user::UserRepository, Collection},
+    domain::{
+        common::Secret,
+        invitation::Invitation,
+        user::{AccountStatus, User},
+        BaseModel,
+    },
+    handles::{
+        middlewares::{Tenant, UserID},
+        permissions::{RequirePermission, UserGrant, UserInvite},
+        response::{api_ok, api_ok_with_data},
+        user::{check_department, check_grant, check_roles},
+    },
+};
+
+use super::super::errors::{Error, Result};
+
+use super::types::{AcceptRequest, InvitationResponse, InvitationSearchRequest, InviteRequest};
+
+pub async fn list(
+    _: RequirePermission<UserInvite>,
+    State(state): State<AppState>,
+    Extension(Tenant(tenant)): Extension<Tenant>,
+    Query(mut request): Query<InvitationSearchRequest>,
+) -> Result<Collection<InvitationResponse>> {
+    let now = Utc::now().timestamp() as u64;
+    request.tenant = tenant;
+    request.now = now;
+
+    let invitations = InvitationRepository::new()
+        .search(&state.db, &request)
+        .await?;
+
+    api_ok_with_data(Collection {
+        items: invitations
+            .items
+            .into_iter()
+            .map(|invitation| InvitationResponse::new(invitation, now))
+            .collect(),
+        total: invitations.total,
+    })
+}
+
+/// create a pending user holding the roles in the active tenant and send them an invitation.
+///
+/// giving roles needs the permission to grant them. a failed delivery is recorded in
+/// `last_error`, the invitation can be sent again.
+pub async fn invite(
+    _: RequirePermission<UserInvite>,
+    grant: Option<RequirePermission<UserGrant>>,
+    State(state): State<AppState>,
+    Extension(Tenant(tenant)): Extension<Tenant>,
+    Extension(UserID(user_id)): Extension<UserID>,
+    Json(request): Json<InviteRequest>,
+) -> Result<InvitationResponse> {
+    request.validate()?;
+    check_grant(&grant, !request.roles.is_empty())?;
+
+    let users = UserRepository::new();
+    if users
+        .find_by_account(&request.account, &state.db)
+        .await?
+        .is_some()
+    {
+        return Err(Error::BadRequest("账号已存在".to_string()));
+    }
+
+    check_roles(&state, &tenant, &request.roles).await?;
+    check_department(&state, &tenant, &request.department_id).await?;
+
+    // no password can match until the invitee sets one
+    let mut user = User {
+        base: BaseModel::new(state.id_gen.next_id().await?),
+        secret: Secret {
+            account: request.account,
+            password: String::new(),
+        },
+        name: request.name,
+        phone: request.phone,
+        email: request.email,
+        department_id: request.department_id,
+        status: AccountStatus::Pending,
+        ..Default::default()
+    };
+    user.set_roles_in(&tenant, &request.roles);
+
+    let mut invitation = Invitation::new(
+        state.id_gen.next_id().await?,
+        tenant,
+        user.base.id.clone(),
+        user.email.clone(),
+        user_id,
+    );
+    let now = Utc::now().timestamp() as u64;
+    let token = invitation.issue(now, state.config.invitation.ttl)?;
+
+    let repository = InvitationRepository::new();
+    users.create(&user, &state.db).await?;
+    repository.create(&invitation, &state.db).await?;
+
+    if !request.roles.is_empty() {
+        state.rbac.reset().await?;
+    }
+
+    deliver(&state, &mut invitation, &user, &token).await;
+    if !invitation.last_error.is_empty() {
+        repository.update(&invitation, &state.db).await?;
+    }
+
+    api_ok_with_data(InvitationResponse::new(invitation, now))
+}
+
+/// send the invitation again with a new token, the previous token stops working.
+pub async fn resend(
+    _: RequirePermission<UserInvite>,
+    State(state): State<AppState>,
+    Extension(Tenant(tenant)): Extension<Tenant>,
+    Path(id): Path<String>,
+) -> Result<InvitationResponse> {
+    let mut invitation = find_invitation(&state, &tenant, &id).await?;
+    let user = UserRepository::new()
+        .find_by_id(&invitation.user_id, &state.db)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    let now = Utc::now().timestamp() as u64;
+    let token = invitation
+        .issue(now, state.config.invitation.ttl)
+        .map_err(|err| Error::BadRequest(err.to_string()))?;
+    deliver(&state, &mut invitation, &user, &token).await;
+
+    InvitationRepository::new()
+        .update(&invitation, &state.db)
+        .await?;
+
+    api_ok_with_data(InvitationResponse::new(invitation, now))
+}
+
+/// revoke the invitation and delete the pending user
+pub async fn revoke(
+    _: RequirePermission<UserInvite>,
+    State(state): State<AppState>,
+    Extension(Tenant(tenant)): Extension<Tenant>,
+    Path(id): Path<String>,
+) -> Result<()> {
+    let mut invitation = find_invitation(&state, &tenant, &id).await?;
+
+    invitation
+        .revoke(Utc::now().timestamp() as u64)
+        .map_err(|err| Error::BadRequest(err.to_string()))?;
+    InvitationRepository::new()
+        .update(&invitation, &state.db)
+        .await?;
+
+    let users = UserRepository::new();
+    if let Some(mut user) = users.find_by_id(&invitation.user_id, &state.db).await? {
+        if user.status == AccountStatus::Pending {
+            let had_roles = !user.assignments().is_empty();
+
+            user.base.delete();
+            users.update(&user, &state.db).await?;
+
+            if had_roles {
+                state.rbac.reset().await?;
+            }
+        }
+    }
+
+    api_ok()
+}
+
+/// set the password of the invited user and activate the account, the token works once.
+pub async fn accept(
+    State(state): State<AppState>,
+    Json(request): Json<AcceptRequest>,
+) -> Result<()> {
+    request.validate()?;
+
+    let repository = InvitationRepository::new();
+    let mut invitation = repository
+        .find_by_token(&request.token, &state.db)
+        .await?
+        .ok_or(Error::BadRequest("邀请无效".to_string()))?;
+
+    let now = Utc::now().timestamp() as u64;
+    invitation
+        .accept(now)
+        .map_err(|err| Error::BadRequest(err.to_string()))?;
+
+    let users = UserRepository::new();
+    let mut user = users
+        .find_by_id(&invitation.user_id, &state.db)
+        .await?
+        .ok_or(Error::BadRequest("邀请无效".to_string()))?;
+
+    let user_id = user.base.id.clone();
+    user.transition(AccountStatus::Active, "接受邀请", &user_id, now)
+        .map_err(|_| Error::BadRequest("邀请无效".to_string()))?;
+
+    // the version check lets only one request use the token
+    repository.update(&invitation, &state.db).await?;
+
+    user.secret.change_password(request.password);
+    users.update(&user, &state.db).await?;
+
+    api_ok()
+}
+
+/// send the invitation to the invitee, a failure is kept in `last_error`
+async fn deliver(state: &AppState, invitation: &mut Invitation, user: &User, token: &str) {
+    let link = state.config.invitation.accept_url.replace("{token}", token);
+    let expires_at = DateTime::<Utc>::from_timestamp(invitation.expires_at as i64, 0)
+        .map(|time| time.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+        .unwrap_or_default();
+
+    let message = Message {
+        to: invitation.contact.clone(),
+        subject: "账号邀请".to_string(),
+        content: format!(
+            "{}, 你好:\n你被邀请使用账号 {} 登录系统, 请在 {} 之前打开以下链接设置密码:\n{}",
+            user.name, user.secret.account, expires_at, link
+        ),
+    };
+
+    invitation.last_error = match state.sender.send(&message).await {
+        Ok(()) => String::new(),
+        Err(err) => {
+            println!("failed to send invitation {}: {}", invitation.base.id, err);
+            format!("消息发送失败: {}", err)
+        }
+    };
+}
+
+/// returns the invitation if it exists in the tenant
+async fn find_invitation(
+    state: &AppState,
+    tenant: &str,
+    id: &str,
+) -> std::result::Result<Invitation, Error> {
+    let invitation = InvitationRepository::new()
+        .find_by_id(id, &state.db)
+        .await?
+        .filter(|invitation| invitation.tenant == tenant)
+        .ok_or(Error::NotFound)?;
+
+    Ok(invitation)
+}
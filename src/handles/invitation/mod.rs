@@ -0,0 +1,4 @@
+mod invitation_handles;
+mod types;
+
+pub use invitation_handles::*;
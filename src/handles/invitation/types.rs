@@ -0,0 +1,144 @@
+use mongodb::bson::{doc, Document};
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+use crate::{
+    database::repositories::{default_page, default_page_size, IFilter, IPaginator},
+    domain::invitation::{Invitation, InvitationStatus},
+    impl_paginator,
+};
+
+#[derive(Deserialize)]
+pub struct InvitationSearchRequest {
+    #[serde(default = "default_page")]
+    pub page: i64,
+    #[serde(default = "default_page_size")]
+    pub page_size: i64,
+    pub status: Option<InvitationStatus>,
+    /// the active tenant, filled in by the handler
+    #[serde(skip)]
+    pub tenant: String,
+    /// the current time expired invitations are found with, filled in by the handler
+    #[serde(skip)]
+    pub now: u64,
+}
+
+impl IFilter for InvitationSearchRequest {
+    fn to_doc(&self) -> Document {
+        let mut filter = doc! { "deleted_at": 0, "tenant": self.tenant.clone() };
+
+        let now = self.now as i64;
+        match self.status {
+            Some(InvitationStatus::Pending) => {
+                filter.insert("accepted_at", 0);
+                filter.insert("revoked_at", 0);
+                filter.insert("expires_at", doc! { "$gt": now });
+            }
+            Some(InvitationStatus::Expired) => {
+                filter.insert("accepted_at", 0);
+                filter.insert("revoked_at", 0);
+                filter.insert("expires_at", doc! { "$lte": now });
+            }
+            Some(InvitationStatus::Accepted) => {
+                filter.insert("accepted_at", doc! { "$gt": 0 });
+            }
+            Some(InvitationStatus::Revoked) => {
+                filter.insert("revoked_at", doc! { "$gt": 0 });
+            }
+            None => {}
+        }
+
+        filter
+    }
+}
+
+impl_paginator!(InvitationSearchRequest);
+
+/// invite a user, the user is created pending until the invitation is accepted
+#[derive(Deserialize, Validate)]
+pub struct InviteRequest {
+    #[validate(length(min = 3, max = 32, message = "账号长度为3-32"))]
+    pub account: String,
+    #[validate(length(min = 1, max = 32, message = "姓名长度为1-32"))]
+    pub name: String,
+    /// the invitation is sent to the address
+    #[validate(email(message = "邮箱格式错误"))]
+    pub email: String,
+    #[serde(default)]
+    pub phone: String,
+    #[serde(default)]
+    pub department_id: String,
+    /// roles in the active tenant
+    #[serde(default)]
+    pub roles: Vec<String>,
+}
+
+/// set the password of an invited user
+#[derive(Deserialize, Validate)]
+pub struct AcceptRequest {
+    #[validate(length(min = 1, message = "邀请无效"))]
+    pub token: String,
+    #[validate(length(min = 6, max = 64, message = "密码长度为6-64"))]
+    pub password: String,
+}
+
+/// an invitation without its token
+#[derive(Serialize)]
+pub struct InvitationResponse {
+    pub id: String,
+    pub user_id: String,
+    pub contact: String,
+    pub invited_by: String,
+    pub status: InvitationStatus,
+    pub expires_at: u64,
+    pub accepted_at: u64,
+    pub revoked_at: u64,
+    pub sent_count: u32,
+    pub last_error: String,
+    pub created_at: u64,
+}
+
+impl InvitationResponse {
+    pub fn new(invitation: Invitation, now: u64) -> Self {
+        InvitationResponse {
+            status: invitation.status(now),
+            id: invitation.base.id,
+            user_id: invitation.user_id,
+            contact: invitation.contact,
+            invited_by: invitation.invited_by,
+            expires_at: invitation.expires_at,
+            accepted_at: invitation.accepted_at,
+            revoked_at: invitation.revoked_at,
+            sent_count: invitation.sent_count,
+            last_error: invitation.last_error,
+            created_at: invitation.base.created_at,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pending_filter() {
+        let request = InvitationSearchRequest {
+            page: 1,
+            page_size: 20,
+            status: Some(InvitationStatus::Pending),
+            tenant: "shop".to_string(),
+            now: 100,
+        };
+
+        assert_eq!(
+            request.to_doc(),
+            doc! {
+                "deleted_at": 0,
+                "tenant": "shop",
+                "accepted_at": 0,
+                "revoked_at": 0,
+                "expires_at": { "$gt": 100_i64 },
+            }
+        );
+    }
+}
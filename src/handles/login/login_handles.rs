@@ -1,29 +1,178 @@
-use axum::{extract::State, Json};
+use axum::{extract::State, Extension, Json};
+use chrono::Utc;
 
 use crate::{
-    config::AppState, database::repositories::user::UserRepository,
-    handles::response::api_ok_with_data,
+    config::AppState,
+    database::repositories::login_record::LoginRecordRepository,
+    domain::{
+        common::DEFAULT_TENANT,
+        login_record::{LoginFailure, LoginRecord},
+        user::User,
+    },
+    handles::{
+        middlewares::{Account, ClientInfo, Realm},
+        response::api_ok_with_data,
+    },
+    jwt::TokenPayload,
 };
 
 use super::super::errors::{Error, Result};
 
-use super::types::{AuthRequest, AuthResponse};
+use super::types::{AuthRequest, AuthResponse, UserInfo};
 
+/// sign in to a tenant of the route's realm, every attempt is written to the login history.
 pub async fn login(
     State(state): State<AppState>,
+    Realm(realm): Realm,
+    client: ClientInfo,
     Json(request): Json<AuthRequest>,
 ) -> Result<AuthResponse> {
-    let repository = UserRepository::new();
+    let repository = state.users_of(&realm).ok_or(Error::NotFound)?;
     let user = repository
         .find_by_account(&request.account, &state.db)
         .await?;
 
-    if let Some(user) = user {
-        if user.secret.is_match(&request.password) {
-            let token = state.jwt.create_token(user)?;
-            return api_ok_with_data(AuthResponse { token });
-        }
+    let tenant = request.tenant.unwrap_or_else(|| DEFAULT_TENANT.to_string());
+
+    let mut record = LoginRecord::new(
+        state.id_gen.next_id().await?,
+        request.account,
+        tenant.clone(),
+        client.ip,
+        client.user_agent,
+    );
+
+    let now = Utc::now().timestamp() as u64;
+    let result = authenticate(user, &request.password, &tenant, now);
+    match &result {
+        Ok(user) => record.succeed(user.base.id.clone()),
+        Err((user_id, failure)) => record.fail(user_id.clone(), *failure),
+    }
+
+    // the history must not keep users from signing in
+    if let Err(err) = LoginRecordRepository::new()
+        .create(&record, &state.db)
+        .await
+    {
+        println!("failed to record login of {}: {}", record.account, err);
     }
 
-    Err(Error::BadRequest("用户名或密码错误".to_string()))
+    let user = result.map_err(|(_, failure)| {
+        let message = match failure {
+            LoginFailure::UnknownAccount | LoginFailure::WrongPassword => "用户名或密码错误",
+            LoginFailure::Pending => "账号尚未激活",
+            LoginFailure::Locked => "账号已被锁定",
+            LoginFailure::Disabled => "用户已被禁用",
+            LoginFailure::Expired => "账号已过期",
+            LoginFailure::NotInTenant => "用户不属于该租户",
+        };
+
+        Error::BadRequest(message.to_string())
+    })?;
+
+    repository
+        .set_last_login(&user.base.id, now, &state.db)
+        .await?;
+
+    let mut payload = TokenPayload::from(user);
+    payload.tenant = tenant;
+    payload.realm = realm;
+
+    let token = state.jwt.create_token(payload)?;
+    api_ok_with_data(AuthResponse { token })
+}
+
+/// returns the signed-in user of the route's realm
+pub async fn me(
+    State(state): State<AppState>,
+    Realm(realm): Realm,
+    Extension(Account(account)): Extension<Account>,
+) -> Result<UserInfo> {
+    let user = state
+        .users_of(&realm)
+        .ok_or(Error::NotFound)?
+        .find_by_account(&account, &state.db)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    api_ok_with_data(UserInfo {
+        user_id: user.base.id,
+        name: user.name,
+        avatar: user.avatar,
+    })
+}
+
+/// returns the user if it may sign in to the tenant with the password,
+/// or the id of the user, if any, with the reason it may not.
+fn authenticate(
+    user: Option<User>,
+    password: &str,
+    tenant: &str,
+    now: u64,
+) -> std::result::Result<User, (String, LoginFailure)> {
+    let Some(user) = user else {
+        return Err((String::new(), LoginFailure::UnknownAccount));
+    };
+
+    let failure = if !user.secret.is_match(password) {
+        LoginFailure::WrongPassword
+    } else if let Some(failure) = LoginFailure::for_status(user.status_at(now)) {
+        failure
+    } else if !user.in_tenant(tenant) {
+        LoginFailure::NotInTenant
+    } else {
+        return Ok(user);
+    };
+
+    Err((user.base.id, failure))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::{common::Secret, user::AccountStatus};
+
+    use super::*;
+
+    #[test]
+    fn test_authenticate() {
+        let mut user = User {
+            secret: Secret::new("alice".to_string(), "secret".to_string()).unwrap(),
+            status: AccountStatus::Active,
+            expires_at: Some(100),
+            role_name: "admin".to_string(),
+            ..Default::default()
+        };
+        user.base.id = "1".to_string();
+
+        let failure = |user: Option<User>, password: &str, tenant: &str| {
+            authenticate(user, password, tenant, 50)
+                .err()
+                .map(|(_, failure)| failure)
+        };
+
+        assert_eq!(
+            failure(None, "secret", "default"),
+            Some(LoginFailure::UnknownAccount)
+        );
+        assert_eq!(
+            failure(Some(user.clone()), "wrong", "default"),
+            Some(LoginFailure::WrongPassword)
+        );
+        assert_eq!(
+            failure(Some(user.clone()), "secret", "shop"),
+            Some(LoginFailure::NotInTenant)
+        );
+        assert_eq!(failure(Some(user.clone()), "secret", "default"), None);
+
+        assert_eq!(
+            authenticate(Some(user.clone()), "secret", "default", 100).err(),
+            Some(("1".to_string(), LoginFailure::Expired))
+        );
+
+        user.status = AccountStatus::Locked;
+        assert_eq!(
+            failure(Some(user), "secret", "default"),
+            Some(LoginFailure::Locked)
+        );
+    }
 }
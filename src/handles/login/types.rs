@@ -4,6 +4,9 @@ use serde::{Deserialize, Serialize};
 pub struct AuthRequest {
     pub account: String,
     pub password: String,
+    /// tenant to sign in to, the default tenant if empty
+    #[serde(default)]
+    pub tenant: Option<String>,
 }
 
 #[derive(Serialize, Deserialize)]
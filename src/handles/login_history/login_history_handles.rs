@@ -0,0 +1,59 @@
+use axum::{
+    extract::{Query, State},
+    Extension,
+};
+
+use crate::{
+    config::AppState,
+    database::repositories::{login_record::LoginRecordRepository, Collection},
+    handles::{
+        middlewares::{Tenant, UserID},
+        permissions::{LoginHistoryRead, RequirePermission},
+        response::api_ok_with_data,
+    },
+};
+
+use super::super::errors::Result;
+
+use super::types::{LoginHistorySearchRequest, LoginRecordResponse};
+
+/// list the login attempts of the caller, in every tenant
+pub async fn mine(
+    State(state): State<AppState>,
+    Extension(UserID(user_id)): Extension<UserID>,
+    Query(mut request): Query<LoginHistorySearchRequest>,
+) -> Result<Collection<LoginRecordResponse>> {
+    request.user_id = Some(user_id);
+
+    search(&state, &request).await
+}
+
+/// list the login attempts to the active tenant, failed attempts of unknown accounts included
+pub async fn list(
+    _: RequirePermission<LoginHistoryRead>,
+    State(state): State<AppState>,
+    Extension(Tenant(tenant)): Extension<Tenant>,
+    Query(mut request): Query<LoginHistorySearchRequest>,
+) -> Result<Collection<LoginRecordResponse>> {
+    request.tenant = Some(tenant);
+
+    search(&state, &request).await
+}
+
+async fn search(
+    state: &AppState,
+    request: &LoginHistorySearchRequest,
+) -> Result<Collection<LoginRecordResponse>> {
+    let records = LoginRecordRepository::new()
+        .search(&state.db, request)
+        .await?;
+
+    api_ok_with_data(Collection {
+        items: records
+            .items
+            .into_iter()
+            .map(LoginRecordResponse::from)
+            .collect(),
+        total: records.total,
+    })
+}
@@ -0,0 +1,4 @@
+mod login_history_handles;
+mod types;
+
+pub use login_history_handles::*;
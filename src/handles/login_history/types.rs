@@ -0,0 +1,133 @@
+use mongodb::bson::{doc, Document};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    database::repositories::{default_page, default_page_size, IFilter, IPaginator},
+    domain::login_record::{LoginFailure, LoginRecord},
+    impl_paginator,
+};
+
+#[derive(Deserialize)]
+pub struct LoginHistorySearchRequest {
+    #[serde(default = "default_page")]
+    pub page: i64,
+    #[serde(default = "default_page_size")]
+    pub page_size: i64,
+    pub account: Option<String>,
+    pub success: Option<bool>,
+    pub ip: Option<String>,
+    /// unix timestamps bounding the time of the attempts, both inclusive
+    pub start: Option<u64>,
+    pub end: Option<u64>,
+    /// the tenant signed in to, filled in by the handler
+    #[serde(skip)]
+    pub tenant: Option<String>,
+    /// the user of the attempts, filled in by the handler
+    #[serde(skip)]
+    pub user_id: Option<String>,
+}
+
+impl IFilter for LoginHistorySearchRequest {
+    fn to_doc(&self) -> Document {
+        let mut filter = doc! { "deleted_at": 0 };
+
+        if let Some(account) = &self.account {
+            if !account.is_empty() {
+                filter.insert("account", account.clone());
+            }
+        }
+
+        if let Some(success) = self.success {
+            filter.insert("success", success);
+        }
+
+        if let Some(ip) = &self.ip {
+            if !ip.is_empty() {
+                filter.insert("ip", ip.clone());
+            }
+        }
+
+        let mut created_at = Document::new();
+        if let Some(start) = self.start {
+            created_at.insert("$gte", start as i64);
+        }
+        if let Some(end) = self.end {
+            created_at.insert("$lte", end as i64);
+        }
+        if !created_at.is_empty() {
+            filter.insert("created_at", created_at);
+        }
+
+        if let Some(tenant) = &self.tenant {
+            filter.insert("tenant", tenant.clone());
+        }
+
+        if let Some(user_id) = &self.user_id {
+            filter.insert("user_id", user_id.clone());
+        }
+
+        filter
+    }
+}
+
+impl_paginator!(LoginHistorySearchRequest);
+
+#[derive(Serialize)]
+pub struct LoginRecordResponse {
+    pub id: String,
+    pub user_id: String,
+    pub account: String,
+    pub tenant: String,
+    pub success: bool,
+    pub failure: Option<LoginFailure>,
+    pub ip: String,
+    pub user_agent: String,
+    pub created_at: u64,
+}
+
+impl From<LoginRecord> for LoginRecordResponse {
+    fn from(record: LoginRecord) -> Self {
+        LoginRecordResponse {
+            id: record.base.id,
+            user_id: record.user_id,
+            account: record.account,
+            tenant: record.tenant,
+            success: record.success,
+            failure: record.failure,
+            ip: record.ip,
+            user_agent: record.user_agent,
+            created_at: record.base.created_at,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_filter() {
+        let request = LoginHistorySearchRequest {
+            page: 1,
+            page_size: 20,
+            account: Some("alice".to_string()),
+            success: Some(false),
+            ip: None,
+            start: None,
+            end: Some(200),
+            tenant: Some("shop".to_string()),
+            user_id: None,
+        };
+
+        assert_eq!(
+            request.to_doc(),
+            doc! {
+                "deleted_at": 0,
+                "account": "alice",
+                "success": false,
+                "created_at": { "$lte": 200_i64 },
+                "tenant": "shop",
+            }
+        );
+    }
+}
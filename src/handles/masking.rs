@@ -0,0 +1,138 @@
+use std::{cell::RefCell, collections::HashMap};
+
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use serde::Serializer;
+
+use crate::{actors::rbac::RbacActorHandler, config::AppState};
+
+use super::{
+    middlewares::{Account, Realm, Tenant},
+    permissions::Permission,
+};
+
+/// permission decisions of the caller, cached for the rest of the request
+struct Grants {
+    rbac: RbacActorHandler,
+    account: String,
+    tenant: String,
+    checked: RefCell<HashMap<&'static str, bool>>,
+}
+
+impl Grants {
+    fn granted(&self, permission: &'static str) -> bool {
+        *self
+            .checked
+            .borrow_mut()
+            .entry(permission)
+            .or_insert_with(|| {
+                self.rbac
+                    .check_permission(&self.account, &self.tenant, permission)
+                    .unwrap_or(false)
+            })
+    }
+}
+
+tokio::task_local! {
+    static GRANTS: Grants;
+}
+
+/// make the caller's permissions available to the masking helpers while the
+/// response is serialised.
+///
+/// every permission is checked against the rbac engine of the route's realm at most
+/// once per request.
+pub async fn masking(
+    State(state): State<AppState>,
+    Realm(realm): Realm,
+    request: Request,
+    next: Next,
+) -> Response {
+    let (Some(Account(account)), Some(Tenant(tenant)), Some(rbac)) = (
+        request.extensions().get::<Account>().cloned(),
+        request.extensions().get::<Tenant>().cloned(),
+        state.rbac_of(&realm),
+    ) else {
+        return next.run(request).await;
+    };
+
+    let grants = Grants {
+        rbac: rbac.clone(),
+        account,
+        tenant,
+        checked: RefCell::new(HashMap::new()),
+    };
+
+    GRANTS.scope(grants, next.run(request)).await
+}
+
+/// returns true if the caller of the current request holds the permission `P`.
+///
+/// outside of a request nothing is granted.
+pub fn granted<P: Permission>() -> bool {
+    GRANTS
+        .try_with(|grants| grants.granted(P::NAME))
+        .unwrap_or(false)
+}
+
+/// omit the field for callers lacking the permission `P`
+///
+/// ```ignore
+/// #[serde(skip_serializing_if = "masking::lacks::<UserAgeRead, _>")]
+/// pub age: u8,
+/// ```
+pub fn lacks<P: Permission, T>(_: &T) -> bool {
+    !granted::<P>()
+}
+
+/// serialise the field masked for callers lacking the permission `P`,
+/// only the first 3 and last 4 characters are kept.
+///
+/// ```ignore
+/// #[serde(serialize_with = "masking::mask::<UserPhoneRead, _>")]
+/// pub phone: String,
+/// ```
+pub fn mask<P: Permission, S: Serializer>(value: &str, serializer: S) -> Result<S::Ok, S::Error> {
+    if granted::<P>() {
+        return serializer.serialize_str(value);
+    }
+
+    serializer.serialize_str(&mask_text(value))
+}
+
+fn mask_text(value: &str) -> String {
+    let chars: Vec<char> = value.chars().collect();
+
+    if chars.len() <= 7 {
+        return "*".repeat(chars.len());
+    }
+
+    let hidden = chars.len() - 7;
+    chars[..3]
+        .iter()
+        .chain(std::iter::repeat_n(&'*', hidden))
+        .chain(chars[chars.len() - 4..].iter())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::handles::permissions::UserPhoneRead;
+
+    use super::*;
+
+    #[test]
+    fn test_mask_text() {
+        assert_eq!(mask_text("13812345678"), "138****5678");
+        assert_eq!(mask_text("1234567"), "*******");
+        assert_eq!(mask_text(""), "");
+    }
+
+    #[test]
+    fn test_nothing_granted_outside_request() {
+        assert!(!granted::<UserPhoneRead>());
+    }
+}
@@ -1,16 +1,32 @@
+use std::{
+    convert::Infallible,
+    net::{IpAddr, SocketAddr},
+};
+
 use axum::{
-    extract::{Request, State},
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts, Request, State},
+    http::{header::USER_AGENT, request::Parts},
     middleware::Next,
     response::{IntoResponse, Response},
 };
+use chrono::Utc;
 use mongodb::Database;
 use serde::Deserialize;
 
-use crate::{config::AppState, handles::response::api_system_error};
+use crate::{
+    actors::rbac::{RbacActorHandler, SUPERUSER},
+    config::AppState,
+    database::repositories::scope::{self, ResolvedScope},
+    domain::common::STAFF_REALM,
+};
 
 use super::{
     errors,
-    response::{api_permission_denied, api_unauthorized},
+    response::{
+        api_permission_denied_with_correlation, api_system_error, api_unauthorized,
+        api_unauthorized_with_message, PermissionDenied,
+    },
 };
 
 #[derive(Debug, Clone, Default, Deserialize)]
@@ -19,9 +35,157 @@ pub struct UserID(pub String);
 #[derive(Debug, Clone)]
 pub struct Account(pub String);
 
+/// the active tenant of the request
+#[derive(Debug, Clone)]
+pub struct Tenant(pub String);
+
+/// the user realm a route belongs to, set on the routers of the realms.
+///
+/// routes outside of them belong to the staff.
+#[derive(Debug, Clone)]
+pub struct Realm(pub String);
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Realm {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        let realm = parts
+            .extensions
+            .get::<Realm>()
+            .cloned()
+            .unwrap_or_else(|| Realm(STAFF_REALM.to_string()));
+
+        Ok(realm)
+    }
+}
+
+/// header used to switch the active tenant, overrides the tenant in the token
+pub const TENANT_HEADER: &str = "X-Tenant";
+
+/// the data scope of the caller in the active tenant.
+///
+/// pass it to the `*_scoped` queries of repositories that opt in to data scopes.
+#[derive(Debug, Clone)]
+pub struct DataScope(pub ResolvedScope);
+
+#[async_trait]
+impl FromRequestParts<AppState> for DataScope {
+    type Rejection = errors::Error;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let account = parts
+            .extensions
+            .get::<Account>()
+            .ok_or(errors::Error::Unauthorized)?;
+        let tenant = parts
+            .extensions
+            .get::<Tenant>()
+            .ok_or(errors::Error::Unauthorized)?;
+
+        let realm = parts
+            .extensions
+            .get::<Realm>()
+            .map_or(STAFF_REALM, |Realm(realm)| realm.as_str());
+        let (Some(rbac), Some(users)) = (state.rbac_of(realm), state.users_of(realm)) else {
+            return Err(errors::Error::Unauthorized);
+        };
+
+        let scope = scope::resolve(&account.0, &tenant.0, rbac, &users, &state.db).await?;
+
+        Ok(DataScope(scope))
+    }
+}
+
+/// address and user agent of the client.
+///
+/// the address is the peer of the connection. behind a trusted proxy it is taken from
+/// the `X-Forwarded-For` or `X-Real-IP` header instead, see [client_ip].
+#[derive(Debug, Clone, Default)]
+pub struct ClientInfo {
+    pub ip: String,
+    pub user_agent: String,
+}
+
+#[async_trait]
+impl FromRequestParts<AppState> for ClientInfo {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let header = |name: &str| {
+            parts
+                .headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.trim().to_string())
+        };
+
+        let peer = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(address)| address.ip());
+        let ip = client_ip(
+            peer,
+            header("X-Forwarded-For").as_deref(),
+            header("X-Real-IP").as_deref(),
+            &state.config.trusted_proxies,
+        )
+        .map(|ip| ip.to_string())
+        .unwrap_or_default();
+
+        Ok(ClientInfo {
+            ip,
+            user_agent: header(USER_AGENT.as_str()).unwrap_or_default(),
+        })
+    }
+}
+
+/// returns the address of the client connected from `peer`.
+///
+/// any client can set the forwarding headers, so they are only read when the peer is a
+/// trusted proxy. `X-Forwarded-For` is read from the right, the first address that is not
+/// a trusted proxy is the client.
+fn client_ip(
+    peer: Option<IpAddr>,
+    forwarded_for: Option<&str>,
+    real_ip: Option<&str>,
+    trusted: &[IpAddr],
+) -> Option<IpAddr> {
+    let peer = peer?;
+    if !trusted.contains(&peer) {
+        return Some(peer);
+    }
+
+    let mut client = None;
+    let hops = forwarded_for
+        .unwrap_or_default()
+        .rsplit(',')
+        .map_while(|hop| hop.trim().parse::<IpAddr>().ok());
+    for hop in hops {
+        client = Some(hop);
+        if !trusted.contains(&hop) {
+            break;
+        }
+    }
+    if client.is_some() {
+        return client;
+    }
+
+    real_ip.and_then(|ip| ip.parse().ok()).or(Some(peer))
+}
+
 /// Authorization middleware
+///
+/// tokens are only accepted by the routes of the realm they were issued for.
 pub async fn authorization(
     State(state): State<AppState>,
+    Realm(realm): Realm,
     mut request: Request,
     next: Next,
 ) -> Response {
@@ -41,34 +205,109 @@ pub async fn authorization(
 
     match state.jwt.verify_token(token) {
         Ok(payload) => {
+            if payload.realm != realm {
+                return unauthorized;
+            }
+
+            let tenant = match request.headers().get(TENANT_HEADER) {
+                Some(value) => match value.to_str() {
+                    Ok(value) if !value.is_empty() => value.to_string(),
+                    _ => payload.tenant,
+                },
+                None => payload.tenant,
+            };
+
+            // tokens stay valid until they expire, the account may have been stopped since
+            if let Err(response) = check_status(&state, &realm, &payload.account).await {
+                return response;
+            }
+
             request.extensions_mut().insert(UserID(payload.id));
             request.extensions_mut().insert(Account(payload.account));
+            request.extensions_mut().insert(Tenant(tenant));
             next.run(request).await
         }
         Err(_) => return unauthorized,
     }
 }
 
-/// Rbac Middleware
-pub async fn rbac(State(state): State<AppState>, request: Request, next: Next) -> Response {
-    let uname = match request.extensions().get::<Account>() {
-        Some(uid) => uid.to_owned(),
-        None => return api_unauthorized().into_response(),
+/// refuse the requests of an account that is not active anymore
+async fn check_status(state: &AppState, realm: &str, account: &str) -> Result<(), Response> {
+    if realm == STAFF_REALM && account == SUPERUSER {
+        return Ok(());
+    }
+
+    let Some(users) = state.users_of(realm) else {
+        return Err(api_unauthorized().into_response());
     };
 
-    let is_permission = state
-        .rbac
-        .check_permission(uname.0, request.uri().path().to_string())
-        .await;
+    let user = match users.find_by_account(account, &state.db).await {
+        Ok(Some(user)) => user,
+        Ok(None) => return Err(api_unauthorized().into_response()),
+        Err(err) => return Err(api_system_error(err.to_string()).into_response()),
+    };
 
-    match is_permission {
-        Ok(is_ok) => {
-            if is_ok {
-                return next.run(request).await;
-            }
-        }
-        Err(err) => return api_system_error(err.to_string()).into_response(),
+    match user.status_at(Utc::now().timestamp() as u64).refusal() {
+        Some(message) => Err(api_unauthorized_with_message(message).into_response()),
+        None => Ok(()),
+    }
+}
+
+/// log the explanation of a denied request and answer with its correlation id
+pub async fn explain_denied(
+    state: &AppState,
+    rbac: &RbacActorHandler,
+    account: &str,
+    tenant: &str,
+    method: &str,
+    permission: &str,
+) -> errors::Result<PermissionDenied> {
+    let correlation_id = state.id_gen.next_id().await?;
+
+    match rbac.explain(account, tenant, permission) {
+        Ok(explanation) => println!(
+            "permission denied [{}]: {} {} {} in tenant {}: {:?}",
+            correlation_id, account, method, permission, tenant, explanation
+        ),
+        Err(err) => println!(
+            "permission denied [{}]: {} {} {} in tenant {}, explain failed: {}",
+            correlation_id, account, method, permission, tenant, err
+        ),
     }
 
-    api_permission_denied().into_response()
+    api_permission_denied_with_correlation(correlation_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_client_ip_trusts_proxies_only() {
+        let proxy: IpAddr = "10.0.0.1".parse().unwrap();
+        let client: IpAddr = "203.0.113.7".parse().unwrap();
+        let trusted = [proxy];
+
+        // a client connecting directly can not choose its address
+        assert_eq!(
+            client_ip(Some(client), Some("1.2.3.4"), Some("1.2.3.4"), &trusted),
+            Some(client)
+        );
+
+        assert_eq!(
+            client_ip(
+                Some(proxy),
+                Some("1.2.3.4, 203.0.113.7, 10.0.0.1"),
+                None,
+                &trusted
+            ),
+            Some(client)
+        );
+        assert_eq!(
+            client_ip(Some(proxy), None, Some("203.0.113.7"), &trusted),
+            Some(client)
+        );
+        assert_eq!(client_ip(Some(proxy), None, None, &trusted), Some(proxy));
+        assert_eq!(client_ip(None, Some("1.2.3.4"), None, &trusted), None);
+    }
 }
@@ -1,5 +1,18 @@
+pub mod catalogue;
+mod department;
 mod errors;
+mod health;
+mod invitation;
 mod login;
+mod login_history;
+mod masking;
 mod middlewares;
+mod permissions;
+mod personal_data;
+mod rbac;
+mod recycle;
+mod registration;
 mod response;
+mod role;
 pub mod routes;
+mod user;
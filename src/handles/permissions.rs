@@ -0,0 +1,145 @@
+use std::marker::PhantomData;
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::request::Parts,
+    response::{IntoResponse, Response},
+};
+
+use crate::{
+    actors::rbac::route_permission,
+    config::AppState,
+    domain::{
+        common::STAFF_REALM,
+        role::{Effect, RouteItem},
+    },
+};
+
+use super::{
+    middlewares::{explain_denied, Account, Realm, Tenant},
+    response::{api_permission_denied, api_system_error, api_unauthorized},
+};
+
+/// a named capability that can be granted to roles, such as `role:write`.
+pub trait Permission {
+    /// the name checked by the enforcer and stored in role permissions
+    const NAME: &'static str;
+    /// module the capability is listed under in the catalogue
+    const MODULE: &'static str;
+    /// description shown to admins building roles
+    const DESCRIPTION: &'static str;
+
+    /// returns the capability as a grantable permission of the catalogue
+    fn item() -> RouteItem {
+        RouteItem {
+            module: Self::MODULE.to_string(),
+            path: Self::NAME.to_string(),
+            description: Self::DESCRIPTION.to_string(),
+            effect: Effect::Allow,
+        }
+    }
+}
+
+/// declare capabilities as marker types.
+///
+/// they are listed in the catalogue by the routes requiring them, see `CatalogueRouter`.
+macro_rules! permissions {
+    ($($marker:ident => ($name:literal, $module:literal, $description:literal)),* $(,)?) => {
+        $(
+            pub struct $marker;
+
+            impl Permission for $marker {
+                const NAME: &'static str = $name;
+                const MODULE: &'static str = $module;
+                const DESCRIPTION: &'static str = $description;
+            }
+        )*
+    };
+}
+
+permissions! {
+    RbacRead => ("rbac:read", "权限管理", "查看权限策略、权限目录和判定结果, 导出权限"),
+    RbacWrite => ("rbac:write", "权限管理", "导入角色和用户角色, 编辑存储的权限规则"),
+    RoleRead => ("role:read", "角色管理", "查看角色"),
+    RoleWrite => ("role:write", "角色管理", "创建、修改和删除角色"),
+    UserRead => ("user:read", "用户管理", "查看用户"),
+    UserWrite => ("user:write", "用户管理", "创建、修改、禁用和删除用户"),
+    UserAgeRead => ("user:read-age", "用户管理", "查看用户年龄"),
+    UserPhoneRead => ("user:read-phone", "用户管理", "查看完整的用户手机号"),
+    UserGrant => ("user:grant", "用户管理", "授予用户角色"),
+    UserStatus => ("user:status", "用户管理", "激活、锁定、禁用和恢复用户账号"),
+    UserInvite => ("user:invite", "用户管理", "邀请用户, 重新发送和撤销邀请"),
+    UserImport => ("user:import", "用户管理", "从CSV或XLSX文件批量导入用户"),
+    UserPersonalData => ("user:personal-data", "用户管理", "导出和擦除用户的个人数据"),
+    UserApprove => ("user:approve", "用户管理", "审批自助注册的用户"),
+    DepartmentRead => ("department:read", "部门管理", "查看部门和部门成员"),
+    DepartmentWrite => ("department:write", "部门管理", "创建、修改、移动、合并和删除部门"),
+    LoginHistoryRead => ("login-history:read", "登录日志", "查看所有用户的登录记录"),
+    RecycleRead => ("recycle:read", "回收站", "查看已删除的用户和角色"),
+    RecycleRestore => ("recycle:restore", "回收站", "恢复已删除的用户和角色"),
+    RecyclePurge => ("recycle:purge", "回收站", "彻底删除已删除的用户和角色"),
+}
+
+/// requires the caller to hold the capability `P` in the active tenant.
+///
+/// rules on the path of the request are checked together with the capability,
+/// either of them grants the request and a deny rule on either refuses it.
+///
+/// declare it as a handler argument, the request is rejected with the standard
+/// 403 body before the handler runs:
+///
+/// ```ignore
+/// pub async fn create(_: RequirePermission<RoleWrite>, ...) -> Result<()>
+/// ```
+pub struct RequirePermission<P: Permission>(PhantomData<P>);
+
+#[async_trait]
+impl<P> FromRequestParts<AppState> for RequirePermission<P>
+where
+    P: Permission + Send,
+{
+    type Rejection = Response;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let (Some(Account(account)), Some(Tenant(tenant))) = (
+            parts.extensions.get::<Account>(),
+            parts.extensions.get::<Tenant>(),
+        ) else {
+            return Err(api_unauthorized().into_response());
+        };
+
+        let realm = parts
+            .extensions
+            .get::<Realm>()
+            .map_or(STAFF_REALM, |Realm(realm)| realm.as_str());
+        let Some(rbac) = state.rbac_of(realm) else {
+            return Err(api_unauthorized().into_response());
+        };
+
+        let permission = route_permission(P::NAME, parts.uri.path());
+        match rbac.check_permission(account, tenant, &permission) {
+            Ok(true) => return Ok(RequirePermission(PhantomData)),
+            Ok(false) => {}
+            Err(err) => return Err(api_system_error(err).into_response()),
+        }
+
+        if state.config.rbac.debug {
+            return Err(explain_denied(
+                state,
+                rbac,
+                account,
+                tenant,
+                parts.method.as_str(),
+                &permission,
+            )
+            .await
+            .into_response());
+        }
+
+        Err(api_permission_denied().into_response())
+    }
+}
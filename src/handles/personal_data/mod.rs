@@ -0,0 +1,4 @@
+mod personal_data_handles;
+mod types;
+
+pub use personal_data_handles::*;
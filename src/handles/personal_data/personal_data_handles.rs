@@ -0,0 +1,193 @@
+use axum::{
+    extract::{Path, State},
+    http::header,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use chrono::Utc;
+
+use crate::{
+    config::AppState,
+    database::repositories::{
+        collection_names::{DEPARTMENT, INVITATION, REGISTRATION, USER},
+        department::DepartmentRepository,
+        invitation::InvitationRepository,
+        login_record::LoginRecordRepository,
+        registration::RegistrationRepository,
+        user::UserRepository,
+    },
+    domain::{
+        personal_data::{PersonalData, Reference},
+        user::User,
+    },
+    handles::{
+        middlewares::{DataScope, Tenant, UserID},
+        permissions::{RequirePermission, UserPersonalData},
+        response::api_ok,
+    },
+};
+
+use super::super::errors::{Error, Result};
+
+use super::types::EraseRequest;
+
+/// download everything stored about a user belonging to the active tenant alone inside
+/// the caller's data scope as a zip archive.
+pub async fn export(
+    _: RequirePermission<UserPersonalData>,
+    State(state): State<AppState>,
+    Extension(Tenant(tenant)): Extension<Tenant>,
+    DataScope(scope): DataScope,
+    Path(id): Path<String>,
+) -> std::result::Result<Response, Error> {
+    let user = UserRepository::new()
+        .find_by_id_scoped(&id, &state.db, &scope)
+        .await?
+        .filter(|user| user.belongs_only_to(&tenant))
+        .ok_or(Error::NotFound)?;
+
+    archive(&state, user).await
+}
+
+/// download everything stored about the caller as a zip archive.
+pub async fn export_mine(
+    State(state): State<AppState>,
+    Extension(UserID(user_id)): Extension<UserID>,
+) -> std::result::Result<Response, Error> {
+    let user = UserRepository::new()
+        .find_by_id(&user_id, &state.db)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    archive(&state, user).await
+}
+
+/// anonymise the user and the records about them, then delete the user.
+///
+/// records of other people keep referring to the user by id only. users shared with
+/// other tenants are not erased by the admins of one of them.
+pub async fn erase(
+    _: RequirePermission<UserPersonalData>,
+    State(state): State<AppState>,
+    Extension(UserID(user_id)): Extension<UserID>,
+    Extension(Tenant(tenant)): Extension<Tenant>,
+    DataScope(scope): DataScope,
+    Path(id): Path<String>,
+    Json(request): Json<EraseRequest>,
+) -> Result<()> {
+    if id == user_id {
+        return Err(Error::BadRequest("不能擦除自己的数据".to_string()));
+    }
+
+    let users = UserRepository::new();
+    let mut user = users
+        .find_by_id_scoped(&id, &state.db, &scope)
+        .await?
+        .filter(|user| user.belongs_only_to(&tenant))
+        .ok_or(Error::NotFound)?;
+    if user.secret.account != request.account {
+        return Err(Error::BadRequest("账号不匹配".to_string()));
+    }
+
+    let had_roles = !user.assignments().is_empty();
+    let account = user.secret.account.clone();
+    user.erase(&user_id, Utc::now().timestamp() as u64);
+
+    // the user goes last, an erasure that failed half way can be run again
+    let records = LoginRecordRepository::new()
+        .anonymise(&id, &account, &user.secret.account, &state.db)
+        .await?;
+    let invitations = InvitationRepository::new()
+        .anonymise(&id, &state.db)
+        .await?;
+    let registrations = RegistrationRepository::new()
+        .anonymise(&id, &user.secret.account, &state.db)
+        .await?;
+    DepartmentRepository::new()
+        .remove_head(&id, &state.db)
+        .await?;
+    users.update(&user, &state.db).await?;
+
+    if had_roles {
+        state.rbac.reset().await?;
+    }
+
+    println!(
+        "personal data of user {} erased by {}: {} login records, {} invitations, {} registrations",
+        id, user_id, records, invitations, registrations
+    );
+
+    api_ok()
+}
+
+async fn archive(state: &AppState, user: User) -> std::result::Result<Response, Error> {
+    let id = user.base.id.clone();
+
+    let login_history = LoginRecordRepository::new()
+        .find_by_user(&id, &user.secret.account, &state.db)
+        .await?;
+    let invitations = InvitationRepository::new()
+        .find_by_user(&id, &state.db)
+        .await?;
+    let registrations = RegistrationRepository::new()
+        .find_by_user(&id, &state.db)
+        .await?;
+
+    let mut references = vec![];
+    for invitation in InvitationRepository::new()
+        .find_by_inviter(&id, &state.db)
+        .await?
+    {
+        references.push(Reference::new(
+            INVITATION,
+            &invitation.base.id,
+            "invited_by",
+        ));
+    }
+    for registration in RegistrationRepository::new()
+        .find_by_decider(&id, &state.db)
+        .await?
+    {
+        references.push(Reference::new(
+            REGISTRATION,
+            &registration.base.id,
+            "decided_by",
+        ));
+    }
+    for department in DepartmentRepository::new()
+        .find_by_head(&id, &state.db)
+        .await?
+    {
+        references.push(Reference::new(DEPARTMENT, &department.base.id, "head_ids"));
+    }
+    for other in UserRepository::new()
+        .find_changed_by(&id, &state.db)
+        .await?
+    {
+        if other.base.id != id {
+            references.push(Reference::new(USER, &other.base.id, "status_changes.actor"));
+        }
+    }
+
+    let data = PersonalData::new(
+        Utc::now().timestamp() as u64,
+        user,
+        login_history,
+        invitations,
+        registrations,
+        references,
+    );
+    let content = data.to_archive()?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/zip".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"personal-data-{}.zip\"", id),
+            ),
+        ],
+        content,
+    )
+        .into_response())
+}
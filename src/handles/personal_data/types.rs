@@ -0,0 +1,7 @@
+use serde::Deserialize;
+
+/// erase the personal data of a user, the account is repeated to confirm it
+#[derive(Deserialize)]
+pub struct EraseRequest {
+    pub account: String,
+}
@@ -0,0 +1,4 @@
+mod rbac_handles;
+mod types;
+
+pub use rbac_handles::*;
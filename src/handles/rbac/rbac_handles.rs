@@ -0,0 +1,239 @@
+use axum::{
+    extract::{Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use chrono::Utc;
+
+use crate::{
+    actors::rbac::StoredRule,
+    config::AppState,
+    database::repositories::{policy_bundle::PolicyBundleRepository, user::UserRepository},
+    domain::{
+        policy_bundle::{BundleFormat, ImportPlan, PolicyBundle},
+        role::PRECEDENCE_RULES,
+    },
+    handles::{
+        middlewares::Tenant,
+        permissions::{RbacRead, RbacWrite, RequirePermission},
+        response::api_ok_with_data,
+    },
+};
+
+use super::super::errors::{Error, Result};
+
+use super::types::{
+    CatalogueResponse, ExpirationQuery, ExpiringGrant, ExplainRequest, ExplainResponse,
+    ExportQuery, ImportQuery, PolicyVersionResponse, RuleChangeResponse,
+};
+
+/// returns the version of the rbac polices loaded by this instance.
+///
+/// operators compare it across instances to verify that they converged.
+pub async fn policy_version(
+    _: RequirePermission<RbacRead>,
+    State(state): State<AppState>,
+) -> Result<PolicyVersionResponse> {
+    let version = state.rbac.policy_version();
+
+    api_ok_with_data(PolicyVersionResponse { version })
+}
+
+/// returns every permission that can be granted to a role, grouped by module,
+/// and the precedence rules of allow and deny permissions.
+pub async fn catalogue(
+    _: RequirePermission<RbacRead>,
+    State(state): State<AppState>,
+) -> Result<CatalogueResponse> {
+    api_ok_with_data(CatalogueResponse {
+        modules: state.catalogue.grouped(),
+        precedence: PRECEDENCE_RULES
+            .iter()
+            .map(|rule| rule.to_string())
+            .collect(),
+    })
+}
+
+/// explain why an account is granted or denied a permission.
+pub async fn explain(
+    _: RequirePermission<RbacRead>,
+    State(state): State<AppState>,
+    Extension(Tenant(tenant)): Extension<Tenant>,
+    Json(request): Json<ExplainRequest>,
+) -> Result<ExplainResponse> {
+    let tenant = request.tenant.unwrap_or(tenant);
+    let explanation = state
+        .rbac
+        .explain(&request.account, &tenant, &request.path)?;
+
+    api_ok_with_data(ExplainResponse {
+        account: request.account,
+        tenant,
+        path: request.path,
+        method: request.method,
+        explanation,
+    })
+}
+
+/// download the roles and user-role assignments of the active tenant as a bundle file.
+///
+/// the bundle of the whole deployment is exported by the command line.
+pub async fn export(
+    _: RequirePermission<RbacRead>,
+    State(state): State<AppState>,
+    Extension(Tenant(tenant)): Extension<Tenant>,
+    Query(query): Query<ExportQuery>,
+) -> std::result::Result<Response, Error> {
+    let bundle = PolicyBundleRepository::new()
+        .export(&state.db)
+        .await?
+        .of_tenant(&tenant);
+    let content = bundle.encode(query.format)?;
+
+    let (content_type, file_name) = match query.format {
+        BundleFormat::Json => ("application/json", "rbac-bundle.json"),
+        BundleFormat::Csv => ("text/csv; charset=utf-8", "rbac-bundle.csv"),
+    };
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", file_name),
+            ),
+        ],
+        content,
+    )
+        .into_response())
+}
+
+/// import a bundle file of the active tenant sent as the request body,
+/// the polices are reloaded afterwards.
+///
+/// with `dry_run` only the changes are returned. the bundle of the whole deployment
+/// is imported by the command line.
+pub async fn import(
+    _: RequirePermission<RbacWrite>,
+    State(state): State<AppState>,
+    Extension(Tenant(tenant)): Extension<Tenant>,
+    Query(query): Query<ImportQuery>,
+    body: String,
+) -> Result<ImportPlan> {
+    let bundle = PolicyBundle::decode(&body, query.format)?;
+    if bundle.of_tenant(&tenant) != bundle {
+        return Err(Error::BadRequest(
+            "只能导入当前租户的角色和用户角色".to_string(),
+        ));
+    }
+
+    let repository = PolicyBundleRepository::new();
+    let plan = repository.plan(&bundle, query.strategy, &state.db).await?;
+
+    if query.dry_run {
+        return api_ok_with_data(plan);
+    }
+
+    if !plan.errors.is_empty() {
+        return Err(Error::BadRequest(plan.errors.join("; ")));
+    }
+
+    if plan.has_changes() {
+        repository.apply(&plan, &state.id_gen, &state.db).await?;
+        state.rbac.reset().await?;
+    }
+
+    api_ok_with_data(plan)
+}
+
+/// list the role grants expiring within the next `within` seconds, soonest first.
+pub async fn expirations(
+    _: RequirePermission<RbacRead>,
+    State(state): State<AppState>,
+    Query(query): Query<ExpirationQuery>,
+) -> Result<Vec<ExpiringGrant>> {
+    let before = Utc::now().timestamp() as u64 + query.within;
+    let users = UserRepository::new()
+        .find_expiring(before, &state.db)
+        .await?;
+
+    let mut grants: Vec<ExpiringGrant> = users
+        .into_iter()
+        .flat_map(|user| {
+            user.tenant_roles
+                .iter()
+                .filter_map(|item| {
+                    let expires_at = item.expires_at.filter(|expires_at| *expires_at <= before)?;
+
+                    Some(ExpiringGrant {
+                        user_id: user.base.id.clone(),
+                        account: user.secret.account.clone(),
+                        name: user.name.clone(),
+                        tenant: item.tenant.clone(),
+                        role_name: item.role_name.clone(),
+                        expires_at,
+                    })
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    grants.sort_by_key(|grant| grant.expires_at);
+
+    api_ok_with_data(grants)
+}
+
+/// list the rules of the active tenant stored by the policy adapter
+pub async fn rules(
+    _: RequirePermission<RbacRead>,
+    State(state): State<AppState>,
+    Extension(Tenant(tenant)): Extension<Tenant>,
+) -> Result<Vec<StoredRule>> {
+    let rules = state
+        .rbac
+        .rules()
+        .await?
+        .into_iter()
+        .filter(|rule| rule.domain() == tenant)
+        .collect();
+
+    api_ok_with_data(rules)
+}
+
+/// store a rule of the active tenant through the policy adapter,
+/// the polices are reloaded afterwards.
+pub async fn add_rule(
+    _: RequirePermission<RbacWrite>,
+    State(state): State<AppState>,
+    Extension(Tenant(tenant)): Extension<Tenant>,
+    Json(rule): Json<StoredRule>,
+) -> Result<RuleChangeResponse> {
+    check_domain(&rule, &tenant)?;
+    let changed = state.rbac.add_rule(rule).await?;
+
+    api_ok_with_data(RuleChangeResponse { changed })
+}
+
+/// remove a rule of the active tenant stored by the policy adapter,
+/// the polices are reloaded afterwards.
+pub async fn remove_rule(
+    _: RequirePermission<RbacWrite>,
+    State(state): State<AppState>,
+    Extension(Tenant(tenant)): Extension<Tenant>,
+    Json(rule): Json<StoredRule>,
+) -> Result<RuleChangeResponse> {
+    check_domain(&rule, &tenant)?;
+    let changed = state.rbac.remove_rule(rule).await?;
+
+    api_ok_with_data(RuleChangeResponse { changed })
+}
+
+/// rules are only changed inside the caller's active tenant
+fn check_domain(rule: &StoredRule, tenant: &str) -> std::result::Result<(), Error> {
+    rule.validate()?;
+    if rule.domain() != tenant {
+        return Err(Error::BadRequest("只能修改当前租户的权限规则".to_string()));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,95 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    actors::rbac::Explanation,
+    domain::policy_bundle::{BundleFormat, ConflictStrategy},
+    handles::catalogue::CatalogueModule,
+};
+
+#[derive(Serialize, Deserialize)]
+pub struct PolicyVersionResponse {
+    pub version: String,
+}
+
+#[derive(Deserialize)]
+pub struct ExplainRequest {
+    pub account: String,
+    /// a capability such as `role:write`, or a route path
+    pub path: String,
+    /// recorded in the answer only, polices are not method specific
+    #[serde(default)]
+    pub method: String,
+    /// the tenant to check in, the caller's active tenant if empty
+    pub tenant: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ExplainResponse {
+    pub account: String,
+    pub tenant: String,
+    pub path: String,
+    pub method: String,
+    #[serde(flatten)]
+    pub explanation: Explanation,
+}
+
+#[derive(Serialize)]
+pub struct CatalogueResponse {
+    pub modules: Vec<CatalogueModule>,
+    /// how allow and deny permissions are combined
+    pub precedence: Vec<String>,
+}
+
+fn default_format() -> BundleFormat {
+    BundleFormat::Json
+}
+
+fn default_strategy() -> ConflictStrategy {
+    ConflictStrategy::Skip
+}
+
+#[derive(Deserialize)]
+pub struct ExportQuery {
+    #[serde(default = "default_format")]
+    pub format: BundleFormat,
+}
+
+#[derive(Deserialize)]
+pub struct ImportQuery {
+    #[serde(default = "default_format")]
+    pub format: BundleFormat,
+    #[serde(default = "default_strategy")]
+    pub strategy: ConflictStrategy,
+    /// only return the changes, without importing them
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+fn default_within() -> u64 {
+    // a week
+    7 * 24 * 3600
+}
+
+#[derive(Deserialize)]
+pub struct ExpirationQuery {
+    /// seconds from now
+    #[serde(default = "default_within")]
+    pub within: u64,
+}
+
+/// a role grant that is about to expire
+#[derive(Serialize)]
+pub struct ExpiringGrant {
+    pub user_id: String,
+    pub account: String,
+    pub name: String,
+    pub tenant: String,
+    pub role_name: String,
+    pub expires_at: u64,
+}
+
+#[derive(Serialize)]
+pub struct RuleChangeResponse {
+    /// false if the rule already existed, or did not exist when removing it
+    pub changed: bool,
+}
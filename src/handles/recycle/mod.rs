@@ -0,0 +1,4 @@
+mod recycle_handles;
+mod types;
+
+pub use recycle_handles::*;
@@ -0,0 +1,174 @@
+use axum::{
+    extract::{Path, Query, State},
+    Extension,
+};
+use mongodb::bson::doc;
+
+use crate::{
+    config::AppState,
+    database::repositories::{role::RoleRepository, user::UserRepository, Collection},
+    handles::{
+        middlewares::Tenant,
+        permissions::{RecyclePurge, RecycleRead, RecycleRestore, RequirePermission},
+        response::{api_ok, api_ok_with_data},
+        user::UserResponse,
+    },
+};
+
+use super::super::errors::{Error, Result};
+
+use super::types::{DeletedItem, RecycleCollection, RecycleSearchRequest};
+
+/// list the soft-deleted documents of the collection, users and roles of the active
+/// tenant only. erased users are not listed.
+pub async fn list(
+    _: RequirePermission<RecycleRead>,
+    State(state): State<AppState>,
+    Extension(Tenant(tenant)): Extension<Tenant>,
+    Path(collection): Path<RecycleCollection>,
+    Query(mut request): Query<RecycleSearchRequest>,
+) -> Result<Collection<DeletedItem>> {
+    let items = match collection {
+        RecycleCollection::Users => {
+            request.conditions = doc! { "erased_at": { "$not": { "$gt": 0 } } };
+            request
+                .conditions
+                .extend(UserRepository::membership(&tenant));
+            let users = UserRepository::new().search(&state.db, &request).await?;
+
+            Collection {
+                total: users.total,
+                items: users
+                    .items
+                    .into_iter()
+                    .map(|user| DeletedItem {
+                        id: user.base.id.clone(),
+                        name: user.secret.account.clone(),
+                        deleted_at: user.base.deleted_at,
+                        // without the password
+                        data: to_value(UserResponse::in_tenant(user, &tenant)),
+                    })
+                    .collect(),
+            }
+        }
+        RecycleCollection::Roles => {
+            request.tenant = Some(tenant);
+            let roles = RoleRepository::new().search(&state.db, &request).await?;
+
+            Collection {
+                total: roles.total,
+                items: roles
+                    .items
+                    .into_iter()
+                    .map(|role| DeletedItem {
+                        id: role.base.id.clone(),
+                        name: role.name.clone(),
+                        deleted_at: role.base.deleted_at,
+                        data: to_value(role),
+                    })
+                    .collect(),
+            }
+        }
+    };
+
+    api_ok_with_data(items)
+}
+
+/// restore a soft-deleted document of the active tenant, if no other document took its
+/// account or name.
+pub async fn restore(
+    _: RequirePermission<RecycleRestore>,
+    State(state): State<AppState>,
+    Extension(Tenant(tenant)): Extension<Tenant>,
+    Path((collection, id)): Path<(RecycleCollection, String)>,
+) -> Result<()> {
+    match collection {
+        RecycleCollection::Users => {
+            let repository = UserRepository::new();
+            let user = repository
+                .find_deleted_by_id(&id, &state.db)
+                .await?
+                .filter(|user| user.erased_at == 0 && user.belongs_to(&tenant))
+                .ok_or(Error::NotFound)?;
+
+            if repository
+                .find_by_account(&user.secret.account, &state.db)
+                .await?
+                .is_some()
+            {
+                return Err(Error::BadRequest("账号已被其他用户使用".to_string()));
+            }
+
+            repository.restore(&id, &state.db).await?;
+        }
+        RecycleCollection::Roles => {
+            let repository = RoleRepository::new();
+            let role = repository
+                .find_deleted_by_id(&id, &state.db)
+                .await?
+                .filter(|role| role.tenant == tenant)
+                .ok_or(Error::NotFound)?;
+
+            if repository
+                .find_by_name(&tenant, &role.name, &state.db)
+                .await?
+                .is_some()
+            {
+                return Err(Error::BadRequest("角色名称已被其他角色使用".to_string()));
+            }
+
+            repository.restore(&id, &state.db).await?;
+        }
+    }
+
+    state.rbac.reset().await?;
+
+    api_ok()
+}
+
+/// permanently remove a soft-deleted document of the active tenant.
+pub async fn purge(
+    _: RequirePermission<RecyclePurge>,
+    State(state): State<AppState>,
+    Extension(Tenant(tenant)): Extension<Tenant>,
+    Path((collection, id)): Path<(RecycleCollection, String)>,
+) -> Result<()> {
+    let purged = match collection {
+        RecycleCollection::Users => {
+            let repository = UserRepository::new();
+            if repository
+                .find_deleted_by_id(&id, &state.db)
+                .await?
+                .filter(|user| user.erased_at == 0 && user.belongs_to(&tenant))
+                .is_none()
+            {
+                return Err(Error::NotFound);
+            }
+
+            repository.purge(&id, &state.db).await?
+        }
+        RecycleCollection::Roles => {
+            let repository = RoleRepository::new();
+            if repository
+                .find_deleted_by_id(&id, &state.db)
+                .await?
+                .filter(|role| role.tenant == tenant)
+                .is_none()
+            {
+                return Err(Error::NotFound);
+            }
+
+            repository.purge(&id, &state.db).await?
+        }
+    };
+
+    if !purged {
+        return Err(Error::NotFound);
+    }
+
+    api_ok()
+}
+
+fn to_value<T: serde::Serialize>(value: T) -> serde_json::Value {
+    serde_json::to_value(value).unwrap_or_default()
+}
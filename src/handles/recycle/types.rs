@@ -0,0 +1,84 @@
+use mongodb::bson::{doc, Document};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    database::repositories::{default_page, default_page_size, IFilter, IPaginator},
+    impl_paginator,
+};
+
+/// collections with a recycle bin
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RecycleCollection {
+    Users,
+    Roles,
+}
+
+#[derive(Deserialize)]
+pub struct RecycleSearchRequest {
+    #[serde(default = "default_page")]
+    pub page: i64,
+    #[serde(default = "default_page_size")]
+    pub page_size: i64,
+    /// the active tenant of roles, filled in by the handler
+    #[serde(skip)]
+    pub tenant: Option<String>,
+    /// conditions of the collection, filled in by the handler
+    #[serde(skip)]
+    pub conditions: Document,
+}
+
+impl IFilter for RecycleSearchRequest {
+    fn to_doc(&self) -> Document {
+        let mut filter = doc! { "deleted_at": { "$gt": 0 } };
+
+        if let Some(tenant) = &self.tenant {
+            filter.insert("tenant", tenant.clone());
+        }
+        filter.extend(self.conditions.clone());
+
+        filter
+    }
+}
+
+impl_paginator!(RecycleSearchRequest);
+
+/// a soft-deleted document
+#[derive(Serialize)]
+pub struct DeletedItem {
+    pub id: String,
+    /// account of a user, name of a role
+    pub name: String,
+    pub deleted_at: u64,
+    pub data: serde_json::Value,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_deleted_filter() {
+        let request = RecycleSearchRequest {
+            page: 1,
+            page_size: 20,
+            tenant: Some("shop".to_string()),
+            conditions: Document::new(),
+        };
+
+        assert_eq!(
+            request.to_doc(),
+            doc! { "deleted_at": { "$gt": 0 }, "tenant": "shop" }
+        );
+
+        let request = RecycleSearchRequest {
+            tenant: None,
+            conditions: doc! { "erased_at": { "$not": { "$gt": 0 } } },
+            ..request
+        };
+        assert_eq!(
+            request.to_doc(),
+            doc! { "deleted_at": { "$gt": 0 }, "erased_at": { "$not": { "$gt": 0 } } }
+        );
+    }
+}
@@ -0,0 +1,4 @@
+mod registration_handles;
+mod types;
+
+pub use registration_handles::*;
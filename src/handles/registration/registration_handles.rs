@@ -0,0 +1,401 @@
+use axum::{
+    extract::{Path, Query, State},
+    Extension, Json,
+};
+use chrono::Utc;
+use validator::Validate;
+
+use crate::{
+    actors::sender::Message,
+    config::AppState,
+    database::repositories::{
+        registration::RegistrationRepository, user::UserRepository, Collection,
+    },
+    domain::{
+        captcha::{self, Challenge},
+        common::Secret,
+        registration::Registration,
+        user::{AccountStatus, User, SYSTEM_ACTOR},
+        BaseModel,
+    },
+    handles::{
+        middlewares::{ClientInfo, Tenant, UserID},
+        permissions::{RequirePermission, UserApprove},
+        response::{api_ok, api_ok_with_data},
+    },
+};
+
+use super::super::errors::{Error, Result};
+
+use super::types::{
+    CaptchaResponse, DecisionRequest, RegisterRequest, RegistrationResponse,
+    RegistrationSearchRequest, VerifyRequest,
+};
+
+/// returns a challenge to answer when registering
+pub async fn captcha(State(state): State<AppState>) -> Result<CaptchaResponse> {
+    let config = &state.config.registration;
+    if !config.enabled {
+        return Err(Error::NotFound);
+    }
+
+    let challenge = Challenge::new(
+        &state.config.secret,
+        Utc::now().timestamp() as u64,
+        config.captcha_ttl,
+    );
+
+    api_ok_with_data(CaptchaResponse {
+        question: challenge.question,
+        token: challenge.token,
+    })
+}
+
+/// sign up a pending user and send them a link to verify their email address.
+pub async fn register(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    Json(request): Json<RegisterRequest>,
+) -> Result<()> {
+    let config = &state.config.registration;
+    if !config.enabled {
+        return Err(Error::NotFound);
+    }
+
+    request.validate()?;
+
+    let now = Utc::now().timestamp() as u64;
+    if config.captcha
+        && !captcha::verify(
+            &state.config.secret,
+            &request.captcha_token,
+            &request.captcha_answer,
+            now,
+        )
+    {
+        return Err(Error::BadRequest("验证码错误".to_string()));
+    }
+
+    let registrations = RegistrationRepository::new();
+    let recent = registrations
+        .count_from_ip(
+            &client.ip,
+            now.saturating_sub(config.rate_window),
+            &state.db,
+        )
+        .await?;
+    if recent >= config.max_per_ip {
+        return Err(Error::BadRequest("注册过于频繁, 请稍后再试".to_string()));
+    }
+
+    let users = state.users_of(&config.realm).ok_or(Error::NotFound)?;
+    release_expired(&state, &users, &request, now).await?;
+    if users
+        .find_by_account(&request.account, &state.db)
+        .await?
+        .is_some()
+    {
+        return Err(Error::BadRequest("账号已存在".to_string()));
+    }
+    if users
+        .find_by_email(&request.email, &state.db)
+        .await?
+        .is_some()
+    {
+        return Err(Error::BadRequest("邮箱已被注册".to_string()));
+    }
+
+    let mut user = User {
+        base: BaseModel::new(state.id_gen.next_id().await?),
+        secret: Secret::new(request.account, request.password)?,
+        name: request.name,
+        email: request.email,
+        status: AccountStatus::Pending,
+        ..Default::default()
+    };
+
+    let mut registration = Registration::new(
+        state.id_gen.next_id().await?,
+        config.realm.clone(),
+        user.base.id.clone(),
+        user.secret.account.clone(),
+        user.email.clone(),
+        client.ip,
+    );
+    let token = registration.issue(now, config.verify_ttl);
+
+    users.create(&user, &state.db).await?;
+    registrations.create(&registration, &state.db).await?;
+
+    let link = config.verify_url.replace("{token}", &token);
+    let message = Message {
+        to: user.email.clone(),
+        subject: "验证邮箱".to_string(),
+        content: format!(
+            "{}, 你好:\n你注册了账号 {}, 请打开以下链接验证邮箱:\n{}",
+            user.name, user.secret.account, link
+        ),
+    };
+
+    // free the account again, the user can not verify it without the link
+    if let Err(err) = state.sender.send(&message).await {
+        println!(
+            "failed to send the verification of registration {}: {}",
+            registration.base.id, err
+        );
+
+        user.base.delete();
+        users.update(&user, &state.db).await?;
+        registration.base.delete();
+        registrations.update(&registration, &state.db).await?;
+
+        return Err(Error::OtherError(format!("验证邮件发送失败: {}", err)));
+    }
+
+    api_ok()
+}
+
+/// verify the email address of a registration, the user is activated
+/// unless registrations need an admin's approval.
+pub async fn verify(
+    State(state): State<AppState>,
+    Json(request): Json<VerifyRequest>,
+) -> Result<()> {
+    request.validate()?;
+
+    let repository = RegistrationRepository::new();
+    let mut registration = repository
+        .find_by_token(&request.token, &state.db)
+        .await?
+        .ok_or(Error::BadRequest("验证链接无效".to_string()))?;
+
+    let now = Utc::now().timestamp() as u64;
+    registration
+        .verify(now)
+        .map_err(|err| Error::BadRequest(err.to_string()))?;
+
+    let approved = !state.config.registration.require_approval;
+    if approved {
+        registration.decide(true, SYSTEM_ACTOR, "", now)?;
+    }
+
+    // the version check lets only one request use the token
+    repository.update(&registration, &state.db).await?;
+
+    if approved {
+        activate(&state, &registration, "邮箱验证通过", now).await?;
+    }
+
+    api_ok()
+}
+
+pub async fn list(
+    _: RequirePermission<UserApprove>,
+    State(state): State<AppState>,
+    Extension(Tenant(tenant)): Extension<Tenant>,
+    Query(mut request): Query<RegistrationSearchRequest>,
+) -> Result<Collection<RegistrationResponse>> {
+    check_tenant(&state, &tenant)?;
+
+    let now = Utc::now().timestamp() as u64;
+    request.now = now;
+
+    let registrations = RegistrationRepository::new()
+        .search(&state.db, &request)
+        .await?;
+
+    api_ok_with_data(Collection {
+        items: registrations
+            .items
+            .into_iter()
+            .map(|registration| RegistrationResponse::new(registration, now))
+            .collect(),
+        total: registrations.total,
+    })
+}
+
+/// activate the user of a verified registration and grant the default role
+pub async fn approve(
+    _: RequirePermission<UserApprove>,
+    State(state): State<AppState>,
+    Extension(Tenant(tenant)): Extension<Tenant>,
+    Extension(UserID(user_id)): Extension<UserID>,
+    Path(id): Path<String>,
+    Json(request): Json<DecisionRequest>,
+) -> Result<()> {
+    request.validate()?;
+
+    let mut registration = find_registration(&state, &tenant, &id).await?;
+    let now = Utc::now().timestamp() as u64;
+    registration
+        .decide(true, &user_id, &request.reason, now)
+        .map_err(|err| Error::BadRequest(err.to_string()))?;
+    RegistrationRepository::new()
+        .update(&registration, &state.db)
+        .await?;
+
+    activate(&state, &registration, "注册审核通过", now).await?;
+    notify(
+        &state,
+        &registration,
+        "注册已通过",
+        &format!(
+            "你注册的账号 {} 已通过审核, 现在可以登录了.",
+            registration.account
+        ),
+    )
+    .await;
+
+    api_ok()
+}
+
+/// reject a registration, the pending user is disabled and deleted so the account is free again
+pub async fn reject(
+    _: RequirePermission<UserApprove>,
+    State(state): State<AppState>,
+    Extension(Tenant(tenant)): Extension<Tenant>,
+    Extension(UserID(user_id)): Extension<UserID>,
+    Path(id): Path<String>,
+    Json(request): Json<DecisionRequest>,
+) -> Result<()> {
+    request.validate()?;
+
+    let mut registration = find_registration(&state, &tenant, &id).await?;
+    let now = Utc::now().timestamp() as u64;
+    registration
+        .decide(false, &user_id, &request.reason, now)
+        .map_err(|err| Error::BadRequest(err.to_string()))?;
+    RegistrationRepository::new()
+        .update(&registration, &state.db)
+        .await?;
+
+    let users = state.users_of(&registration.realm).ok_or(Error::NotFound)?;
+    if let Some(mut user) = users.find_by_id(&registration.user_id, &state.db).await? {
+        if user.status == AccountStatus::Pending {
+            user.transition(AccountStatus::Disabled, &request.reason, &user_id, now)?;
+            user.base.delete();
+            users.update(&user, &state.db).await?;
+        }
+    }
+
+    if registration.verified_at > 0 {
+        notify(
+            &state,
+            &registration,
+            "注册未通过",
+            &format!(
+                "你注册的账号 {} 未通过审核. {}",
+                registration.account, request.reason
+            ),
+        )
+        .await;
+    }
+
+    api_ok()
+}
+
+/// delete the registrations holding the account or email address that were not verified
+/// in time with their pending users, so nobody can keep an address from signing up.
+async fn release_expired(
+    state: &AppState,
+    users: &UserRepository,
+    request: &RegisterRequest,
+    now: u64,
+) -> std::result::Result<(), Error> {
+    let registrations = RegistrationRepository::new();
+    let expired = registrations
+        .find_expired(
+            &state.config.registration.realm,
+            &request.account,
+            &request.email,
+            now,
+            &state.db,
+        )
+        .await?;
+
+    for mut registration in expired {
+        if let Some(mut user) = users.find_by_id(&registration.user_id, &state.db).await? {
+            if user.status == AccountStatus::Pending {
+                user.transition(AccountStatus::Disabled, "邮箱验证已过期", SYSTEM_ACTOR, now)?;
+                user.base.delete();
+                users.update(&user, &state.db).await?;
+            }
+        }
+
+        registration.base.delete();
+        registrations.update(&registration, &state.db).await?;
+    }
+
+    Ok(())
+}
+
+/// activate the pending user of the approved registration, granting the default role
+async fn activate(
+    state: &AppState,
+    registration: &Registration,
+    reason: &str,
+    now: u64,
+) -> std::result::Result<(), Error> {
+    let config = &state.config.registration;
+    let users = state.users_of(&registration.realm).ok_or(Error::NotFound)?;
+    let mut user = users
+        .find_by_id(&registration.user_id, &state.db)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    user.transition(AccountStatus::Active, reason, &registration.decided_by, now)
+        .map_err(|err| Error::BadRequest(err.to_string()))?;
+    if !config.default_role.is_empty() {
+        user.grant(&config.tenant, &config.default_role);
+    }
+    users.update(&user, &state.db).await?;
+
+    if !config.default_role.is_empty() {
+        if let Some(rbac) = state.rbac_of(&registration.realm) {
+            rbac.reset().await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// tell the user about the decision, a failed delivery does not undo it
+async fn notify(state: &AppState, registration: &Registration, subject: &str, content: &str) {
+    let message = Message {
+        to: registration.email.clone(),
+        subject: subject.to_string(),
+        content: content.to_string(),
+    };
+
+    if let Err(err) = state.sender.send(&message).await {
+        println!(
+            "failed to notify registration {}: {}",
+            registration.base.id, err
+        );
+    }
+}
+
+/// returns the registration, only admins of the tenant new users join may decide on it
+async fn find_registration(
+    state: &AppState,
+    tenant: &str,
+    id: &str,
+) -> std::result::Result<Registration, Error> {
+    check_tenant(state, tenant)?;
+
+    let registration = RegistrationRepository::new()
+        .find_by_id(id, &state.db)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    Ok(registration)
+}
+
+/// registrations are only managed from the tenant the new users join
+fn check_tenant(state: &AppState, tenant: &str) -> std::result::Result<(), Error> {
+    if tenant != state.config.registration.tenant {
+        return Err(Error::Forbidden);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,157 @@
+use mongodb::bson::{doc, Document};
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+use crate::{
+    database::repositories::{default_page, default_page_size, IFilter, IPaginator},
+    domain::registration::{Registration, RegistrationStatus},
+    impl_paginator,
+};
+
+#[derive(Deserialize)]
+pub struct RegistrationSearchRequest {
+    #[serde(default = "default_page")]
+    pub page: i64,
+    #[serde(default = "default_page_size")]
+    pub page_size: i64,
+    pub status: Option<RegistrationStatus>,
+    /// the current time expired registrations are found with, filled in by the handler
+    #[serde(skip)]
+    pub now: u64,
+}
+
+impl IFilter for RegistrationSearchRequest {
+    fn to_doc(&self) -> Document {
+        let mut filter = doc! { "deleted_at": 0 };
+
+        let now = self.now as i64;
+        match self.status {
+            Some(RegistrationStatus::Unverified) => {
+                filter.insert("verified_at", 0);
+                filter.insert("decided_at", 0);
+                filter.insert("expires_at", doc! { "$gt": now });
+            }
+            Some(RegistrationStatus::Expired) => {
+                filter.insert("verified_at", 0);
+                filter.insert("decided_at", 0);
+                filter.insert("expires_at", doc! { "$lte": now });
+            }
+            Some(RegistrationStatus::AwaitingApproval) => {
+                filter.insert("verified_at", doc! { "$gt": 0 });
+                filter.insert("decided_at", 0);
+            }
+            Some(RegistrationStatus::Approved) => {
+                filter.insert("decided_at", doc! { "$gt": 0 });
+                filter.insert("approved", true);
+            }
+            Some(RegistrationStatus::Rejected) => {
+                filter.insert("decided_at", doc! { "$gt": 0 });
+                filter.insert("approved", false);
+            }
+            None => {}
+        }
+
+        filter
+    }
+}
+
+impl_paginator!(RegistrationSearchRequest);
+
+/// a challenge to answer when registering
+#[derive(Serialize)]
+pub struct CaptchaResponse {
+    pub question: String,
+    pub token: String,
+}
+
+#[derive(Deserialize, Validate)]
+pub struct RegisterRequest {
+    #[validate(length(min = 3, max = 32, message = "账号长度为3-32"))]
+    pub account: String,
+    #[validate(length(min = 6, max = 64, message = "密码长度为6-64"))]
+    pub password: String,
+    #[validate(length(min = 1, max = 32, message = "姓名长度为1-32"))]
+    pub name: String,
+    /// the verification link is sent to the address
+    #[validate(email(message = "邮箱格式错误"))]
+    pub email: String,
+    #[serde(default)]
+    pub captcha_token: String,
+    #[serde(default)]
+    pub captcha_answer: String,
+}
+
+#[derive(Deserialize, Validate)]
+pub struct VerifyRequest {
+    #[validate(length(min = 1, message = "验证链接无效"))]
+    pub token: String,
+}
+
+#[derive(Deserialize, Validate)]
+pub struct DecisionRequest {
+    #[serde(default)]
+    #[validate(length(max = 200, message = "原因长度不能超过200"))]
+    pub reason: String,
+}
+
+/// a registration without its token
+#[derive(Serialize)]
+pub struct RegistrationResponse {
+    pub id: String,
+    pub realm: String,
+    pub user_id: String,
+    pub account: String,
+    pub email: String,
+    pub ip: String,
+    pub status: RegistrationStatus,
+    pub expires_at: u64,
+    pub verified_at: u64,
+    pub decided_at: u64,
+    pub decided_by: String,
+    pub reason: String,
+    pub created_at: u64,
+}
+
+impl RegistrationResponse {
+    pub fn new(registration: Registration, now: u64) -> Self {
+        RegistrationResponse {
+            status: registration.status(now),
+            id: registration.base.id,
+            realm: registration.realm,
+            user_id: registration.user_id,
+            account: registration.account,
+            email: registration.email,
+            ip: registration.ip,
+            expires_at: registration.expires_at,
+            verified_at: registration.verified_at,
+            decided_at: registration.decided_at,
+            decided_by: registration.decided_by,
+            reason: registration.reason,
+            created_at: registration.base.created_at,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_awaiting_approval_filter() {
+        let request = RegistrationSearchRequest {
+            page: 1,
+            page_size: 20,
+            status: Some(RegistrationStatus::AwaitingApproval),
+            now: 100,
+        };
+
+        assert_eq!(
+            request.to_doc(),
+            doc! {
+                "deleted_at": 0,
+                "verified_at": { "$gt": 0 },
+                "decided_at": 0,
+            }
+        );
+    }
+}
@@ -48,6 +48,16 @@ pub fn api_unauthorized() -> Result<()> {
     })
 }
 
+/// the request is unauthorized for a reason the caller should see
+pub fn api_unauthorized_with_message(message: &str) -> Result<()> {
+    Ok(ApiResponse {
+        status: 401,
+        message: message.to_string(),
+        data: None,
+        success: false,
+    })
+}
+
 pub fn api_system_error(message: String) -> Result<()> {
     Ok(ApiResponse {
         status: 500,
@@ -65,3 +75,19 @@ pub fn api_permission_denied() -> Result<()> {
         success: false,
     })
 }
+
+/// details of a denied request, only returned in rbac debug mode
+#[derive(Debug, serde::Serialize)]
+pub struct PermissionDenied {
+    /// maps to the explanation written to the log
+    pub correlation_id: String,
+}
+
+pub fn api_permission_denied_with_correlation(correlation_id: String) -> Result<PermissionDenied> {
+    Ok(ApiResponse {
+        status: 403,
+        message: "Permission denied".to_string(),
+        data: Some(PermissionDenied { correlation_id }),
+        success: false,
+    })
+}
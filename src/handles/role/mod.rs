@@ -0,0 +1,4 @@
+mod role_handles;
+mod types;
+
+pub use role_handles::*;
@@ -0,0 +1,159 @@
+use axum::{
+    extract::{Path, Query, State},
+    Extension, Json,
+};
+use validator::Validate;
+
+use crate::{
+    config::AppState,
+    database::repositories::{role::RoleRepository, user::UserRepository, Collection},
+    domain::role::Role,
+    handles::{
+        middlewares::Tenant,
+        permissions::{RequirePermission, RoleRead, RoleWrite},
+        response::{api_ok, api_ok_with_data},
+    },
+};
+
+use super::super::errors::{Error, Result};
+
+use super::types::{RoleRequest, RoleSearchRequest};
+
+pub async fn list(
+    _: RequirePermission<RoleRead>,
+    State(state): State<AppState>,
+    Extension(Tenant(tenant)): Extension<Tenant>,
+    Query(mut request): Query<RoleSearchRequest>,
+) -> Result<Collection<Role>> {
+    request.tenant = tenant;
+
+    let roles = RoleRepository::new().search(&state.db, &request).await?;
+
+    api_ok_with_data(roles)
+}
+
+pub async fn detail(
+    _: RequirePermission<RoleRead>,
+    State(state): State<AppState>,
+    Extension(Tenant(tenant)): Extension<Tenant>,
+    Path(id): Path<String>,
+) -> Result<Role> {
+    let role = find_role(&state, &tenant, &id).await?;
+
+    api_ok_with_data(role)
+}
+
+pub async fn create(
+    _: RequirePermission<RoleWrite>,
+    State(state): State<AppState>,
+    Extension(Tenant(tenant)): Extension<Tenant>,
+    Json(request): Json<RoleRequest>,
+) -> Result<()> {
+    request.validate()?;
+    state.catalogue.validate(&request.permissions)?;
+
+    let repository = RoleRepository::new();
+    if repository
+        .find_by_name(&tenant, &request.name, &state.db)
+        .await?
+        .is_some()
+    {
+        return Err(Error::BadRequest("角色名称已存在".to_string()));
+    }
+
+    let id = state.id_gen.next_id().await?;
+    let role = Role::new(
+        id,
+        tenant,
+        request.name,
+        request.permissions,
+        request.data_scope,
+    );
+    repository.create(&role, &state.db).await?;
+
+    state.rbac.reset().await?;
+
+    api_ok()
+}
+
+pub async fn update(
+    _: RequirePermission<RoleWrite>,
+    State(state): State<AppState>,
+    Extension(Tenant(tenant)): Extension<Tenant>,
+    Path(id): Path<String>,
+    Json(request): Json<RoleRequest>,
+) -> Result<()> {
+    request.validate()?;
+    state.catalogue.validate(&request.permissions)?;
+
+    let repository = RoleRepository::new();
+    let mut role = find_role(&state, &tenant, &id).await?;
+
+    if role.name != request.name {
+        if let Some(other) = repository
+            .find_by_name(&tenant, &request.name, &state.db)
+            .await?
+        {
+            if other.base.id != role.base.id {
+                return Err(Error::BadRequest("角色名称已存在".to_string()));
+            }
+        }
+
+        // users reference roles by name
+        if UserRepository::new()
+            .count_by_role(&tenant, &role.name, &state.db)
+            .await?
+            > 0
+        {
+            return Err(Error::BadRequest(
+                "角色已分配给用户, 不能修改名称".to_string(),
+            ));
+        }
+    }
+
+    role.name = request.name;
+    role.permissions = request.permissions;
+    role.data_scope = request.data_scope;
+    repository.update(&role, &state.db).await?;
+
+    state.rbac.reset().await?;
+
+    api_ok()
+}
+
+pub async fn delete(
+    _: RequirePermission<RoleWrite>,
+    State(state): State<AppState>,
+    Extension(Tenant(tenant)): Extension<Tenant>,
+    Path(id): Path<String>,
+) -> Result<()> {
+    let mut role = find_role(&state, &tenant, &id).await?;
+
+    let assigned = UserRepository::new()
+        .count_by_role(&tenant, &role.name, &state.db)
+        .await?;
+    if assigned > 0 {
+        return Err(Error::BadRequest(format!(
+            "角色仍分配给{}个用户, 不能删除",
+            assigned
+        )));
+    }
+
+    role.base.delete();
+    RoleRepository::new().update(&role, &state.db).await?;
+
+    state.rbac.reset().await?;
+
+    api_ok()
+}
+
+/// returns the role if it exists in the tenant
+async fn find_role(state: &AppState, tenant: &str, id: &str) -> std::result::Result<Role, Error> {
+    let role = RoleRepository::new()
+        .find_by_id(id, &state.db)
+        .await?
+        .filter(|role| role.tenant == tenant)
+        .ok_or(Error::NotFound)?;
+
+    Ok(role)
+}
@@ -0,0 +1,51 @@
+use mongodb::bson::{doc, Document};
+use serde::Deserialize;
+use validator::Validate;
+
+use crate::{
+    database::repositories::{
+        default_page, default_page_size, department::escape_regex, IFilter, IPaginator,
+    },
+    domain::role::{DataScope, RouteItem},
+    impl_paginator,
+};
+
+#[derive(Deserialize)]
+pub struct RoleSearchRequest {
+    #[serde(default = "default_page")]
+    pub page: i64,
+    #[serde(default = "default_page_size")]
+    pub page_size: i64,
+    pub name: Option<String>,
+    /// the active tenant, filled in by the handler
+    #[serde(skip)]
+    pub tenant: String,
+}
+
+impl IFilter for RoleSearchRequest {
+    fn to_doc(&self) -> Document {
+        let mut filter = doc! { "deleted_at": 0, "tenant": self.tenant.clone() };
+
+        if let Some(name) = &self.name {
+            if !name.is_empty() {
+                filter.insert(
+                    "name",
+                    doc! { "$regex": escape_regex(name), "$options": "i" },
+                );
+            }
+        }
+
+        filter
+    }
+}
+
+impl_paginator!(RoleSearchRequest);
+
+#[derive(Deserialize, Validate)]
+pub struct RoleRequest {
+    #[validate(length(min = 1, max = 32, message = "角色名称长度为1-32"))]
+    pub name: String,
+    pub permissions: Vec<RouteItem>,
+    #[serde(default)]
+    pub data_scope: DataScope,
+}
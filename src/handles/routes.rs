@@ -2,8 +2,8 @@ use std::time::Duration;
 
 use axum::{
     middleware,
-    routing::{get, post},
-    Router,
+    routing::{delete, get, post, put},
+    Extension, Router,
 };
 use tower::ServiceBuilder;
 use tower_http::{
@@ -13,7 +13,17 @@ use tower_http::{
 
 use crate::config::AppState;
 
-use super::{login, middlewares};
+use super::{
+    catalogue::CatalogueRouter,
+    department, health, invitation, login, login_history, masking, middlewares,
+    permissions::{
+        DepartmentRead, DepartmentWrite, LoginHistoryRead, Permission, RbacRead, RbacWrite,
+        RecyclePurge, RecycleRead, RecycleRestore, RoleRead, RoleWrite, UserAgeRead, UserApprove,
+        UserGrant, UserImport, UserInvite, UserPersonalData, UserPhoneRead, UserRead, UserStatus,
+        UserWrite,
+    },
+    personal_data, rbac, recycle, registration, role, user,
+};
 
 /// Creates the main application router with all the routes configured.
 ///
@@ -28,30 +38,38 @@ use super::{login, middlewares};
 /// # Returns
 ///
 /// Returns a `Router` with all the routes and middleware configured.
-pub fn create(app_state: AppState) -> Router {
+pub fn create(mut app_state: AppState) -> Router {
+    let (permission_routes, catalogue) = permission_routes().into_parts();
+    app_state.catalogue = catalogue;
+
     // build our application with a single route
-    let app = Router::new()
+    let mut app = Router::new()
         .route("/login", post(login::login))
-        .nest("/", secret_routes(app_state.clone()))
-        .with_state(app_state)
-        .layer(
-            ServiceBuilder::new()
-                .layer(TimeoutLayer::new(Duration::from_secs(30)))
-                .layer(
-                    CorsLayer::new()
-                        .allow_origin(Any)
-                        .allow_methods(Any)
-                        .allow_headers(Any),
-                ),
+        .route("/health/ready", get(health::ready))
+        .route("/invitations/accept", post(invitation::accept))
+        .route("/register", post(registration::register))
+        .route("/register/captcha", get(registration::captcha))
+        .route("/register/verify", post(registration::verify))
+        .nest("/", secret_routes(app_state.clone(), permission_routes));
+
+    for realm in &app_state.config.realms {
+        app = app.nest(
+            &format!("/{}", realm.name),
+            realm_routes(app_state.clone(), &realm.name),
         );
-    app
-}
+    }
 
-fn rbac_routes(state: AppState) -> Router<AppState> {
-    Router::new().route_layer(middleware::from_fn_with_state(
-        state.clone(),
-        middlewares::rbac,
-    ))
+    let app = app.with_state(app_state).layer(
+        ServiceBuilder::new()
+            .layer(TimeoutLayer::new(Duration::from_secs(30)))
+            .layer(
+                CorsLayer::new()
+                    .allow_origin(Any)
+                    .allow_methods(Any)
+                    .allow_headers(Any),
+            ),
+    );
+    app
 }
 
 /// Defines secret routes that require authorization.
@@ -62,16 +80,241 @@ fn rbac_routes(state: AppState) -> Router<AppState> {
 /// # Arguments
 ///
 /// * `state` - The application state containing shared resources and configurations.
+/// * `permission_routes` - The routes that can be granted to roles.
 ///
 /// # Returns
 ///
 /// Returns a `Router` configured with secret routes.
-fn secret_routes(state: AppState) -> Router<AppState> {
+fn secret_routes(state: AppState, permission_routes: Router<AppState>) -> Router<AppState> {
     Router::new()
         .route("/test-auth", get({ "test-auth" }))
-        // .nest("/", rbac_routes(state.clone()))
+        .route("/me", get(login::me))
+        .route("/me/personal-data", get(personal_data::export_mine))
+        .route("/login-history/mine", get(login_history::mine))
+        .merge(permission_routes)
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            masking::masking,
+        ))
         .route_layer(middleware::from_fn_with_state(
             state.clone(),
             middlewares::authorization,
         ))
 }
+
+/// Defines the routes that can be granted to roles.
+///
+/// Every route lists the capabilities its handlers require with `RequirePermission`,
+/// the resulting catalogue is used to validate role permissions. A rule on the path of
+/// a route is checked together with the capabilities.
+///
+/// # Returns
+///
+/// Returns a `CatalogueRouter` holding the routes and their permissions.
+fn permission_routes() -> CatalogueRouter {
+    CatalogueRouter::new()
+        .route(
+            "/rbac/policy-version",
+            get(rbac::policy_version),
+            &[RbacRead::item()],
+        )
+        .route("/rbac/catalogue", get(rbac::catalogue), &[RbacRead::item()])
+        .route("/rbac/explain", post(rbac::explain), &[RbacRead::item()])
+        .route("/rbac/export", get(rbac::export), &[RbacRead::item()])
+        .route("/rbac/import", post(rbac::import), &[RbacWrite::item()])
+        .route(
+            "/rbac/expirations",
+            get(rbac::expirations),
+            &[RbacRead::item()],
+        )
+        .route(
+            "/rbac/rules",
+            get(rbac::rules)
+                .post(rbac::add_rule)
+                .delete(rbac::remove_rule),
+            &[RbacRead::item(), RbacWrite::item()],
+        )
+        .route(
+            "/roles",
+            get(role::list).post(role::create),
+            &[RoleRead::item(), RoleWrite::item()],
+        )
+        .route(
+            "/roles/:id",
+            get(role::detail).put(role::update).delete(role::delete),
+            &[RoleRead::item(), RoleWrite::item()],
+        )
+        .route(
+            "/users",
+            get(user::list).post(user::create),
+            &[
+                UserRead::item(),
+                UserWrite::item(),
+                UserGrant::item(),
+                UserAgeRead::item(),
+                UserPhoneRead::item(),
+            ],
+        )
+        .route(
+            "/users/import",
+            post(user::import),
+            &[UserImport::item(), UserGrant::item()],
+        )
+        .route(
+            "/users/:id",
+            get(user::detail).put(user::update).delete(user::delete),
+            &[
+                UserRead::item(),
+                UserWrite::item(),
+                UserGrant::item(),
+                UserAgeRead::item(),
+                UserPhoneRead::item(),
+            ],
+        )
+        .route(
+            "/users/:id/disable",
+            post(user::disable),
+            &[UserWrite::item()],
+        )
+        .route(
+            "/users/:id/enable",
+            post(user::enable),
+            &[UserWrite::item()],
+        )
+        .route(
+            "/users/:id/status",
+            get(user::status).post(user::set_status),
+            &[UserRead::item(), UserStatus::item()],
+        )
+        .route("/users/:id/roles", post(user::grant), &[UserGrant::item()])
+        .route(
+            "/users/:id/personal-data",
+            get(personal_data::export),
+            &[UserPersonalData::item()],
+        )
+        .route(
+            "/users/:id/erase",
+            post(personal_data::erase),
+            &[UserPersonalData::item()],
+        )
+        .route(
+            "/invitations",
+            get(invitation::list).post(invitation::invite),
+            &[UserInvite::item(), UserGrant::item()],
+        )
+        .route(
+            "/invitations/:id",
+            delete(invitation::revoke),
+            &[UserInvite::item()],
+        )
+        .route(
+            "/invitations/:id/resend",
+            post(invitation::resend),
+            &[UserInvite::item()],
+        )
+        .route(
+            "/registrations",
+            get(registration::list),
+            &[UserApprove::item()],
+        )
+        .route(
+            "/registrations/:id/approve",
+            post(registration::approve),
+            &[UserApprove::item()],
+        )
+        .route(
+            "/registrations/:id/reject",
+            post(registration::reject),
+            &[UserApprove::item()],
+        )
+        .route(
+            "/login-history",
+            get(login_history::list),
+            &[LoginHistoryRead::item()],
+        )
+        .route(
+            "/departments",
+            post(department::create),
+            &[DepartmentWrite::item()],
+        )
+        .route(
+            "/departments/tree",
+            get(department::tree),
+            &[DepartmentRead::item()],
+        )
+        .route(
+            "/departments/:id",
+            put(department::update).delete(department::delete),
+            &[DepartmentWrite::item()],
+        )
+        .route(
+            "/departments/:id/subtree",
+            get(department::subtree),
+            &[DepartmentRead::item()],
+        )
+        .route(
+            "/departments/:id/users",
+            get(department::users),
+            &[
+                DepartmentRead::item(),
+                UserAgeRead::item(),
+                UserPhoneRead::item(),
+            ],
+        )
+        .route(
+            "/departments/:id/move",
+            post(department::move_to),
+            &[DepartmentWrite::item()],
+        )
+        .route(
+            "/departments/:id/merge",
+            post(department::merge),
+            &[DepartmentWrite::item()],
+        )
+        .route(
+            "/departments/:id/heads",
+            put(department::set_heads),
+            &[DepartmentWrite::item()],
+        )
+        .route(
+            "/recycle-bin/:collection",
+            get(recycle::list),
+            &[RecycleRead::item()],
+        )
+        .route(
+            "/recycle-bin/:collection/:id",
+            delete(recycle::purge),
+            &[RecyclePurge::item()],
+        )
+        .route(
+            "/recycle-bin/:collection/:id/restore",
+            post(recycle::restore),
+            &[RecycleRestore::item()],
+        )
+}
+
+/// Defines the routes of a user realm other than the staff.
+///
+/// The realm is attached to every request, so the login looks the user up in the
+/// realm's collection, the authorization middleware refuses tokens of other realms and
+/// permissions are checked by the realm's rbac engine.
+///
+/// The roles and users of a realm are not managed through the api, they are defined by
+/// the realm's policy file or written to its collections directly.
+fn realm_routes(state: AppState, realm: &str) -> Router<AppState> {
+    let secret = Router::new()
+        .route("/me", get(login::me))
+        .route_layer(middleware::from_fn_with_state(
+            state.clone(),
+            masking::masking,
+        ))
+        .route_layer(middleware::from_fn_with_state(
+            state,
+            middlewares::authorization,
+        ));
+
+    Router::new()
+        .route("/login", post(login::login))
+        .merge(secret)
+        .layer(Extension(middlewares::Realm(realm.to_string())))
+}
@@ -0,0 +1,5 @@
+mod types;
+mod user_handles;
+
+pub use types::{UserResponse, UserSearchRequest};
+pub use user_handles::*;
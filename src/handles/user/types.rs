@@ -0,0 +1,403 @@
+use chrono::Utc;
+use mongodb::bson::{doc, Document};
+use serde::{Deserialize, Serialize};
+use validator::{Validate, ValidationErrors, ValidationErrorsKind};
+
+use crate::{
+    database::repositories::{
+        default_page, default_page_size, department::escape_regex, user::UserRepository, IFilter,
+        IPaginator,
+    },
+    domain::{
+        common::DEFAULT_TENANT,
+        user::{AccountStatus, RoleAssignment, StatusChange, User},
+        user_import::ImportRow,
+    },
+    handles::{
+        masking,
+        permissions::{UserAgeRead, UserPhoneRead},
+    },
+    impl_paginator,
+};
+
+#[derive(Deserialize)]
+pub struct UserSearchRequest {
+    #[serde(default = "default_page")]
+    pub page: i64,
+    #[serde(default = "default_page_size")]
+    pub page_size: i64,
+    pub name: Option<String>,
+    /// name of a role held in the active tenant
+    pub role: Option<String>,
+    pub status: Option<AccountStatus>,
+    /// unix timestamps bounding the creation time, both inclusive
+    pub created_start: Option<u64>,
+    pub created_end: Option<u64>,
+    /// the active tenant, filled in by the handler. only its members are listed
+    #[serde(skip)]
+    pub tenant: String,
+    /// departments the users belong to, filled in by the department handlers
+    #[serde(skip)]
+    pub department_ids: Option<Vec<String>>,
+}
+
+impl IFilter for UserSearchRequest {
+    fn to_doc(&self) -> Document {
+        let mut filter = doc! { "deleted_at": 0 };
+
+        if let Some(name) = &self.name {
+            if !name.is_empty() {
+                filter.insert(
+                    "name",
+                    doc! { "$regex": escape_regex(name), "$options": "i" },
+                );
+            }
+        }
+
+        match self.role.as_ref().filter(|role| !role.is_empty()) {
+            Some(role) => {
+                let mut conditions = vec![doc! {
+                    "tenant_roles": { "$elemMatch": { "tenant": self.tenant.clone(), "role_name": role } }
+                }];
+                if self.tenant == DEFAULT_TENANT {
+                    conditions.push(doc! { "role_name": role });
+                }
+
+                filter.insert("$or", conditions);
+            }
+            // holding the role already makes the user a member of the tenant
+            None => filter.extend(UserRepository::membership(&self.tenant)),
+        }
+
+        if let Some(status) = self.status {
+            filter.insert("status", status.as_str());
+        }
+
+        let mut created_at = Document::new();
+        if let Some(start) = self.created_start {
+            created_at.insert("$gte", start as i64);
+        }
+        if let Some(end) = self.created_end {
+            created_at.insert("$lte", end as i64);
+        }
+        if !created_at.is_empty() {
+            filter.insert("created_at", created_at);
+        }
+
+        if let Some(department_ids) = &self.department_ids {
+            filter.insert("department_id", doc! { "$in": department_ids });
+        }
+
+        filter
+    }
+}
+
+impl_paginator!(UserSearchRequest);
+
+/// a user as returned to the api, sensitive fields depend on the caller's permissions
+#[derive(Serialize)]
+pub struct UserResponse {
+    pub id: String,
+    pub account: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "masking::lacks::<UserAgeRead, _>")]
+    pub age: u8,
+    pub avatar: String,
+    #[serde(serialize_with = "masking::mask::<UserPhoneRead, _>")]
+    pub phone: String,
+    pub email: String,
+    pub status: AccountStatus,
+    pub expires_at: Option<u64>,
+    pub last_login_at: u64,
+    pub department_id: String,
+    pub role_name: String,
+    pub tenant_roles: Vec<RoleAssignment>,
+    pub created_at: u64,
+}
+
+impl UserResponse {
+    /// returns the user as seen from the tenant, the roles of other tenants are left out
+    pub fn in_tenant(user: User, tenant: &str) -> Self {
+        let status = user.status_at(Utc::now().timestamp() as u64);
+        let role_name = match tenant == DEFAULT_TENANT {
+            true => user.role_name,
+            false => String::new(),
+        };
+        let tenant_roles = user
+            .tenant_roles
+            .into_iter()
+            .filter(|item| item.tenant == tenant)
+            .collect();
+
+        UserResponse {
+            id: user.base.id,
+            account: user.secret.account,
+            name: user.name,
+            age: user.age,
+            avatar: user.avatar,
+            phone: user.phone,
+            email: user.email,
+            status,
+            expires_at: user.expires_at,
+            last_login_at: user.last_login_at,
+            department_id: user.department_id,
+            role_name,
+            tenant_roles,
+            created_at: user.base.created_at,
+        }
+    }
+}
+
+#[derive(Deserialize, Validate)]
+pub struct CreateUserRequest {
+    #[validate(length(min = 3, max = 32, message = "账号长度为3-32"))]
+    pub account: String,
+    #[validate(length(min = 6, max = 64, message = "密码长度为6-64"))]
+    pub password: String,
+    #[serde(flatten)]
+    #[validate]
+    pub profile: UserRequest,
+}
+
+#[derive(Deserialize, Validate)]
+pub struct UserRequest {
+    #[validate(length(min = 1, max = 32, message = "姓名长度为1-32"))]
+    pub name: String,
+    #[serde(default)]
+    pub age: u8,
+    #[serde(default)]
+    pub avatar: String,
+    #[serde(default)]
+    pub phone: String,
+    #[serde(default)]
+    pub email: String,
+    #[serde(default)]
+    pub department_id: String,
+    /// unix timestamp the account expires, never if not set
+    #[serde(default)]
+    pub expires_at: Option<u64>,
+    /// roles in the active tenant, the roles are not changed on update if not set
+    pub roles: Option<Vec<String>>,
+}
+
+/// grant a role in the caller's active tenant to a user, optionally for a period
+#[derive(Deserialize, Validate)]
+pub struct GrantRequest {
+    #[validate(length(min = 1, message = "角色名称不能为空"))]
+    pub role_name: String,
+    /// unix timestamp the grant takes effect, immediately if not set
+    pub starts_at: Option<u64>,
+    /// unix timestamp the grant ends, never if not set
+    pub expires_at: Option<u64>,
+}
+
+/// move an account to another status
+#[derive(Deserialize, Validate)]
+pub struct StatusRequest {
+    pub status: AccountStatus,
+    #[serde(default)]
+    #[validate(length(max = 200, message = "原因长度不能超过200"))]
+    pub reason: String,
+}
+
+#[derive(Serialize)]
+pub struct StatusResponse {
+    pub status: AccountStatus,
+    pub expires_at: Option<u64>,
+    /// the transitions of the status, newest first
+    pub changes: Vec<StatusChange>,
+}
+
+impl From<User> for StatusResponse {
+    fn from(user: User) -> Self {
+        let status = user.status_at(Utc::now().timestamp() as u64);
+        let mut changes = user.status_changes;
+        changes.reverse();
+
+        StatusResponse {
+            status,
+            expires_at: user.expires_at,
+            changes,
+        }
+    }
+}
+
+impl TryFrom<&ImportRow> for CreateUserRequest {
+    type Error = String;
+
+    fn try_from(row: &ImportRow) -> std::result::Result<Self, Self::Error> {
+        let age = match row.age.is_empty() {
+            true => 0,
+            false => row
+                .age
+                .parse()
+                .map_err(|_| format!("年龄格式错误: {}", row.age))?,
+        };
+
+        Ok(CreateUserRequest {
+            account: row.account.clone(),
+            password: row.password.clone(),
+            profile: UserRequest {
+                name: row.name.clone(),
+                age,
+                avatar: String::new(),
+                phone: row.phone.clone(),
+                email: row.email.clone(),
+                department_id: row.department_id.clone(),
+                expires_at: None,
+                roles: Some(row.role_names()),
+            },
+        })
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ImportQuery {
+    /// only validate the rows, without creating the users
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+/// the problems of a row that is not imported
+#[derive(Serialize)]
+pub struct RowError {
+    pub line: usize,
+    pub account: String,
+    pub errors: Vec<String>,
+}
+
+#[derive(Serialize)]
+pub struct ImportReport {
+    pub dry_run: bool,
+    /// rows in the file
+    pub total: usize,
+    /// users created, or that would be created in a dry run
+    pub imported: usize,
+    pub errors: Vec<RowError>,
+}
+
+/// returns the messages of the validation errors, nested structs included
+pub fn validation_messages(errors: &ValidationErrors) -> Vec<String> {
+    let mut messages = vec![];
+
+    for (field, kind) in errors.errors() {
+        match kind {
+            ValidationErrorsKind::Field(items) => {
+                messages.extend(items.iter().map(|item| match &item.message {
+                    Some(message) => message.to_string(),
+                    None => format!("{}: {}", field, item.code),
+                }))
+            }
+            ValidationErrorsKind::Struct(nested) => {
+                messages.extend(validation_messages(nested));
+            }
+            ValidationErrorsKind::List(items) => {
+                for nested in items.values() {
+                    messages.extend(validation_messages(nested));
+                }
+            }
+        }
+    }
+
+    messages.sort();
+    messages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_search_filter() {
+        let request = UserSearchRequest {
+            page: 1,
+            page_size: 20,
+            name: None,
+            role: Some("admin".to_string()),
+            status: Some(AccountStatus::Active),
+            created_start: Some(100),
+            created_end: None,
+            tenant: "shop".to_string(),
+            department_ids: None,
+        };
+
+        assert_eq!(
+            request.to_doc(),
+            doc! {
+                "deleted_at": 0,
+                "$or": [{ "tenant_roles": { "$elemMatch": { "tenant": "shop", "role_name": "admin" } } }],
+                "status": "active",
+                "created_at": { "$gte": 100_i64 },
+            }
+        );
+
+        let request = UserSearchRequest {
+            role: None,
+            status: None,
+            created_start: None,
+            ..request
+        };
+        assert_eq!(
+            request.to_doc(),
+            doc! { "deleted_at": 0, "tenant_roles.tenant": "shop" }
+        );
+
+        let request = UserSearchRequest {
+            name: Some("a.b".to_string()),
+            ..request
+        };
+        assert_eq!(
+            request.to_doc(),
+            doc! {
+                "deleted_at": 0,
+                "name": { "$regex": "a\\.b", "$options": "i" },
+                "tenant_roles.tenant": "shop",
+            }
+        );
+    }
+
+    #[test]
+    fn test_response_leaves_out_other_tenants() {
+        let mut user = User::default();
+        user.grant(DEFAULT_TENANT, "admin");
+        user.grant("shop", "clerk");
+        user.grant("bar", "clerk");
+
+        let response = UserResponse::in_tenant(user.clone(), "shop");
+        assert!(response.role_name.is_empty());
+        assert_eq!(response.tenant_roles.len(), 1);
+        assert_eq!(response.tenant_roles[0].tenant, "shop");
+
+        let response = UserResponse::in_tenant(user, DEFAULT_TENANT);
+        assert_eq!(response.role_name, "admin");
+        assert!(response.tenant_roles.is_empty());
+    }
+
+    #[test]
+    fn test_import_row_validation() {
+        let row = ImportRow {
+            line: 2,
+            account: "al".to_string(),
+            password: "123".to_string(),
+            name: "Alice".to_string(),
+            roles: "admin|editor".to_string(),
+            ..Default::default()
+        };
+
+        let request = CreateUserRequest::try_from(&row).unwrap();
+        assert_eq!(
+            request.profile.roles,
+            Some(vec!["admin".to_string(), "editor".to_string()])
+        );
+        assert_eq!(
+            validation_messages(&request.validate().unwrap_err()),
+            vec!["密码长度为6-64", "账号长度为3-32"]
+        );
+
+        let row = ImportRow {
+            age: "abc".to_string(),
+            ..row
+        };
+        assert!(CreateUserRequest::try_from(&row).is_err());
+    }
+}
@@ -0,0 +1,617 @@
+use std::collections::HashSet;
+
+use axum::{
+    extract::{Multipart, Path, Query, State},
+    Extension, Json,
+};
+use chrono::Utc;
+use validator::Validate;
+
+use crate::{
+    config::AppState,
+    database::repositories::{
+        department::DepartmentRepository, role::RoleRepository, scope::ResolvedScope,
+        user::UserRepository, Collection,
+    },
+    domain::{
+        common::{Secret, DEFAULT_TENANT},
+        user::{AccountStatus, User},
+        user_import::{self, ImportFormat},
+        BaseModel,
+    },
+    handles::{
+        middlewares::{DataScope, Tenant, UserID},
+        permissions::{RequirePermission, UserGrant, UserImport, UserRead, UserStatus, UserWrite},
+        response::{api_ok, api_ok_with_data},
+    },
+};
+
+use super::super::errors::{Error, Result};
+
+use super::types::{
+    validation_messages, CreateUserRequest, GrantRequest, ImportQuery, ImportReport, RowError,
+    StatusRequest, StatusResponse, UserRequest, UserResponse, UserSearchRequest,
+};
+
+/// list the users inside the caller's data scope.
+pub async fn list(
+    _: RequirePermission<UserRead>,
+    State(state): State<AppState>,
+    Extension(Tenant(tenant)): Extension<Tenant>,
+    DataScope(scope): DataScope,
+    Query(mut request): Query<UserSearchRequest>,
+) -> Result<Collection<UserResponse>> {
+    request.tenant = tenant.clone();
+
+    let users = UserRepository::new()
+        .search_scoped(&state.db, &request, &scope)
+        .await?;
+
+    api_ok_with_data(Collection {
+        items: users
+            .items
+            .into_iter()
+            .map(|user| UserResponse::in_tenant(user, &tenant))
+            .collect(),
+        total: users.total,
+    })
+}
+
+pub async fn detail(
+    _: RequirePermission<UserRead>,
+    State(state): State<AppState>,
+    Extension(Tenant(tenant)): Extension<Tenant>,
+    DataScope(scope): DataScope,
+    Path(id): Path<String>,
+) -> Result<UserResponse> {
+    let user = find_user(&state, &tenant, &scope, &id).await?;
+
+    api_ok_with_data(UserResponse::in_tenant(user, &tenant))
+}
+
+/// create an active user holding the requested roles in the active tenant.
+///
+/// giving roles needs the permission to grant them.
+pub async fn create(
+    _: RequirePermission<UserWrite>,
+    grant: Option<RequirePermission<UserGrant>>,
+    State(state): State<AppState>,
+    Extension(Tenant(tenant)): Extension<Tenant>,
+    Json(request): Json<CreateUserRequest>,
+) -> Result<()> {
+    request.validate()?;
+    check_grant(
+        &grant,
+        request
+            .profile
+            .roles
+            .as_ref()
+            .is_some_and(|roles| !roles.is_empty()),
+    )?;
+
+    let repository = UserRepository::new();
+    if repository
+        .find_by_account(&request.account, &state.db)
+        .await?
+        .is_some()
+    {
+        return Err(Error::BadRequest("账号已存在".to_string()));
+    }
+
+    let roles = request.profile.roles.clone().unwrap_or_default();
+    check_member(&tenant, &roles).map_err(Error::BadRequest)?;
+    check_roles(&state, &tenant, &roles).await?;
+    check_department(&state, &tenant, &request.profile.department_id).await?;
+
+    let id = state.id_gen.next_id().await?;
+    let mut user = User {
+        base: BaseModel::new(id),
+        secret: Secret::new(request.account, request.password)?,
+        status: AccountStatus::Active,
+        ..Default::default()
+    };
+    apply_profile(&mut user, request.profile);
+    user.set_roles_in(&tenant, &roles);
+    repository.create(&user, &state.db).await?;
+
+    if !roles.is_empty() {
+        state.rbac.reset().await?;
+    }
+
+    api_ok()
+}
+
+/// update the profile of a user, and the roles in the active tenant if they are set.
+///
+/// changing roles needs the permission to grant them. the profile of a user shared with
+/// other tenants can not be changed, only the roles.
+pub async fn update(
+    _: RequirePermission<UserWrite>,
+    grant: Option<RequirePermission<UserGrant>>,
+    State(state): State<AppState>,
+    Extension(Tenant(tenant)): Extension<Tenant>,
+    DataScope(scope): DataScope,
+    Path(id): Path<String>,
+    Json(request): Json<UserRequest>,
+) -> Result<()> {
+    request.validate()?;
+
+    let mut user = find_user(&state, &tenant, &scope, &id).await?;
+    if changes_profile(&user, &request) {
+        check_owned(&user, &tenant)?;
+    }
+    if let Some(roles) = &request.roles {
+        let mut before = user.roles_in(&tenant);
+        let mut after = roles.clone();
+        before.sort();
+        after.sort();
+        after.dedup();
+        check_grant(&grant, before != after)?;
+    }
+    if user.department_id != request.department_id {
+        check_department(&state, &tenant, &request.department_id).await?;
+    }
+
+    let roles_changed = match &request.roles {
+        Some(roles) => {
+            check_member(&tenant, roles).map_err(Error::BadRequest)?;
+            check_roles(&state, &tenant, roles).await?;
+
+            let before = user.roles_in(&tenant);
+            user.set_roles_in(&tenant, roles);
+            before != user.roles_in(&tenant)
+        }
+        None => false,
+    };
+
+    apply_profile(&mut user, request);
+    UserRepository::new().update(&user, &state.db).await?;
+
+    if roles_changed {
+        state.rbac.reset().await?;
+    }
+
+    api_ok()
+}
+
+/// a disabled user can not sign in
+pub async fn disable(
+    _: RequirePermission<UserWrite>,
+    State(state): State<AppState>,
+    Extension(UserID(user_id)): Extension<UserID>,
+    Extension(Tenant(tenant)): Extension<Tenant>,
+    DataScope(scope): DataScope,
+    Path(id): Path<String>,
+) -> Result<()> {
+    transition(
+        &state,
+        &tenant,
+        &scope,
+        &id,
+        AccountStatus::Disabled,
+        "",
+        &user_id,
+    )
+    .await
+}
+
+pub async fn enable(
+    _: RequirePermission<UserWrite>,
+    State(state): State<AppState>,
+    Extension(UserID(user_id)): Extension<UserID>,
+    Extension(Tenant(tenant)): Extension<Tenant>,
+    DataScope(scope): DataScope,
+    Path(id): Path<String>,
+) -> Result<()> {
+    transition(
+        &state,
+        &tenant,
+        &scope,
+        &id,
+        AccountStatus::Active,
+        "",
+        &user_id,
+    )
+    .await
+}
+
+/// returns the status of the account and its transitions
+pub async fn status(
+    _: RequirePermission<UserRead>,
+    State(state): State<AppState>,
+    Extension(Tenant(tenant)): Extension<Tenant>,
+    DataScope(scope): DataScope,
+    Path(id): Path<String>,
+) -> Result<StatusResponse> {
+    let user = find_user(&state, &tenant, &scope, &id).await?;
+
+    api_ok_with_data(StatusResponse::from(user))
+}
+
+/// move the account to another status, the caller is recorded with the reason.
+pub async fn set_status(
+    _: RequirePermission<UserStatus>,
+    State(state): State<AppState>,
+    Extension(UserID(user_id)): Extension<UserID>,
+    Extension(Tenant(tenant)): Extension<Tenant>,
+    DataScope(scope): DataScope,
+    Path(id): Path<String>,
+    Json(request): Json<StatusRequest>,
+) -> Result<()> {
+    request.validate()?;
+
+    transition(
+        &state,
+        &tenant,
+        &scope,
+        &id,
+        request.status,
+        &request.reason,
+        &user_id,
+    )
+    .await
+}
+
+/// delete the user, a user shared with other tenants only loses the roles of the active tenant
+pub async fn delete(
+    _: RequirePermission<UserWrite>,
+    State(state): State<AppState>,
+    Extension(UserID(user_id)): Extension<UserID>,
+    Extension(Tenant(tenant)): Extension<Tenant>,
+    DataScope(scope): DataScope,
+    Path(id): Path<String>,
+) -> Result<()> {
+    if id == user_id {
+        return Err(Error::BadRequest("不能删除自己".to_string()));
+    }
+
+    let mut user = find_user(&state, &tenant, &scope, &id).await?;
+    let had_roles = !user.assignments().is_empty();
+
+    match user.belongs_only_to(&tenant) {
+        true => user.base.delete(),
+        false => user.set_roles_in(&tenant, &[]),
+    }
+    UserRepository::new().update(&user, &state.db).await?;
+
+    if had_roles {
+        state.rbac.reset().await?;
+    }
+
+    api_ok()
+}
+
+/// grant a role in the active tenant to a user, a previous grant of the same role is replaced.
+pub async fn grant(
+    _: RequirePermission<UserGrant>,
+    State(state): State<AppState>,
+    Extension(Tenant(tenant)): Extension<Tenant>,
+    DataScope(scope): DataScope,
+    Path(id): Path<String>,
+    Json(request): Json<GrantRequest>,
+) -> Result<()> {
+    request.validate()?;
+
+    let now = Utc::now().timestamp() as u64;
+    if let Some(expires_at) = request.expires_at {
+        if expires_at <= now || expires_at <= request.starts_at.unwrap_or(0) {
+            return Err(Error::BadRequest(
+                "过期时间必须晚于当前时间和生效时间".to_string(),
+            ));
+        }
+    }
+
+    if RoleRepository::new()
+        .find_by_name(&tenant, &request.role_name, &state.db)
+        .await?
+        .is_none()
+    {
+        return Err(Error::BadRequest("角色不存在".to_string()));
+    }
+
+    let mut user = find_user(&state, &tenant, &scope, &id).await?;
+
+    user.grant_between(
+        &tenant,
+        &request.role_name,
+        request.starts_at,
+        request.expires_at,
+    );
+    UserRepository::new().update(&user, &state.db).await?;
+
+    state.rbac.reset().await?;
+
+    api_ok()
+}
+
+/// create users from the `file` field of a multipart upload, a CSV or XLSX file.
+///
+/// every row is validated like a single user creation, the valid rows are created in one
+/// transaction holding their roles in the active tenant, and the invalid rows are reported.
+/// with `dry_run` nothing is created.
+pub async fn import(
+    _: RequirePermission<UserImport>,
+    grant: Option<RequirePermission<UserGrant>>,
+    State(state): State<AppState>,
+    Extension(Tenant(tenant)): Extension<Tenant>,
+    Query(query): Query<ImportQuery>,
+    mut multipart: Multipart,
+) -> Result<ImportReport> {
+    let mut upload = None;
+    while let Some(field) = multipart.next_field().await? {
+        if field.name() == Some("file") {
+            let file_name = field.file_name().unwrap_or_default().to_string();
+            upload = Some((file_name, field.bytes().await?));
+            break;
+        }
+    }
+
+    let (file_name, content) = upload.ok_or(Error::BadRequest("请上传导入文件".to_string()))?;
+    let format = ImportFormat::from_file_name(&file_name)
+        .ok_or(Error::BadRequest("仅支持CSV和XLSX文件".to_string()))?;
+    let rows =
+        user_import::parse(&content, format).map_err(|err| Error::BadRequest(err.to_string()))?;
+    check_grant(&grant, rows.iter().any(|row| !row.role_names().is_empty()))?;
+
+    let repository = UserRepository::new();
+    let accounts: Vec<String> = rows.iter().map(|row| row.account.clone()).collect();
+    let existing: HashSet<String> = repository
+        .find_by_accounts(&accounts, &state.db)
+        .await?
+        .into_iter()
+        .map(|user| user.secret.account)
+        .collect();
+
+    let mut role_names: Vec<String> = rows.iter().flat_map(|row| row.role_names()).collect();
+    role_names.sort();
+    role_names.dedup();
+    let roles: HashSet<String> = RoleRepository::new()
+        .find_by_names(&tenant, &role_names, &state.db)
+        .await?
+        .into_iter()
+        .map(|role| role.name)
+        .collect();
+
+    let department_ids: Vec<String> = rows
+        .iter()
+        .filter(|row| !row.department_id.is_empty())
+        .map(|row| row.department_id.clone())
+        .collect();
+    let departments: HashSet<String> = DepartmentRepository::new()
+        .find_by_ids(&department_ids, &state.db)
+        .await?
+        .into_iter()
+        .filter(|department| department.tenant == tenant)
+        .map(|department| department.base.id)
+        .collect();
+
+    let mut seen = HashSet::new();
+    let mut users = vec![];
+    let mut errors = vec![];
+    for row in rows.iter() {
+        let mut messages = vec![];
+
+        if existing.contains(&row.account) {
+            messages.push("账号已存在".to_string());
+        }
+        if !row.account.is_empty() && !seen.insert(row.account.clone()) {
+            messages.push("账号在文件中重复".to_string());
+        }
+
+        let unknown: Vec<String> = row
+            .role_names()
+            .into_iter()
+            .filter(|name| !roles.contains(name))
+            .collect();
+        if !unknown.is_empty() {
+            messages.push(format!("角色不存在: {}", unknown.join(", ")));
+        }
+
+        if let Err(message) = check_member(&tenant, &row.role_names()) {
+            messages.push(message);
+        }
+
+        if !row.department_id.is_empty() && !departments.contains(&row.department_id) {
+            messages.push("部门不存在".to_string());
+        }
+
+        match CreateUserRequest::try_from(row) {
+            Ok(request) => {
+                if let Err(err) = request.validate() {
+                    messages.extend(validation_messages(&err));
+                }
+
+                if messages.is_empty() {
+                    let roles = request.profile.roles.clone().unwrap_or_default();
+                    let mut user = User {
+                        secret: Secret::new(request.account, request.password)?,
+                        status: AccountStatus::Active,
+                        ..Default::default()
+                    };
+                    apply_profile(&mut user, request.profile);
+                    user.set_roles_in(&tenant, &roles);
+                    users.push(user);
+                }
+            }
+            Err(message) => messages.push(message),
+        }
+
+        if !messages.is_empty() {
+            errors.push(RowError {
+                line: row.line,
+                account: row.account.clone(),
+                errors: messages,
+            });
+        }
+    }
+
+    let report = ImportReport {
+        dry_run: query.dry_run,
+        total: rows.len(),
+        imported: users.len(),
+        errors,
+    };
+
+    if query.dry_run || users.is_empty() {
+        return api_ok_with_data(report);
+    }
+
+    for user in users.iter_mut() {
+        user.base = BaseModel::new(state.id_gen.next_id().await?);
+    }
+
+    let mut session = state.client.start_session(None).await?;
+    session.start_transaction(None).await?;
+    if let Err(err) = repository
+        .create_many_with_session(&users, &state.db, &mut session)
+        .await
+    {
+        session.abort_transaction().await?;
+        return Err(err.into());
+    }
+    session.commit_transaction().await?;
+
+    if users.iter().any(|user| !user.assignments().is_empty()) {
+        state.rbac.reset().await?;
+    }
+
+    api_ok_with_data(report)
+}
+
+async fn transition(
+    state: &AppState,
+    tenant: &str,
+    scope: &ResolvedScope,
+    id: &str,
+    to: AccountStatus,
+    reason: &str,
+    actor: &str,
+) -> Result<()> {
+    if id == actor && to != AccountStatus::Active {
+        return Err(Error::BadRequest("不能修改自己的账号状态".to_string()));
+    }
+
+    let mut user = find_user(state, tenant, scope, id).await?;
+    check_owned(&user, tenant)?;
+    user.transition(to, reason, actor, Utc::now().timestamp() as u64)
+        .map_err(|err| Error::BadRequest(err.to_string()))?;
+    UserRepository::new().update(&user, &state.db).await?;
+
+    api_ok()
+}
+
+/// returns the user if it is a member of the active tenant inside the caller's data scope
+async fn find_user(
+    state: &AppState,
+    tenant: &str,
+    scope: &ResolvedScope,
+    id: &str,
+) -> std::result::Result<User, Error> {
+    let user = UserRepository::new()
+        .find_by_id_scoped(id, &state.db, scope)
+        .await?
+        .filter(|user| user.belongs_to(tenant))
+        .ok_or(Error::NotFound)?;
+
+    Ok(user)
+}
+
+/// the account of a user shared with other tenants is not changed by the admins of one of them
+fn check_owned(user: &User, tenant: &str) -> std::result::Result<(), Error> {
+    if !user.belongs_only_to(tenant) {
+        return Err(Error::BadRequest(
+            "用户同时属于其他租户, 只能修改其在当前租户的角色".to_string(),
+        ));
+    }
+
+    Ok(())
+}
+
+/// returns true if the request changes the profile of the user, the roles aside
+fn changes_profile(user: &User, request: &UserRequest) -> bool {
+    user.name != request.name
+        || user.age != request.age
+        || user.avatar != request.avatar
+        || user.phone != request.phone
+        || user.email != request.email
+        || user.department_id != request.department_id
+        || user.expires_at != request.expires_at
+}
+
+/// roles are only changed by callers allowed to grant them
+pub(crate) fn check_grant(
+    grant: &Option<RequirePermission<UserGrant>>,
+    changes_roles: bool,
+) -> std::result::Result<(), Error> {
+    if changes_roles && grant.is_none() {
+        return Err(Error::Forbidden);
+    }
+
+    Ok(())
+}
+
+/// users of a tenant other than the default one are its members through their roles
+fn check_member(tenant: &str, roles: &[String]) -> std::result::Result<(), String> {
+    if tenant != DEFAULT_TENANT && roles.is_empty() {
+        return Err("用户必须在当前租户持有角色".to_string());
+    }
+
+    Ok(())
+}
+
+/// check that every role exists in the tenant
+pub(crate) async fn check_roles(
+    state: &AppState,
+    tenant: &str,
+    roles: &[String],
+) -> std::result::Result<(), Error> {
+    let found = RoleRepository::new()
+        .find_by_names(tenant, roles, &state.db)
+        .await?;
+
+    let unknown: Vec<&str> = roles
+        .iter()
+        .filter(|name| !found.iter().any(|role| &role.name == *name))
+        .map(|name| name.as_str())
+        .collect();
+
+    if !unknown.is_empty() {
+        return Err(Error::BadRequest(format!(
+            "角色不存在: {}",
+            unknown.join(", ")
+        )));
+    }
+
+    Ok(())
+}
+
+/// check that the department exists in the tenant, users may belong to no department
+pub(crate) async fn check_department(
+    state: &AppState,
+    tenant: &str,
+    department_id: &str,
+) -> std::result::Result<(), Error> {
+    if department_id.is_empty() {
+        return Ok(());
+    }
+
+    let exists = DepartmentRepository::new()
+        .find_by_id(department_id, &state.db)
+        .await?
+        .is_some_and(|department| department.tenant == tenant);
+    if !exists {
+        return Err(Error::BadRequest("部门不存在".to_string()));
+    }
+
+    Ok(())
+}
+
+fn apply_profile(user: &mut User, request: UserRequest) {
+    user.name = request.name;
+    user.age = request.age;
+    user.avatar = request.avatar;
+    user.phone = request.phone;
+    user.email = request.email;
+    user.department_id = request.department_id;
+    user.expires_at = request.expires_at;
+}
@@ -6,7 +6,10 @@ use jwt::{Claims, RegisteredClaims, SignWithKey, VerifyWithKey};
 use serde_json::Value;
 use sha2::Sha256;
 
-use crate::domain::user::User;
+use crate::domain::{
+    common::{DEFAULT_TENANT, STAFF_REALM},
+    user::User,
+};
 
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
@@ -29,11 +32,21 @@ pub struct TokenPayload {
     pub id: String,
     pub account: String,
     pub role: String,
+    /// tenant the token was issued for
+    pub tenant: String,
+    /// realm of the user, sent as the audience of the token
+    pub realm: String,
 }
 
 impl TokenPayload {
-    pub fn new(id: String, account: String, role: String) -> Self {
-        Self { id, account, role }
+    pub fn new(id: String, account: String, role: String, tenant: String) -> Self {
+        Self {
+            id,
+            account,
+            role,
+            tenant,
+            realm: STAFF_REALM.to_string(),
+        }
     }
 }
 
@@ -43,6 +56,7 @@ impl Into<BTreeMap<String, Value>> for TokenPayload {
         out.insert("id".to_string(), self.id.into());
         out.insert("account".to_string(), self.account.into());
         out.insert("role".to_string(), self.role.into());
+        out.insert("tenant".to_string(), self.tenant.into());
 
         out
     }
@@ -65,7 +79,19 @@ impl From<BTreeMap<String, Value>> for TokenPayload {
             _ => String::new(),
         };
 
-        TokenPayload { id, account, role }
+        // tokens issued before tenants were introduced belong to the default tenant
+        let tenant = match payload_map.get("tenant") {
+            Some(Value::String(s)) => s.clone(),
+            _ => DEFAULT_TENANT.to_string(),
+        };
+
+        TokenPayload {
+            id,
+            account,
+            role,
+            tenant,
+            realm: STAFF_REALM.to_string(),
+        }
     }
 }
 
@@ -75,6 +101,8 @@ impl From<User> for TokenPayload {
             id: user.base.id,
             account: user.secret.account,
             role: user.role_name,
+            tenant: DEFAULT_TENANT.to_string(),
+            realm: STAFF_REALM.to_string(),
         }
     }
 }
@@ -100,6 +128,7 @@ impl Engine {
 
         let mut claims = Claims::new(RegisteredClaims {
             subject: Some(infomation.id.clone()),
+            audience: Some(infomation.realm.clone()),
             expiration: Some(expiration as u64),
             ..Default::default()
         });
@@ -124,6 +153,35 @@ impl Engine {
             .subject
             .ok_or(Error::TokenCreationFailed)?;
 
-        Ok(TokenPayload::from(claims.private))
+        let mut payload = TokenPayload::from(claims.private);
+        // tokens issued before realms were introduced belong to the staff
+        if let Some(audience) = claims.registered.audience {
+            payload.realm = audience;
+        }
+
+        Ok(payload)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_keeps_realm() {
+        let engine = Engine::new("secret".to_string()).unwrap();
+        let mut payload = TokenPayload::new(
+            "1".to_string(),
+            "alice".to_string(),
+            String::new(),
+            DEFAULT_TENANT.to_string(),
+        );
+        payload.realm = "customer".to_string();
+
+        let token = engine.create_token(payload).unwrap();
+        let payload = engine.verify_token(&token).unwrap();
+
+        assert_eq!(payload.realm, "customer");
+        assert_eq!(payload.account, "alice");
     }
 }
@@ -1,14 +1,21 @@
 mod actors;
+mod commands;
 mod config;
 mod database;
 mod domain;
 mod handles;
 mod jwt;
 
-use actors::{id_gen::IDGeneratorHandler, rbac::RbacActorHandler};
-use clap::Parser;
-use config::{AppConfig, AppState};
-use database::repositories;
+use std::{collections::HashMap, net::SocketAddr, sync::Arc, time::Duration};
+
+use actors::{
+    file_fetcher::FilePolicyFetcher, id_gen::IDGeneratorHandler, rbac::RbacActorHandler,
+    sender::ConsoleSender,
+};
+use clap::{Parser, Subcommand};
+use config::{AppConfig, AppState, RealmState};
+use database::repositories::{self, collection_names};
+use domain::policy_bundle::{BundleFormat, ConflictStrategy};
 use handles::routes;
 use mongodb::{Client, Database};
 
@@ -18,6 +25,36 @@ struct Args {
     /// Name of the person to greet
     #[arg(short, long, default_value = "./config.toml")]
     config_path: String,
+
+    /// Run a command instead of the server
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Export all roles and user-role assignments to a bundle file
+    Export {
+        #[arg(short, long)]
+        output: String,
+        /// json or csv
+        #[arg(short, long, default_value = "json")]
+        format: BundleFormat,
+    },
+    /// Import roles and user-role assignments from a bundle file
+    Import {
+        #[arg(short, long)]
+        input: String,
+        /// json or csv
+        #[arg(short, long, default_value = "json")]
+        format: BundleFormat,
+        /// skip, overwrite or merge existing roles and assignments
+        #[arg(short, long, default_value = "skip")]
+        strategy: ConflictStrategy,
+        /// Print the changes without importing them
+        #[arg(long)]
+        dry_run: bool,
+    },
 }
 
 #[tokio::main]
@@ -26,6 +63,8 @@ async fn main() {
     let app_cfg = config::load_config(&args.config_path)
         .await
         .expect("Failed to load config");
+    app_cfg.check_realms().expect("Invalid realms");
+    app_cfg.rbac.check().expect("Invalid rbac config");
 
     let (client, db) = database::mongodb::connect(&app_cfg.database.uri, &app_cfg.database.db_name)
         .await
@@ -33,16 +72,175 @@ async fn main() {
 
     let id_gen = IDGeneratorHandler::new();
 
+    if let Some(command) = args.command {
+        let result = match command {
+            Command::Export { output, format } => commands::export(&db, &output, format).await,
+            Command::Import {
+                input,
+                format,
+                strategy,
+                dry_run,
+            } => commands::import(&db, &id_gen, &input, format, strategy, dry_run).await,
+        };
+
+        if let Err(err) = result {
+            eprintln!("{}", err);
+            std::process::exit(1);
+        }
+
+        return;
+    }
+
     let jwt_engine = jwt::Engine::new(app_cfg.secret.clone()).expect("Failed to create jwt engine");
 
-    let rbac_engine = RbacActorHandler::new(
+    let rbac_model = actors::rbac::load_model(app_cfg.rbac.model_path.as_deref())
+        .await
+        .expect("Failed to load rbac model");
+
+    let rbac_adapter = app_cfg
+        .rbac
+        .adapter
+        .then(|| actors::mongo_adapter::MongoAdapter::new(&db));
+
+    let rbac_engine = match &app_cfg.rbac.policy_path {
+        Some(path) => {
+            let fetcher = FilePolicyFetcher::open(path)
+                .await
+                .expect("Failed to load rbac policy file");
+
+            RbacActorHandler::new(
+                db.clone(),
+                rbac_model,
+                app_cfg.rbac.backoff(),
+                rbac_adapter,
+                fetcher.clone(),
+                fetcher,
+            )
+            .await
+        }
+        None => {
+            RbacActorHandler::new(
+                db.clone(),
+                rbac_model,
+                app_cfg.rbac.backoff(),
+                rbac_adapter,
+                repositories::role::RoleRepository::new(),
+                repositories::user::UserRepository::new(),
+            )
+            .await
+        }
+    }
+    .expect("Failed to create rbac engine");
+
+    actors::rbac_watcher::spawn(
         db.clone(),
-        repositories::role::RoleRepository::new(),
-        repositories::user::UserRepository::new(),
-    )
-    .await;
+        rbac_engine.clone(),
+        vec![
+            collection_names::USER.to_string(),
+            collection_names::ROLE.to_string(),
+            collection_names::CASBIN_RULE.to_string(),
+        ],
+        Duration::from_secs(app_cfg.rbac.poll_interval),
+        app_cfg.rbac.backoff(),
+    );
+
+    // grants of policy files are not stored in the database
+    if app_cfg.rbac.policy_path.is_none() {
+        actors::grant_sweeper::spawn(
+            db.clone(),
+            repositories::user::UserRepository::new(),
+            rbac_engine.clone(),
+            Duration::from_secs(app_cfg.rbac.sweep_interval),
+        );
+    }
 
-    start(app_cfg, client, db, id_gen, jwt_engine, rbac_engine).await
+    match repositories::user::UserRepository::new()
+        .migrate_status(&db)
+        .await
+    {
+        Ok(0) => {}
+        Ok(migrated) => println!("migrated the status of {} users", migrated),
+        Err(err) => println!("failed to migrate the status of users: {}", err),
+    }
+
+    if let Err(err) = repositories::login_record::LoginRecordRepository::new()
+        .ensure_ttl_index(app_cfg.login_history.retention(), &db)
+        .await
+    {
+        println!(
+            "failed to create the ttl index of the login history: {}",
+            err
+        );
+    }
+
+    let mut realms = HashMap::new();
+    for realm in &app_cfg.realms {
+        realms.insert(
+            realm.name.clone(),
+            start_realm(realm, &app_cfg.rbac, &db).await,
+        );
+    }
+
+    start(app_cfg, client, db, id_gen, jwt_engine, rbac_engine, realms).await
+}
+
+/// create the rbac engine of the realm and keep it in sync with the realm's collections
+async fn start_realm(realm: &config::Realm, rbac: &config::Rbac, db: &Database) -> RealmState {
+    let model = actors::rbac::load_model(realm.model_path.as_deref())
+        .await
+        .expect("Failed to load rbac model");
+
+    let engine = match &realm.policy_path {
+        Some(path) => {
+            let fetcher = FilePolicyFetcher::open(path)
+                .await
+                .expect("Failed to load rbac policy file");
+
+            RbacActorHandler::new(
+                db.clone(),
+                model,
+                rbac.backoff(),
+                None,
+                fetcher.clone(),
+                fetcher,
+            )
+            .await
+        }
+        None => {
+            RbacActorHandler::new(
+                db.clone(),
+                model,
+                rbac.backoff(),
+                None,
+                repositories::role::RoleRepository::with_collection(&realm.role_collection),
+                repositories::user::UserRepository::with_collection(&realm.collection),
+            )
+            .await
+        }
+    }
+    .expect("Failed to create rbac engine");
+
+    actors::rbac_watcher::spawn(
+        db.clone(),
+        engine.clone(),
+        vec![realm.collection.clone(), realm.role_collection.clone()],
+        Duration::from_secs(rbac.poll_interval),
+        rbac.backoff(),
+    );
+
+    if realm.policy_path.is_none() {
+        actors::grant_sweeper::spawn(
+            db.clone(),
+            repositories::user::UserRepository::with_collection(&realm.collection),
+            engine.clone(),
+            Duration::from_secs(rbac.sweep_interval),
+        );
+    }
+
+    RealmState {
+        config: realm.clone(),
+        rbac: engine,
+    }
 }
 
 async fn start(
@@ -52,6 +250,7 @@ async fn start(
     id_gen: IDGeneratorHandler,
     jwt_engine: jwt::Engine,
     rbac: RbacActorHandler,
+    realms: HashMap<String, RealmState>,
 ) {
     let state = AppState {
         client,
@@ -60,6 +259,9 @@ async fn start(
         id_gen,
         jwt: jwt_engine,
         rbac,
+        realms: Arc::new(realms),
+        catalogue: Default::default(),
+        sender: Arc::new(ConsoleSender),
     };
 
     let app = routes::create(state);
@@ -69,5 +271,10 @@ async fn start(
         .await
         .unwrap();
 
-    axum::serve(listener, app).await.unwrap();
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .unwrap();
 }